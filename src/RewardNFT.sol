@@ -5,6 +5,8 @@ import "../lib/openzeppelin-contracts/contracts/token/ERC721/ERC721.sol";
 import "../lib/openzeppelin-contracts/contracts/token/ERC721/extensions/ERC721URIStorage.sol";
 import "../lib/openzeppelin-contracts/contracts/token/ERC721/extensions/ERC721Burnable.sol";
 import "../lib/openzeppelin-contracts/contracts/access/Ownable.sol";
+import "../lib/openzeppelin-contracts/contracts/utils/cryptography/ECDSA.sol";
+import "../lib/openzeppelin-contracts/contracts/utils/cryptography/EIP712.sol";
 import "./Reward.sol";
 
 /**
@@ -13,7 +15,22 @@ import "./Reward.sol";
  * the reward ERC20 tokens to the owner of the NFT. An ERC721 token was chosen
  * for simplicity, however, this could also be an ERC1155 token.
  */
-contract RewardNFT is ERC721, ERC721URIStorage, ERC721Burnable, Ownable {
+contract RewardNFT is
+    ERC721,
+    ERC721URIStorage,
+    ERC721Burnable,
+    Ownable,
+    EIP712
+{
+    /**
+     * @dev The EIP-712 type hash of a burn authorization, signed by a token
+     * owner to let anyone burn the token on their behalf.
+     */
+    bytes32 private constant BURN_AUTHORIZATION_TYPEHASH =
+        keccak256(
+            "BurnAuthorization(address owner,uint256 tokenId,uint256 nonce,uint256 deadline)"
+        );
+
     /**
      * @dev The reward token that will be minted when the NFT is burned.
      */
@@ -23,6 +40,11 @@ contract RewardNFT is ERC721, ERC721URIStorage, ERC721Burnable, Ownable {
      * minted when the NFT is burned.
      */
     mapping(uint256 => uint256) private tokenRewardValues;
+    /**
+     * @dev The nonce the next burn authorization of each owner must use.
+     * Each nonce is used once, so a signed authorization cannot be replayed.
+     */
+    mapping(address => uint256) public burnNonces;
 
     /**
      * @dev constructor
@@ -32,7 +54,11 @@ contract RewardNFT is ERC721, ERC721URIStorage, ERC721Burnable, Ownable {
     constructor(
         address initialOwner,
         address _rewardToken
-    ) ERC721("RewardNFT", "RWNFT") Ownable(initialOwner) {
+    )
+        ERC721("RewardNFT", "RWNFT")
+        Ownable(initialOwner)
+        EIP712("RewardNFT", "1")
+    {
         rewardToken = Reward(_rewardToken);
     }
 
@@ -132,10 +158,58 @@ contract RewardNFT is ERC721, ERC721URIStorage, ERC721Burnable, Ownable {
     function burn(
         uint256 tokenId
     ) public virtual override(ERC721Burnable) onlyOwnerOrTokenOwner(tokenId) {
+        _redeem(tokenId);
+    }
+
+    /**
+     * @dev Burn the NFT on behalf of its owner, who signed an EIP-712
+     * authorization for the burn. The reward ERC20 tokens are minted to the
+     * owner, while the caller pays for the gas.
+     *
+     * @param owner address of the owner of the NFT
+     * @param tokenId id of the NFT to be burned
+     * @param nonce the owner's next burn authorization nonce
+     * @param deadline timestamp after which the authorization is invalid
+     * @param signature the owner's signature of the authorization
+     */
+    function burnWithAuthorization(
+        address owner,
+        uint256 tokenId,
+        uint256 nonce,
+        uint256 deadline,
+        bytes memory signature
+    ) public tokenExists(tokenId) {
+        require(block.timestamp <= deadline, "Error: Authorization expired!");
+        require(ownerOf(tokenId) == owner, "Error: Invalid authorization!");
+        require(nonce == burnNonces[owner], "Error: Invalid nonce!");
+
+        bytes32 structHash = keccak256(
+            abi.encode(
+                BURN_AUTHORIZATION_TYPEHASH,
+                owner,
+                tokenId,
+                nonce,
+                deadline
+            )
+        );
+        address signer = ECDSA.recover(_hashTypedDataV4(structHash), signature);
+        require(signer == owner, "Error: Invalid authorization!");
+
         /**
-         * When burning the NFT, we will want to mint the reward
-         * ERC20 tokens to the owner of the NFT
+         * Use up the nonce before burning
          */
+        burnNonces[owner] = nonce + 1;
+
+        _redeem(tokenId);
+    }
+
+    /**
+     * @dev Burn the NFT and mint its reward value as ERC20 tokens to the
+     * owner of the NFT
+     *
+     * @param tokenId id of the NFT to be burned
+     */
+    function _redeem(uint256 tokenId) internal {
         address owner = ownerOf(tokenId);
 
         /**
@@ -153,4 +227,83 @@ contract RewardNFT is ERC721, ERC721URIStorage, ERC721Burnable, Ownable {
          */
         tokenRewardValues[tokenId] = 0;
     }
+
+    /**
+     * @dev Burn the NFT without minting the reward ERC20 tokens, e.g. when
+     * the reward expired before it was redeemed or was revoked
+     *
+     * @param tokenId id of the NFT to be forfeited
+     */
+    function forfeit(uint256 tokenId) public onlyOwner tokenExists(tokenId) {
+        /**
+         * Burn the NFT
+         */
+        _burn(tokenId);
+
+        /**
+         * The reward value is never minted
+         */
+        tokenRewardValues[tokenId] = 0;
+    }
+
+    /**
+     * @dev Burn the NFT and carry its reward value over to new NFTs owned by
+     * the same address. Part of the value can be redeemed at the same time,
+     * minting the reward ERC20 tokens to the owner of the NFT.
+     *
+     * @param tokenId id of the NFT to be split
+     * @param redeemValue reward value minted as ERC20 tokens
+     * @param newTokenIds ids of the new NFTs
+     * @param values reward values of the new NFTs
+     * @param uris URIs of the new NFTs
+     */
+    function split(
+        uint256 tokenId,
+        uint256 redeemValue,
+        uint256[] memory newTokenIds,
+        uint256[] memory values,
+        string[] memory uris
+    ) public onlyOwnerOrTokenOwner(tokenId) {
+        require(
+            newTokenIds.length > 0 &&
+                newTokenIds.length == values.length &&
+                newTokenIds.length == uris.length,
+            "Error: Invalid split!"
+        );
+
+        /**
+         * The new NFTs and the redeemed value must add up to the value of the
+         * NFT, so no value is created or lost
+         */
+        uint256 total = redeemValue;
+        for (uint256 i = 0; i < values.length; i++) {
+            require(values[i] > 0, "Error: Invalid split!");
+            total += values[i];
+        }
+        require(total == tokenRewardValues[tokenId], "Error: Invalid split!");
+
+        address owner = ownerOf(tokenId);
+
+        /**
+         * Burn the NFT
+         */
+        _burn(tokenId);
+        tokenRewardValues[tokenId] = 0;
+
+        /**
+         * Mint the redeemed part of the reward
+         */
+        if (redeemValue > 0) {
+            rewardToken.mint(owner, redeemValue);
+        }
+
+        /**
+         * Mint the new NFTs with the rest of the reward value
+         */
+        for (uint256 i = 0; i < newTokenIds.length; i++) {
+            _safeMint(owner, newTokenIds[i]);
+            _setTokenURI(newTokenIds[i], uris[i]);
+            _setRewardValue(newTokenIds[i], values[i]);
+        }
+    }
 }
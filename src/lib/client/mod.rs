@@ -0,0 +1,481 @@
+use std::time::Duration;
+
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::core::amount::Amount;
+use crate::core::retry::RetryPolicy;
+use crate::services::job::JobResult;
+use crate::services::reward::{RedeemOptions, RedeemResult, RewardInfo};
+use crate::services::user::{
+    BalanceResult, HistoryOptions, HistoryResult, RegisterRequest, RegisterResult, RewardRequest,
+    RewardResult,
+};
+use crate::services::ErrorDetails;
+use crate::utils::router::get_base_path;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Invalid request: {0}")]
+    Validation(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalance(String),
+    #[error("Chain unavailable: {0}")]
+    ChainUnavailable(String),
+    /// The action was accepted with `202 Accepted` but is completed later,
+    /// so it must not be sent again. The id is the job of a reward, which
+    /// can be polled with `job`, or the outbox entry of another action.
+    #[error("Queued: {id}")]
+    Queued { id: Uuid },
+    /// Any other error returned by the API.
+    #[error("{kind} ({status}): {message}")]
+    Api {
+        status: u16,
+        kind: String,
+        message: String,
+    },
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl ClientError {
+    /// Map an error returned by the API to its variant.
+    pub fn from_response(status: StatusCode, error: ErrorDetails) -> Self {
+        match error.kind.as_str() {
+            "NotFoundError" => ClientError::NotFound(error.message),
+            "ValidationError" => ClientError::Validation(error.message),
+            "UnauthorizedError" => ClientError::Unauthorized(error.message),
+            "InsufficientBalanceError" => ClientError::InsufficientBalance(error.message),
+            "ChainUnavailableError" => ClientError::ChainUnavailable(error.message),
+            _ => ClientError::Api {
+                status: status.as_u16(),
+                kind: error.kind,
+                message: error.message,
+            },
+        }
+    }
+}
+
+/// NftestClient calls the nftest API.
+///
+/// Failed requests are retried according to the retry policy. Requests that
+/// only read are retried when the API cannot be reached, times out or is
+/// briefly unavailable, while requests that change something are only
+/// retried when the API could not be reached, so a reward is never issued or
+/// redeemed twice.
+#[derive(Clone, Debug)]
+pub struct NftestClient {
+    http: reqwest::Client,
+    /// The URL of the API, including the base path.
+    base_url: String,
+    /// The API key sent as a bearer token, if any.
+    api_key: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl NftestClient {
+    /// Create a client for the API served at a URL, such as
+    /// `http://localhost:3000`.
+    pub fn new(url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: format!("{}{}", url.trim_end_matches('/'), get_base_path()),
+            api_key: None,
+            retry: RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(200),
+                max_delay: Duration::from_secs(5),
+            },
+        }
+    }
+
+    /// Send an API key as a bearer token with every request.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Set how failed requests are retried.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Send requests with a configured HTTP client, such as one with a
+    /// timeout.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Register a user.
+    pub async fn register(&self, id: Uuid) -> Result<RegisterResult, ClientError> {
+        let request = RegisterRequest { id };
+
+        self.send(Method::POST, "/user", |r| r.json(&request)).await
+    }
+
+    /// Get the balance of a user.
+    pub async fn balance(&self, user_id: Uuid) -> Result<BalanceResult, ClientError> {
+        self.send(Method::GET, &format!("/user/{}/balance", user_id), |r| r)
+            .await
+    }
+
+    /// List the rewards of a user.
+    pub async fn rewards(&self, user_id: Uuid) -> Result<Vec<RewardInfo>, ClientError> {
+        self.send(Method::GET, &format!("/user/{}/rewards", user_id), |r| r)
+            .await
+    }
+
+    /// Get a page of the history of a user, newest first.
+    pub async fn history(
+        &self,
+        user_id: Uuid,
+        options: &HistoryOptions,
+    ) -> Result<HistoryResult, ClientError> {
+        self.send(Method::GET, &format!("/user/{}/history", user_id), |r| {
+            r.query(options)
+        })
+        .await
+    }
+
+    /// Issue a reward to a user and wait for its mint. If the mint could not
+    /// be sent yet the reward is queued as a job instead, which is returned
+    /// as `ClientError::Queued`.
+    pub async fn reward(
+        &self,
+        user_id: Uuid,
+        request: &RewardRequest,
+    ) -> Result<RewardResult, ClientError> {
+        self.send(Method::POST, &format!("/user/{}/reward", user_id), |r| {
+            r.json(request)
+        })
+        .await
+    }
+
+    /// Queue a reward for a user as a job, without waiting for its mint.
+    pub async fn reward_async(
+        &self,
+        user_id: Uuid,
+        request: &RewardRequest,
+    ) -> Result<JobResult, ClientError> {
+        let response = self
+            .send_request(Method::POST, &format!("/user/{}/reward", user_id), |r| {
+                r.query(&[("async", true)]).json(request)
+            })
+            .await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Get a queued reward job.
+    pub async fn job(&self, id: Uuid) -> Result<JobResult, ClientError> {
+        self.send(Method::GET, &format!("/jobs/{}", id), |r| r)
+            .await
+    }
+
+    /// Redeem a reward, or only part of its value if one is given. If the burn
+    /// could not be sent yet it is queued, which is returned as
+    /// `ClientError::Queued`.
+    pub async fn redeem(
+        &self,
+        reward_id: Uuid,
+        value: Option<Amount>,
+    ) -> Result<RedeemResult, ClientError> {
+        let options = value.map(RedeemOptions::partial).unwrap_or_default();
+
+        self.send(
+            Method::POST,
+            &format!("/reward/{}/redeem", reward_id),
+            |r| r.query(&options),
+        )
+        .await
+    }
+
+    /// Send a request to a path of the API and read the JSON response,
+    /// retrying failures according to the retry policy. An action accepted
+    /// to be completed later is returned as `ClientError::Queued`.
+    async fn send<T, F>(&self, method: Method, path: &str, build: F) -> Result<T, ClientError>
+    where
+        T: DeserializeOwned,
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        let response = self.send_request(method, path, build).await?;
+
+        if response.status() == StatusCode::ACCEPTED {
+            let queued: Queued = response.json().await?;
+
+            return Err(ClientError::Queued { id: queued.id });
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Send a request to a path of the API and return the successful
+    /// response, retrying failures according to the retry policy.
+    async fn send_request<F>(
+        &self,
+        method: Method,
+        path: &str,
+        build: F,
+    ) -> Result<reqwest::Response, ClientError>
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        let url = format!("{}{}", self.base_url, path);
+        let idempotent = method == Method::GET;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let mut request = build(self.http.request(method.clone(), &url));
+
+            if let Some(api_key) = &self.api_key {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", api_key));
+            }
+
+            let last_attempt = attempt >= self.retry.max_attempts;
+
+            match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    if last_attempt || !(idempotent && is_unavailable(response.status())) {
+                        return Err(read_error(response).await);
+                    }
+                }
+                Err(e) => {
+                    if last_attempt || !(e.is_connect() || idempotent && e.is_timeout()) {
+                        return Err(e.into());
+                    }
+                }
+            }
+
+            tokio::time::sleep(self.retry.delay(attempt)).await;
+        }
+    }
+}
+
+/// Queued is the part of a `202 Accepted` response shared by queued jobs and
+/// outbox entries.
+#[derive(Deserialize)]
+struct Queued {
+    id: Uuid,
+}
+
+/// If a status means the API is briefly unable to answer.
+fn is_unavailable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Read the error of a failed response.
+async fn read_error(response: reqwest::Response) -> ClientError {
+    let status = response.status();
+
+    match response.json::<ErrorDetails>().await {
+        Ok(error) => ClientError::from_response(status, error),
+        Err(_) => ClientError::Api {
+            status: status.as_u16(),
+            kind: "UnknownError".into(),
+            message: status.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use axum::http::{HeaderMap, StatusCode as AxumStatus};
+    use axum::routing::{get, post};
+    use axum::Router;
+
+    use crate::utils::router::init_router;
+
+    use super::*;
+
+    /// Serve a router on a local port, returning its URL.
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        url
+    }
+
+    fn retry(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        let client = NftestClient::new(&serve(init_router()).await);
+        let user_id = Uuid::new_v4();
+
+        assert!(client.register(user_id).await.unwrap().success);
+
+        let request = RewardRequest {
+            value: 1337.into(),
+            campaign_id: None,
+            expires_at: None,
+        };
+        let reward = client.reward(user_id, &request).await.unwrap();
+        let reward_id = Uuid::parse_str(&reward.id).unwrap();
+
+        assert!(reward.success);
+
+        let rewards = client.rewards(user_id).await.unwrap();
+
+        assert_eq!(rewards.len(), 1);
+        assert_eq!(rewards[0].value, "1337");
+
+        let redeemed = client.redeem(reward_id, None).await.unwrap();
+
+        assert_eq!(redeemed.id, reward_id);
+        assert_eq!(redeemed.reward, "1337");
+
+        // Part of the value of a reward can be redeemed
+        let reward = client.reward(user_id, &request).await.unwrap();
+        let redeemed = client
+            .redeem(Uuid::parse_str(&reward.id).unwrap(), Some(337.into()))
+            .await
+            .unwrap();
+
+        assert_eq!(redeemed.reward, "337");
+        assert!(redeemed.remainder.is_some());
+
+        let balance = client.balance(user_id).await.unwrap();
+
+        assert_eq!(balance.rewards.active, 1);
+
+        let history = client
+            .history(user_id, &HistoryOptions::default())
+            .await
+            .unwrap();
+
+        assert!(history.total > 0);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors() {
+        let client = NftestClient::new(&serve(init_router()).await);
+
+        assert!(matches!(
+            client.balance(Uuid::new_v4()).await,
+            Err(ClientError::NotFound(_))
+        ));
+
+        let user_id = Uuid::new_v4();
+
+        client.register(user_id).await.unwrap();
+
+        assert!(matches!(
+            client.register(user_id).await,
+            Err(ClientError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_retries() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let app = {
+            let calls = calls.clone();
+
+            Router::new().route(
+                "/api/v1/user/:id/rewards",
+                get(move |headers: HeaderMap| async move {
+                    let authorized = headers
+                        .get(AUTHORIZATION.as_str())
+                        .is_some_and(|value| value == "Bearer key");
+
+                    if !authorized {
+                        return (AxumStatus::UNAUTHORIZED, "{}".to_string());
+                    }
+
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 => (AxumStatus::SERVICE_UNAVAILABLE, "{}".to_string()),
+                        _ => (AxumStatus::OK, "[]".to_string()),
+                    }
+                }),
+            )
+        };
+        let url = serve(app).await;
+
+        // The API key is sent and an unavailable API is retried
+        let client = NftestClient::new(&url)
+            .with_api_key("key")
+            .with_retry(retry(2));
+
+        assert!(client.rewards(Uuid::new_v4()).await.unwrap().is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Errors without details are still reported
+        let client = NftestClient::new(&url).with_retry(retry(2));
+
+        assert!(matches!(
+            client.rewards(Uuid::new_v4()).await,
+            Err(ClientError::Api { status: 401, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_queued() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let job_id = Uuid::new_v4();
+        let app = {
+            let calls = calls.clone();
+
+            Router::new().route(
+                "/api/v1/user/:id/reward",
+                post(move || async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+
+                    let job = serde_json::json!({
+                        "id": job_id,
+                        "status": "Queued",
+                        "attempts": 0,
+                        "result": null,
+                        "error": null,
+                    });
+
+                    (AxumStatus::ACCEPTED, job.to_string())
+                }),
+            )
+        };
+        let client = NftestClient::new(&serve(app).await).with_retry(retry(3));
+        let request = RewardRequest {
+            value: 1337.into(),
+            campaign_id: None,
+            expires_at: None,
+        };
+
+        // A reward queued as a job is reported as queued and not sent again
+        assert!(matches!(
+            client.reward(Uuid::new_v4(), &request).await,
+            Err(ClientError::Queued { id }) if id == job_id
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // The job is the result of a reward queued on purpose
+        let job = client.reward_async(Uuid::new_v4(), &request).await.unwrap();
+
+        assert_eq!(job.id, job_id);
+    }
+}
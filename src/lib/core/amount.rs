@@ -0,0 +1,257 @@
+use std::fmt;
+
+use ethers::types::U256;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+use utoipa::openapi::schema::{ObjectBuilder, OneOfBuilder, Schema, SchemaType};
+use utoipa::openapi::RefOr;
+use utoipa::ToSchema;
+
+use super::reward::RewardError;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AmountError {
+    #[error("Invalid amount: {0}")]
+    Invalid(String),
+    #[error("Amount is too large")]
+    Overflow,
+    #[error("Amount has more than {decimals} decimal places")]
+    TooPrecise { decimals: u8 },
+}
+
+impl From<AmountError> for RewardError {
+    fn from(error: AmountError) -> Self {
+        RewardError::InvalidValue(error.to_string())
+    }
+}
+
+/// Amount is an amount of reward tokens in a request. It is either a raw
+/// amount in the smallest unit of the token, `{"raw": "2500"}`, or a decimal
+/// amount of whole tokens, `{"decimal": "2.5"}`, converted with the decimals
+/// of the token. A plain JSON number is read as a raw amount.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Amount {
+    Raw(String),
+    Decimal(String),
+}
+
+impl From<u128> for Amount {
+    fn from(value: u128) -> Self {
+        Amount::Raw(value.to_string())
+    }
+}
+
+impl Amount {
+    /// Convert the amount to the smallest unit of a token with the given
+    /// decimals.
+    pub fn to_units(&self, decimals: u8) -> Result<U256, AmountError> {
+        match self {
+            Amount::Raw(raw) => parse_integer(raw),
+            Amount::Decimal(decimal) => parse_decimal(decimal, decimals),
+        }
+    }
+
+    /// Convert the amount to the smallest unit of the reward token, reading
+    /// its decimals from the contract if needed.
+    pub async fn to_token_units(&self) -> Result<U256, RewardError> {
+        let decimals = match self {
+            Amount::Raw(_) => 0,
+            Amount::Decimal(_) => crate::models::balance::get_token_metadata().await?.decimals,
+        };
+
+        Ok(self.to_units(decimals)?)
+    }
+}
+
+/// Parse a string of decimal digits, failing if it does not fit a `U256`.
+fn parse_integer(digits: &str) -> Result<U256, AmountError> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(AmountError::Invalid(digits.to_string()));
+    }
+
+    U256::from_dec_str(digits).map_err(|_| AmountError::Overflow)
+}
+
+/// Parse a decimal number of whole tokens into the smallest unit. Digits
+/// past the decimals of the token are only allowed if they are zeros.
+fn parse_decimal(decimal: &str, decimals: u8) -> Result<U256, AmountError> {
+    let invalid = || AmountError::Invalid(decimal.to_string());
+    let (whole, fraction) = decimal.split_once('.').unwrap_or((decimal, ""));
+
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    let places = fraction.len();
+
+    if places > decimals as usize {
+        return Err(AmountError::TooPrecise { decimals });
+    }
+
+    let whole = match whole {
+        "" => U256::zero(),
+        whole => parse_integer(whole).map_err(|e| match e {
+            AmountError::Invalid(_) => invalid(),
+            e => e,
+        })?,
+    };
+    let fraction = match fraction {
+        "" => U256::zero(),
+        fraction => parse_integer(fraction).map_err(|_| invalid())?,
+    };
+    let unit = U256::from(10)
+        .checked_pow(U256::from(decimals))
+        .ok_or(AmountError::Overflow)?;
+    let scale = U256::from(10).pow(U256::from(decimals as usize - places));
+
+    whole
+        .checked_mul(unit)
+        .and_then(|whole| whole.checked_add(fraction * scale))
+        .ok_or(AmountError::Overflow)
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// The tagged forms of an amount.
+        #[derive(Deserialize)]
+        #[serde(rename_all = "lowercase", deny_unknown_fields)]
+        enum Tagged {
+            Raw(String),
+            Decimal(String),
+        }
+
+        struct AmountVisitor;
+
+        impl<'de> Visitor<'de> for AmountVisitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a raw amount or an object tagged with `raw` or `decimal`")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+                Ok(Amount::Raw(value.to_string()))
+            }
+
+            fn visit_u128<E: de::Error>(self, value: u128) -> Result<Amount, E> {
+                Ok(Amount::Raw(value.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Amount, A::Error> {
+                let tagged = Tagged::deserialize(de::value::MapAccessDeserializer::new(map))?;
+
+                Ok(match tagged {
+                    Tagged::Raw(raw) => Amount::Raw(raw),
+                    Tagged::Decimal(decimal) => Amount::Decimal(decimal),
+                })
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+impl<'s> ToSchema<'s> for Amount {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let tagged = |tag: &str, description: &str| {
+            ObjectBuilder::new()
+                .property(
+                    tag,
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .description(Some(description)),
+                )
+                .required(tag)
+        };
+        let schema = OneOfBuilder::new()
+            .description(Some(
+                "An amount of reward tokens, raw or as a decimal number of whole tokens",
+            ))
+            .item(
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::Integer)
+                    .minimum(Some(0.0))
+                    .description(Some("A raw amount in the smallest unit of the token")),
+            )
+            .item(tagged(
+                "raw",
+                "A raw amount in the smallest unit of the token, such as `2500`",
+            ))
+            .item(tagged(
+                "decimal",
+                "A decimal amount of whole tokens, such as `2.5`",
+            ));
+
+        ("Amount", schema.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn decimal(value: &str) -> Amount {
+        Amount::Decimal(value.to_string())
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let amount = |value| serde_json::from_value::<Amount>(value);
+
+        assert_eq!(amount(json!(1337)).unwrap(), Amount::from(1337));
+        assert_eq!(
+            amount(json!({ "raw": "1337" })).unwrap(),
+            Amount::from(1337)
+        );
+        assert_eq!(amount(json!({ "decimal": "2.5" })).unwrap(), decimal("2.5"));
+
+        // Untagged strings, negative numbers and unknown tags are rejected
+        assert!(amount(json!("2.5")).is_err());
+        assert!(amount(json!(-1)).is_err());
+        assert!(amount(json!({ "wei": "1" })).is_err());
+    }
+
+    #[test]
+    fn test_to_units() {
+        assert_eq!(decimal("2.5").to_units(18), Ok(U256::exp10(17) * 25));
+        assert_eq!(decimal("3").to_units(2), Ok(U256::from(300)));
+        assert_eq!(decimal(".5").to_units(1), Ok(U256::from(5)));
+        assert_eq!(decimal("1.2300").to_units(2), Ok(U256::from(123)));
+        assert_eq!(Amount::from(42).to_units(18), Ok(U256::from(42)));
+    }
+
+    #[test]
+    fn test_to_units_errors() {
+        assert_eq!(
+            decimal("1.234").to_units(2),
+            Err(AmountError::TooPrecise { decimals: 2 })
+        );
+        assert!(matches!(
+            decimal("1e18").to_units(18),
+            Err(AmountError::Invalid(_))
+        ));
+        assert!(matches!(
+            decimal(".").to_units(18),
+            Err(AmountError::Invalid(_))
+        ));
+        assert!(matches!(
+            decimal("-1").to_units(18),
+            Err(AmountError::Invalid(_))
+        ));
+
+        // Amounts that do not fit a U256 overflow
+        let max = U256::MAX.to_string();
+
+        assert_eq!(Amount::Raw(max.clone()).to_units(0), Ok(U256::MAX));
+        assert_eq!(
+            Amount::Raw(format!("{}0", max)).to_units(0),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(decimal(&max).to_units(1), Err(AmountError::Overflow));
+    }
+}
@@ -1,18 +1,272 @@
+use std::fmt;
+use std::future::Future;
 use std::io::Error;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 use dotenvy::dotenv;
-use ethers::abi::{Abi, Address};
-use ethers::contract::{Contract, ContractInstance, FunctionCall};
+use ethers::abi::{Abi, Address, ParamType, Token, Tokenize};
+use ethers::contract::{Contract, ContractError, ContractInstance, FunctionCall};
 use ethers::core::k256::ecdsa::SigningKey;
 use ethers::core::k256::SecretKey;
 use ethers::core::rand;
 use ethers::middleware::SignerMiddleware;
-use ethers::providers::{Http, Provider};
+use ethers::providers::{
+    Http, Middleware, MiddlewareError, PendingTransaction, Provider, ProviderError, RpcError,
+};
 use ethers::signers::{LocalWallet, Signer, Wallet};
-use ethers::types::U256;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{
+    BlockNumber, Bytes, Filter, Log, Signature, TransactionReceipt, TransactionRequest, H256, U256,
+};
+use ethers::utils::keccak256;
 use hex::FromHexError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::utils::config::parse_env;
+
+use super::eip712::BurnAuthorization;
+use super::gas::{Fees, GasMode, GasPolicy};
+use super::nonce::nonce_manager;
+use super::retry::{classify, rpc_circuit, with_retry, Classify, ErrorClass, RetryPolicy};
+
+/// The signature of the `Transfer` event shared by ERC20 and ERC721.
+pub const TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+
+/// The signature of the ERC721 `safeTransferFrom` without data. The function
+/// is overloaded, so it is called by selector.
+const SAFE_TRANSFER_FROM: &str = "safeTransferFrom(address,address,uint256)";
+
+/// The selector of the `Error(string)` revert used by `require`.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// The selector of the `Panic(uint256)` revert used by failed assertions.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// ChainError is a failed call to the chain, with known contract reverts
+/// decoded into their own variants.
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("Caller is not the owner of the token")]
+    NotTokenOwner,
+    #[error("Caller is not the owner of the contract")]
+    NotContractOwner,
+    #[error("Token does not exist")]
+    TokenNotFound,
+    #[error("Token already exists")]
+    TokenAlreadyExists,
+    #[error("Transaction reverted: {0}")]
+    Reverted(RevertReason),
+    #[error("Base fee {base_fee} exceeds the ceiling {ceiling}")]
+    FeeTooHigh { base_fee: U256, ceiling: U256 },
+    #[error("Chain unavailable: {0}")]
+    Unavailable(String),
+    #[error("Transaction nonce rejected: {0}")]
+    NonceRejected(String),
+    #[error("Transaction fee too low: {0}")]
+    Underpriced(String),
+    #[error("RPC error: {0}")]
+    RpcError(String),
+}
+
+impl ChainError {
+    /// If the call was executed and reverted, so sending it again will fail
+    pub fn is_revert(&self) -> bool {
+        matches!(
+            self,
+            ChainError::NotTokenOwner
+                | ChainError::NotContractOwner
+                | ChainError::TokenNotFound
+                | ChainError::TokenAlreadyExists
+                | ChainError::Reverted(_)
+        )
+    }
+
+    /// Get the error for a decoded revert reason
+    pub fn from_revert(reason: RevertReason) -> Self {
+        let known = match &reason {
+            RevertReason::Message(message) => match message.as_str() {
+                "Error: Token does not exist!" => Some(ChainError::TokenNotFound),
+                "Caller is not owner nor the contract owner" => Some(ChainError::NotTokenOwner),
+                _ => None,
+            },
+            // OpenZeppelin custom errors
+            RevertReason::Custom { name, .. } => match name.as_str() {
+                "ERC721NonexistentToken" => Some(ChainError::TokenNotFound),
+                "ERC721IncorrectOwner" | "ERC721InsufficientApproval" => {
+                    Some(ChainError::NotTokenOwner)
+                }
+                "OwnableUnauthorizedAccount" => Some(ChainError::NotContractOwner),
+                // Minting a token that exists reverts with the zero sender
+                "ERC721InvalidSender" => Some(ChainError::TokenAlreadyExists),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        known.unwrap_or(ChainError::Reverted(reason))
+    }
+
+    /// Convert a failed contract call, decoding the revert reason with the
+    /// errors declared in the contract ABI
+    fn from_contract_error<M: Middleware>(
+        error: ContractError<M>,
+        abi: &Abi,
+        context: &str,
+    ) -> Self {
+        if let Some(data) = error.as_revert() {
+            return ChainError::from_revert(RevertReason::decode(data, abi));
+        }
+
+        let class = match &error {
+            ContractError::MiddlewareError { e } => e.as_provider_error().map(classify_provider),
+            ContractError::ProviderError { e } => Some(classify_provider(e)),
+            _ => None,
+        };
+
+        ChainError::from_class(class, format!("{}: {}", context, error))
+    }
+
+    /// Convert a failed provider call, decoding the revert reason if the node
+    /// returned one
+    fn from_provider_error(error: ProviderError, context: &str) -> Self {
+        match RpcError::as_error_response(&error).and_then(|response| response.as_revert_data()) {
+            Some(data) => ChainError::from_revert(RevertReason::decode(&data, &Abi::default())),
+            None => ChainError::from_class(
+                Some(classify_provider(&error)),
+                format!("{}: {}", context, error),
+            ),
+        }
+    }
+
+    /// Convert a failed call through a middleware, such as the signer
+    fn from_middleware_error<E: MiddlewareError>(error: E, context: &str) -> Self {
+        ChainError::from_class(
+            error.as_provider_error().map(classify_provider),
+            format!("{}: {}", context, error),
+        )
+    }
+
+    /// Get the error for a failure that did not revert, by its class
+    fn from_class(class: Option<ErrorClass>, message: String) -> Self {
+        match class {
+            Some(ErrorClass::Connection) => ChainError::Unavailable(message),
+            Some(ErrorClass::Nonce) => ChainError::NonceRejected(message),
+            Some(ErrorClass::Underpriced) => ChainError::Underpriced(message),
+            _ => ChainError::RpcError(message),
+        }
+    }
+}
+
+impl Classify for ChainError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            ChainError::Unavailable(_) => ErrorClass::Connection,
+            ChainError::NonceRejected(_) => ErrorClass::Nonce,
+            ChainError::Underpriced(_) => ErrorClass::Underpriced,
+            error if error.is_revert() => ErrorClass::Revert,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+/// Classify a failed provider call from how it failed rather than from its
+/// message: requests that did not reach the node or timed out, answers that
+/// are not JSON-RPC such as the error page of a gateway, and the code of the
+/// JSON-RPC error the node answered with.
+fn classify_provider(error: &ProviderError) -> ErrorClass {
+    match error {
+        ProviderError::HTTPError(e) if e.is_timeout() || e.is_connect() || e.is_request() => {
+            ErrorClass::Connection
+        }
+        _ => match RpcError::as_error_response(error) {
+            Some(response) => classify(response.code, &response.message),
+            None if RpcError::as_serde_error(error).is_some() => ErrorClass::Connection,
+            None => ErrorClass::Other,
+        },
+    }
+}
+
+impl From<Error> for ChainError {
+    fn from(error: Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::ConnectionRefused => {
+                ChainError::Unavailable(error.to_string())
+            }
+            _ => ChainError::RpcError(error.to_string()),
+        }
+    }
+}
+
+/// RevertReason is the decoded reason a contract call reverted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RevertReason {
+    /// A `require` or `revert` with a message.
+    Message(String),
+    /// A failed assertion or arithmetic error, with its panic code.
+    Panic(U256),
+    /// A custom error declared in the contract ABI.
+    Custom { name: String, args: Vec<String> },
+    /// Revert data that could not be decoded.
+    Unknown(Bytes),
+}
+
+impl RevertReason {
+    /// Decode revert data, looking up custom errors in the contract ABI
+    pub fn decode(data: &[u8], abi: &Abi) -> Self {
+        if data.len() >= 4 {
+            let (selector, args) = data.split_at(4);
+
+            if selector == ERROR_SELECTOR {
+                if let Ok(Some(Token::String(message))) =
+                    ethers::abi::decode(&[ParamType::String], args).map(|t| t.into_iter().next())
+                {
+                    return RevertReason::Message(message);
+                }
+            } else if selector == PANIC_SELECTOR {
+                if let Ok(Some(Token::Uint(code))) =
+                    ethers::abi::decode(&[ParamType::Uint(256)], args).map(|t| t.into_iter().next())
+                {
+                    return RevertReason::Panic(code);
+                }
+            } else if let Some(error) = abi
+                .errors()
+                .find(|error| error.signature()[..4] == *selector)
+            {
+                if let Ok(tokens) = error.decode(args) {
+                    return RevertReason::Custom {
+                        name: error.name.clone(),
+                        args: tokens.iter().map(format_token).collect(),
+                    };
+                }
+            }
+        }
+
+        RevertReason::Unknown(Bytes::from(data.to_vec()))
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RevertReason::Message(message) => write!(f, "{}", message),
+            RevertReason::Panic(code) => write!(f, "panic code {:#x}", code),
+            RevertReason::Custom { name, args } => write!(f, "{}({})", name, args.join(", ")),
+            RevertReason::Unknown(data) => write!(f, "unknown reason {}", data),
+        }
+    }
+}
+
+/// Format a decoded error argument
+fn format_token(token: &Token) -> String {
+    match token {
+        Token::Address(address) => format!("{:#x}", address),
+        Token::Uint(value) | Token::Int(value) => value.to_string(),
+        token => token.to_string(),
+    }
+}
 
 /// Simple function to generate a new secret key
 pub fn generate_secret_key() -> String {
@@ -54,108 +308,686 @@ pub(crate) fn get_admin_wallet() -> Result<Wallet<SigningKey>, Error> {
 
     let admin_key =
         std::env::var("PRIVATE_KEY").unwrap_or_else(|_| panic!("PRIVATE_KEY must be set"));
-    let chain_id = std::env::var("CHAIN_ID").unwrap_or_else(|_| panic!("CHAIN_ID must be set"));
     let admin_wallet = get_wallet_from_secret_key(&admin_key)?;
 
-    Ok(admin_wallet.with_chain_id(
-        u64::from_str(&chain_id).unwrap_or_else(|_| panic!("CHAIN_ID must be a number")),
+    Ok(admin_wallet.with_chain_id(get_chain_id()))
+}
+
+/// Get the id of the chain from the environment
+pub(crate) fn get_chain_id() -> u64 {
+    dotenv().expect(".env file not found");
+
+    let chain_id = std::env::var("CHAIN_ID").unwrap_or_else(|_| panic!("CHAIN_ID must be set"));
+
+    u64::from_str(&chain_id).unwrap_or_else(|_| panic!("CHAIN_ID must be a number"))
+}
+
+/// Create a client signing transactions with a wallet on the current chain
+fn get_signer_client(wallet: LocalWallet) -> Result<SignerClient, Error> {
+    let provider = get_provider()?;
+
+    Ok(SignerMiddleware::new(
+        provider,
+        wallet.with_chain_id(get_chain_id()),
     ))
 }
 
+/// A client that signs transactions with a wallet
+type SignerClient = SignerMiddleware<Provider<Http>, Wallet<SigningKey>>;
+
+/// A contract call sent from a wallet
+type SignerCall = FunctionCall<Arc<SignerClient>, SignerClient, ()>;
+
+/// PreparedCall is a contract call sent from a wallet, with the ABI its
+/// revert reasons are decoded with and the wallet signing it.
+pub struct PreparedCall {
+    call: SignerCall,
+    abi: Abi,
+    wallet: LocalWallet,
+}
+
+/// SignedTransaction is a transaction signed and ready to be broadcast, so
+/// its hash and nonce can be recorded before it is sent.
+#[derive(Clone, Debug)]
+pub struct SignedTransaction {
+    /// The address of the wallet that signed the transaction.
+    pub from: Address,
+    /// The nonce of the transaction.
+    pub nonce: U256,
+    /// The hash of the transaction.
+    pub hash: String,
+    /// The signed transaction as broadcast to the node.
+    pub raw: Bytes,
+}
+
+/// Simulation is the outcome of a transaction run against the latest block
+/// without being sent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Simulation {
+    /// The estimated gas used by the transaction.
+    pub gas_estimate: U256,
+}
+
+/// Prepare a contract call sent from a wallet. Overloaded functions are
+/// given by their signature and called by selector, others by name.
+fn prepare_call<T: Tokenize>(
+    contract: ContractInstance<Arc<Provider<Http>>, Provider<Http>>,
+    wallet: LocalWallet,
+    method: &str,
+    args: T,
+    label: &str,
+) -> Result<PreparedCall, ChainError> {
+    let wallet = wallet.with_chain_id(get_chain_id());
+    let from = wallet.address();
+    let client = Arc::new(get_signer_client(wallet.clone())?);
+    // Connect the contract to the provider to use the signer middleware
+    let connected = contract.connect(client);
+
+    let call = match method.contains('(') {
+        true => connected.method_hash(ethers::utils::id(method), args),
+        false => connected.method(method, args),
+    }
+    .map_err(|e| {
+        Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to prepare {} call: {:?}", label, e),
+        )
+    })?
+    // Send from the wallet so its nonce can be managed
+    .from(from);
+
+    Ok(PreparedCall {
+        call,
+        abi: contract.abi().clone(),
+        wallet,
+    })
+}
+
+/// Prepare the call minting a new NFT reward
+pub(crate) fn prepare_mint(
+    to: Address,
+    token_id: U256,
+    url: String,
+    value: U256,
+) -> Result<PreparedCall, ChainError> {
+    let args = (to, token_id, url, value);
+
+    prepare_call(
+        get_reward_nft_contract()?,
+        get_admin_wallet()?,
+        "safeMint",
+        args,
+        "minting",
+    )
+}
+
+/// Prepare the call burning an NFT reward
+pub(crate) fn prepare_burn(
+    wallet: LocalWallet,
+    token_id: U256,
+) -> Result<PreparedCall, ChainError> {
+    prepare_call(get_reward_nft_contract()?, wallet, "burn", token_id, "burn")
+}
+
+/// Prepare the call burning an NFT reward on behalf of its owner with their
+/// signed burn authorization, sent from the admin wallet
+pub(crate) fn prepare_relay_burn(
+    authorization: &BurnAuthorization,
+    signature: &Signature,
+) -> Result<PreparedCall, ChainError> {
+    let args = (
+        authorization.owner,
+        authorization.token_id,
+        authorization.nonce,
+        U256::from(authorization.deadline),
+        Bytes::from(signature.to_vec()),
+    );
+
+    // The relayer pays for the burn, while the signature authorizes it
+    prepare_call(
+        get_reward_nft_contract()?,
+        get_admin_wallet()?,
+        "burnWithAuthorization",
+        args,
+        "relayed burn",
+    )
+}
+
+/// Run a call with `eth_call` and estimate its gas, failing with the decoded
+/// revert reason if it would revert
+async fn simulate(prepared: &PreparedCall) -> Result<Simulation, ChainError> {
+    let (call, abi) = (&prepared.call, &prepared.abi);
+
+    rpc(|| async {
+        call.call()
+            .await
+            .map_err(|e| ChainError::from_contract_error(e, abi, "Failed to simulate transaction"))
+    })
+    .await?;
+
+    let gas_estimate = rpc(|| async {
+        call.estimate_gas()
+            .await
+            .map_err(|e| ChainError::from_contract_error(e, abi, "Failed to estimate gas"))
+    })
+    .await?;
+
+    Ok(Simulation { gas_estimate })
+}
+
+/// Simulate a call and send it if it would succeed, returning the transaction
+/// hash
+async fn simulate_and_send(prepared: PreparedCall, context: &str) -> Result<String, ChainError> {
+    let signed = simulate_and_sign(prepared, context, None).await?;
+    broadcast(&signed).await?;
+
+    Ok(signed.hash)
+}
+
+/// Simulate a call and sign it if it would succeed, without sending it. The
+/// transaction is signed with `nonce` if given, so it takes the place of a
+/// transaction sent before with that nonce, and with the next nonce of the
+/// wallet otherwise.
+pub(crate) async fn simulate_and_sign(
+    prepared: PreparedCall,
+    context: &str,
+    nonce: Option<U256>,
+) -> Result<SignedTransaction, ChainError> {
+    let simulation = simulate(&prepared).await?;
+    let policy = GasPolicy::from_env();
+    let fees = get_fees(&policy).await?;
+    // Use the estimate so the node does not estimate the gas again
+    let mut tx = prepared
+        .call
+        .gas(policy.gas_limit(simulation.gas_estimate))
+        .tx;
+    fees.apply(&mut tx);
+
+    let wallet = &prepared.wallet;
+
+    match nonce {
+        Some(nonce) => sign(wallet, tx, nonce, context).await,
+        None => {
+            let address = wallet.address();
+
+            nonce_manager()
+                .send(address, get_pending_nonce(address), |nonce| {
+                    sign(wallet, tx, nonce, context)
+                })
+                .await
+        }
+    }
+}
+
+/// Sign a transaction from a wallet with a nonce
+async fn sign(
+    wallet: &LocalWallet,
+    mut tx: TypedTransaction,
+    nonce: U256,
+    context: &str,
+) -> Result<SignedTransaction, ChainError> {
+    tx.set_nonce(nonce);
+    tx.set_chain_id(wallet.chain_id());
+
+    let signature = wallet
+        .sign_transaction(&tx)
+        .await
+        .map_err(|e| ChainError::RpcError(format!("{}: {}", context, e)))?;
+    let raw = tx.rlp_signed(&signature);
+
+    Ok(SignedTransaction {
+        from: wallet.address(),
+        nonce,
+        hash: format!("{:#x}", H256::from(keccak256(&raw))),
+        raw,
+    })
+}
+
+/// Broadcast a signed transaction. If the node does not accept it, the next
+/// nonce of its wallet is asked from the node again, so a nonce left unused
+/// is handed out again.
+pub(crate) async fn broadcast(signed: &SignedTransaction) -> Result<(), ChainError> {
+    let provider = get_provider()?;
+    let provider = &provider;
+
+    let result = rpc(|| async move {
+        provider
+            .send_raw_transaction(signed.raw.clone())
+            .await
+            .map(|_| ())
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to send transaction"))
+    })
+    .await;
+
+    if result.is_err() {
+        nonce_manager().release(signed.from).await;
+    }
+
+    result
+}
+
+/// Get the fees of a new transaction according to the gas policy
+async fn get_fees(policy: &GasPolicy) -> Result<Fees, ChainError> {
+    let provider = get_provider()?;
+    let provider = &provider;
+
+    let base_fee = match policy.mode {
+        GasMode::Legacy => None,
+        GasMode::Eip1559 => rpc(|| async move {
+            provider
+                .get_block(BlockNumber::Latest)
+                .await
+                .map_err(|e| ChainError::from_provider_error(e, "Failed to get block"))
+        })
+        .await?
+        .and_then(|block| block.base_fee_per_gas),
+    };
+
+    // Fall back to the gas price on networks without a base fee
+    let base_fee = match base_fee {
+        Some(base_fee) => base_fee,
+        None => {
+            rpc(|| async move {
+                provider
+                    .get_gas_price()
+                    .await
+                    .map_err(|e| ChainError::from_provider_error(e, "Failed to get gas price"))
+            })
+            .await?
+        }
+    };
+
+    policy.fees(base_fee)
+}
+
+/// Get the nonce of the next transaction from an address, counting
+/// transactions still in the mempool
+async fn get_pending_nonce(address: Address) -> Result<U256, ChainError> {
+    let provider = get_provider()?;
+
+    rpc(|| async {
+        provider
+            .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get nonce"))
+    })
+    .await
+}
+
+/// Get the nonce of the next transaction from an address, counting only
+/// mined transactions
+pub async fn get_confirmed_nonce(address: Address) -> Result<U256, ChainError> {
+    let provider = get_provider()?;
+
+    rpc(|| async {
+        provider
+            .get_transaction_count(address, Some(BlockNumber::Latest.into()))
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get nonce"))
+    })
+    .await
+}
+
+/// Get the ETH balance of an address
+pub async fn get_eth_balance(address: Address) -> Result<U256, ChainError> {
+    let provider = get_provider()?;
+
+    rpc(|| async {
+        provider
+            .get_balance(address, None)
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get balance"))
+    })
+    .await
+}
+
+/// Send ETH from a wallet to an address and return the transaction hash
+pub async fn send_eth(
+    wallet: LocalWallet,
+    to: Address,
+    amount: U256,
+) -> Result<String, ChainError> {
+    let policy = GasPolicy::from_env();
+    let fees = get_fees(&policy).await?;
+    let client = &get_signer_client(wallet)?;
+
+    // A transfer to a wallet always uses the base transaction gas
+    let mut tx: TypedTransaction = TransactionRequest::new()
+        .to(to)
+        .value(amount)
+        .gas(21_000)
+        .into();
+    fees.apply(&mut tx);
+
+    let address = client.address();
+    let tx_hash = nonce_manager()
+        .send(address, get_pending_nonce(address), |nonce| async move {
+            tx.set_nonce(nonce);
+
+            let tx = &tx;
+            rpc(|| async move {
+                let pending = client
+                    .send_transaction(tx.clone(), None)
+                    .await
+                    .map_err(|e| ChainError::from_middleware_error(e, "Failed to send ETH"))?;
+
+                Ok(pending.tx_hash())
+            })
+            .await
+        })
+        .await?;
+
+    Ok(format!("{:#x}", tx_hash))
+}
+
+/// Replace a pending transaction with one paying higher fees. Returns the
+/// hash of the replacement, or `None` if the transaction is no longer pending
+/// or its fees cannot be raised.
+pub async fn bump_transaction(
+    wallet: LocalWallet,
+    tx_hash: &str,
+) -> Result<Option<String>, ChainError> {
+    let provider = get_provider()?;
+    let tx_hash = parse_tx_hash(tx_hash)?;
+    let tx = {
+        let provider = &provider;
+
+        rpc(|| async move {
+            provider
+                .get_transaction(tx_hash)
+                .await
+                .map_err(|e| ChainError::from_provider_error(e, "Failed to get transaction"))
+        })
+        .await?
+    };
+
+    // Only pending transactions sent by the wallet can be replaced
+    let tx = match tx {
+        Some(tx) if tx.block_number.is_none() && tx.from == wallet.address() => tx,
+        _ => return Ok(None),
+    };
+
+    let policy = GasPolicy::from_env();
+    let fees = match Fees::from_transaction(&tx).and_then(|fees| policy.bump(fees)) {
+        Some(fees) => fees,
+        None => return Ok(None),
+    };
+
+    // The replacement reuses the nonce so only one of them can be mined
+    let mut replacement: TypedTransaction = (&tx).into();
+    fees.apply(&mut replacement);
+
+    let client = &get_signer_client(wallet)?;
+    let replacement = &replacement;
+    let tx_hash = rpc(|| async move {
+        let tx = client
+            .send_transaction(replacement.clone(), None)
+            .await
+            .map_err(|e| ChainError::from_middleware_error(e, "Failed to replace transaction"))?;
+
+        Ok(tx.tx_hash())
+    })
+    .await?;
+
+    Ok(Some(format!("{:#x}", tx_hash)))
+}
+
 /// Mint a new NFT reward
 pub async fn mint_nft_reward(
     to: Address,
     token_id: U256,
     url: String,
     value: U256,
-) -> Result<String, Error> {
-    // Get the admin wallet
-    let wallet = get_admin_wallet()?;
-    // Get the reward NFT contract
-    let contract = get_reward_nft_contract()?;
-    // Get the provider
-    let provider = get_provider()?;
-    // Create a new signer middleware
-    let client = SignerMiddleware::new(provider, wallet);
-
-    // Mint the reward NFT
-    let call: FunctionCall<
-        Arc<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>,
-        SignerMiddleware<Provider<Http>, Wallet<SigningKey>>,
-        (
-            Address,
-            ethers::types::U256,
-            std::string::String,
-            ethers::types::U256,
-        ),
-    > = contract
-        // Connect the contract to the provider to use the signer middleware
-        .connect(client.into())
-        // Specify the safeMint function of the contract
-        .method("safeMint", (to, token_id, url, value))
-        .map_err(|e| {
-            Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Failed to prepare minting call: {:?}", e),
-            )
-        })?;
+) -> Result<String, ChainError> {
+    simulate_and_send(
+        prepare_mint(to, token_id, url, value)?,
+        "Failed to mint reward",
+    )
+    .await
+}
 
-    let tx = call.send().await.map_err(|e| {
-        Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Failed to mint reward: {:?}", e),
-        )
-    })?;
+/// Prepare the call burning an NFT reward without minting its reward tokens
+pub(crate) fn prepare_forfeit(token_id: U256) -> Result<PreparedCall, ChainError> {
+    // Only the contract owner can forfeit a token
+    prepare_call(
+        get_reward_nft_contract()?,
+        get_admin_wallet()?,
+        "forfeit",
+        token_id,
+        "forfeit",
+    )
+}
 
-    Ok(tx.tx_hash().to_string())
+/// Prepare the call transferring an NFT reward from the wallet holding it
+pub(crate) fn prepare_transfer(
+    wallet: LocalWallet,
+    to: Address,
+    token_id: U256,
+) -> Result<PreparedCall, ChainError> {
+    let args = (wallet.address(), to, token_id);
+
+    prepare_call(
+        get_reward_nft_contract()?,
+        wallet,
+        SAFE_TRANSFER_FROM,
+        args,
+        "transfer",
+    )
+}
+
+/// Prepare the call transferring reward tokens from a wallet
+pub(crate) fn prepare_token_transfer(
+    wallet: LocalWallet,
+    to: Address,
+    amount: U256,
+) -> Result<PreparedCall, ChainError> {
+    prepare_call(
+        get_reward_token_contract()?,
+        wallet,
+        "transfer",
+        (to, amount),
+        "token transfer",
+    )
+}
+
+/// SplitToken is a new NFT reward minted when a reward is split.
+#[derive(Clone, Debug)]
+pub struct SplitToken {
+    /// The id of the new token.
+    pub token_id: U256,
+    /// The metadata uri of the new token.
+    pub uri: String,
+    /// The reward value of the new token.
+    pub value: U256,
+}
+
+/// Prepare the call splitting an NFT reward into new rewards
+pub(crate) fn prepare_split(
+    wallet: LocalWallet,
+    token_id: U256,
+    redeem_value: U256,
+    tokens: &[SplitToken],
+) -> Result<PreparedCall, ChainError> {
+    let token_ids: Vec<U256> = tokens.iter().map(|token| token.token_id).collect();
+    let values: Vec<U256> = tokens.iter().map(|token| token.value).collect();
+    let uris: Vec<String> = tokens.iter().map(|token| token.uri.clone()).collect();
+    let args = (token_id, redeem_value, token_ids, values, uris);
+
+    prepare_call(get_reward_nft_contract()?, wallet, "split", args, "split")
+}
+
+/// Simulate minting a new NFT reward without sending the transaction
+pub async fn simulate_mint_nft_reward(
+    to: Address,
+    token_id: U256,
+    url: String,
+    value: U256,
+) -> Result<Simulation, ChainError> {
+    simulate(&prepare_mint(to, token_id, url, value)?).await
 }
 
 /// Redeem an NFT reward
-pub async fn burn_nft_reward(wallet: LocalWallet, token_id: U256) -> Result<String, Error> {
-    // Get the reward NFT contract
-    let contract = get_reward_nft_contract()?;
-    // Get the provider
-    let provider = get_provider()?;
-    // Create a new signer middleware
-    let client = SignerMiddleware::new(provider, wallet);
-
-    // Burn the NFT
-    let call: FunctionCall<
-        Arc<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>,
-        SignerMiddleware<Provider<Http>, Wallet<SigningKey>>,
-        ethers::types::U256,
-    > = contract
-        // Connect the contract to the provider to use the signer middleware
-        .connect(client.into())
-        // Specify the burn function of the contract
-        .method("burn", token_id)
-        .map_err(|e| {
-            Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Failed to prepare burn call: {:?}", e),
-            )
-        })?;
+pub async fn burn_nft_reward(wallet: LocalWallet, token_id: U256) -> Result<String, ChainError> {
+    simulate_and_send(prepare_burn(wallet, token_id)?, "Failed to burn reward").await
+}
 
-    let tx = call.send().await.map_err(|e| {
-        Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Failed to mint reward: {:?}", e),
-        )
-    })?;
+/// Simulate redeeming an NFT reward without sending the transaction
+pub async fn simulate_burn_nft_reward(
+    wallet: LocalWallet,
+    token_id: U256,
+) -> Result<Simulation, ChainError> {
+    simulate(&prepare_burn(wallet, token_id)?).await
+}
+
+/// Burn an NFT reward from the admin wallet on behalf of its owner, with
+/// their signed burn authorization
+pub async fn relay_burn_nft_reward(
+    authorization: &BurnAuthorization,
+    signature: &Signature,
+) -> Result<String, ChainError> {
+    simulate_and_send(
+        prepare_relay_burn(authorization, signature)?,
+        "Failed to relay burn",
+    )
+    .await
+}
+
+/// Simulate relaying the burn of an NFT reward without sending the
+/// transaction
+pub async fn simulate_relay_burn_nft_reward(
+    authorization: &BurnAuthorization,
+    signature: &Signature,
+) -> Result<Simulation, ChainError> {
+    simulate(&prepare_relay_burn(authorization, signature)?).await
+}
+
+/// Burn an NFT reward from the admin wallet without crediting its holder
+/// with reward tokens
+pub async fn forfeit_nft_reward(token_id: U256) -> Result<String, ChainError> {
+    simulate_and_send(prepare_forfeit(token_id)?, "Failed to forfeit reward").await
+}
+
+/// Burn an NFT reward and mint new rewards carrying its value, crediting
+/// the holder with `redeem_value` reward tokens
+pub async fn split_nft_reward(
+    wallet: LocalWallet,
+    token_id: U256,
+    redeem_value: U256,
+    tokens: &[SplitToken],
+) -> Result<String, ChainError> {
+    simulate_and_send(
+        prepare_split(wallet, token_id, redeem_value, tokens)?,
+        "Failed to split reward",
+    )
+    .await
+}
+
+/// Simulate splitting an NFT reward without sending the transaction
+pub async fn simulate_split_nft_reward(
+    wallet: LocalWallet,
+    token_id: U256,
+    redeem_value: U256,
+    tokens: &[SplitToken],
+) -> Result<Simulation, ChainError> {
+    simulate(&prepare_split(wallet, token_id, redeem_value, tokens)?).await
+}
+
+/// Transfer an NFT reward from the wallet holding it to another address
+pub async fn transfer_nft_reward(
+    wallet: LocalWallet,
+    to: Address,
+    token_id: U256,
+) -> Result<String, ChainError> {
+    simulate_and_send(
+        prepare_transfer(wallet, to, token_id)?,
+        "Failed to transfer reward",
+    )
+    .await
+}
+
+/// Transfer reward tokens from a wallet to another address
+pub async fn transfer_reward_tokens(
+    wallet: LocalWallet,
+    to: Address,
+    amount: U256,
+) -> Result<String, ChainError> {
+    simulate_and_send(
+        prepare_token_transfer(wallet, to, amount)?,
+        "Failed to transfer reward tokens",
+    )
+    .await
+}
 
-    Ok(tx.tx_hash().to_string())
+/// Run an RPC call with the retry policy and circuit breaker
+async fn rpc<T, F, Fut>(operation: F) -> Result<T, ChainError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ChainError>>,
+{
+    with_retry(&RetryPolicy::from_env(), rpc_circuit(), operation).await
 }
 
 fn get_provider() -> Result<Provider<Http>, Error> {
     dotenv().expect(".env file not found");
 
     let rpc_url = std::env::var("RPC_URL").unwrap_or_else(|_| panic!("RPC_URL must be set"));
-    Provider::<Http>::try_from(rpc_url).map_err(|e| {
+    let provider = Provider::<Http>::try_from(rpc_url).map_err(|e| {
         Error::new(
             std::io::ErrorKind::ConnectionRefused,
             format!("Failed to connect to provider: {:?}", e),
         )
+    })?;
+
+    Ok(provider.interval(get_poll_interval()))
+}
+
+/// Get how often pending transactions are polled for a receipt
+fn get_poll_interval() -> Duration {
+    Duration::from_millis(parse_env("TX_POLL_INTERVAL_MS", 1000))
+}
+
+/// Wait for a transaction to be mined. Returns `None` if the transaction was
+/// dropped from the mempool.
+pub async fn wait_for_transaction(tx_hash: &str) -> Result<Option<TransactionReceipt>, ChainError> {
+    let provider = get_provider()?;
+    let tx_hash = parse_tx_hash(tx_hash)?;
+    let provider = &provider;
+
+    rpc(|| async move {
+        PendingTransaction::new(tx_hash, provider)
+            .interval(get_poll_interval())
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get transaction receipt"))
+    })
+    .await
+}
+
+/// Get the receipt of a transaction without waiting for it to be mined.
+/// Returns `None` if the transaction has not been mined.
+pub async fn get_transaction_receipt(
+    tx_hash: &str,
+) -> Result<Option<TransactionReceipt>, ChainError> {
+    let provider = get_provider()?;
+    let tx_hash = parse_tx_hash(tx_hash)?;
+    let provider = &provider;
+
+    rpc(|| async move {
+        provider
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get transaction receipt"))
     })
+    .await
+}
+
+/// Parse a transaction hash
+fn parse_tx_hash(tx_hash: &str) -> Result<H256, ChainError> {
+    Ok(H256::from_str(tx_hash).map_err(|e| {
+        Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Invalid transaction hash: {:?}", e),
+        )
+    })?)
 }
 
 /// Creates a new contract instance from the given contract JSON and address
@@ -184,7 +1016,7 @@ fn get_contract_address_from_env(env_var: &str) -> Address {
 pub fn get_reward_token_contract(
 ) -> Result<ContractInstance<Arc<Provider<Http>>, Provider<Http>>, Error> {
     let contract_json = include_str!("../../../out/Reward.sol/Reward.json");
-    let contract_address = get_contract_address_from_env("REWARD_TOKEN_ADDRESS");
+    let contract_address = get_reward_token_address();
     create_contract_instance(contract_json, contract_address)
 }
 
@@ -192,13 +1024,74 @@ pub fn get_reward_token_contract(
 pub fn get_reward_nft_contract(
 ) -> Result<ContractInstance<Arc<Provider<Http>>, Provider<Http>>, Error> {
     let contract_json = include_str!("../../../out/RewardNFT.sol/RewardNFT.json");
-    let contract_address = get_contract_address_from_env("REWARD_NFT_ADDRESS");
+    let contract_address = get_reward_nft_address();
     create_contract_instance(contract_json, contract_address)
 }
 
+/// Get the address of the reward token contract
+pub fn get_reward_token_address() -> Address {
+    get_contract_address_from_env("REWARD_TOKEN_ADDRESS")
+}
+
+/// Get the address of the reward NFT contract
+pub fn get_reward_nft_address() -> Address {
+    get_contract_address_from_env("REWARD_NFT_ADDRESS")
+}
+
+/// Get the number of the latest block
+pub async fn get_block_number() -> Result<u64, ChainError> {
+    let provider = get_provider()?;
+    let block_number = rpc(|| async {
+        provider
+            .get_block_number()
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get block number"))
+    })
+    .await?;
+
+    Ok(block_number.as_u64())
+}
+
+/// Get the hash of a block, if the block exists
+pub async fn get_block_hash(block_number: u64) -> Result<Option<H256>, ChainError> {
+    let provider = get_provider()?;
+    let block = rpc(|| async {
+        provider
+            .get_block(block_number)
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get block"))
+    })
+    .await?;
+
+    Ok(block.and_then(|block| block.hash))
+}
+
+/// Get the `Transfer` event logs emitted by the given contracts between two
+/// blocks (inclusive)
+pub async fn get_transfer_logs(
+    addresses: Vec<Address>,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Log>, ChainError> {
+    let provider = get_provider()?;
+    let filter = Filter::new()
+        .address(addresses)
+        .topic0(H256::from(keccak256(TRANSFER_EVENT)))
+        .from_block(from_block)
+        .to_block(to_block);
+
+    rpc(|| async {
+        provider
+            .get_logs(&filter)
+            .await
+            .map_err(|e| ChainError::from_provider_error(e, "Failed to get logs"))
+    })
+    .await
+}
+
 /// Get the reward balance of a wallet
 /// TODO Improve error handling
-pub async fn get_reward_balance(address: Address) -> Result<U256, Error> {
+pub async fn get_reward_balance(address: Address) -> Result<U256, ChainError> {
     // Create a new contract instance
     let contract = get_reward_token_contract()?;
     let contract_method = contract.method("balanceOf", address).map_err(|e| {
@@ -209,14 +1102,147 @@ pub async fn get_reward_balance(address: Address) -> Result<U256, Error> {
     })?;
 
     // Call the balanceOf function
-    let balance: U256 = contract_method.call().await.map_err(|e| {
+    let balance: U256 = rpc(|| async {
+        contract_method.call().await.map_err(|e| {
+            ChainError::from_contract_error(e, contract.abi(), "Failed to get balance")
+        })
+    })
+    .await?;
+
+    Ok(balance)
+}
+
+/// TokenMetadata describes how amounts of the reward token are displayed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    /// The symbol of the token.
+    pub symbol: String,
+    /// The number of decimals of the token.
+    pub decimals: u8,
+}
+
+/// The metadata of the reward token, which never changes once read
+static REWARD_TOKEN_METADATA: OnceLock<TokenMetadata> = OnceLock::new();
+
+/// Get the symbol and decimals of the reward token
+pub async fn get_reward_token_metadata() -> Result<TokenMetadata, ChainError> {
+    if let Some(metadata) = REWARD_TOKEN_METADATA.get() {
+        return Ok(metadata.clone());
+    }
+
+    let contract = get_reward_token_contract()?;
+    let symbol_method = contract.method::<_, String>("symbol", ()).map_err(|e| {
         Error::new(
             std::io::ErrorKind::InvalidData,
-            format!("Failed to get balance: {:?}", e),
+            format!("Failed to get symbol: {:?}", e),
+        )
+    })?;
+    let decimals_method = contract.method::<_, u8>("decimals", ()).map_err(|e| {
+        Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to get decimals: {:?}", e),
         )
     })?;
 
-    Ok(balance)
+    let symbol = rpc(|| async {
+        symbol_method
+            .call()
+            .await
+            .map_err(|e| ChainError::from_contract_error(e, contract.abi(), "Failed to get symbol"))
+    })
+    .await?;
+    let decimals = rpc(|| async {
+        decimals_method.call().await.map_err(|e| {
+            ChainError::from_contract_error(e, contract.abi(), "Failed to get decimals")
+        })
+    })
+    .await?;
+
+    Ok(REWARD_TOKEN_METADATA
+        .get_or_init(|| TokenMetadata { symbol, decimals })
+        .clone())
+}
+
+/// Check if a reward NFT exists
+pub async fn check_token_exists(token_id: U256) -> Result<bool, ChainError> {
+    let contract = get_reward_nft_contract()?;
+    let contract_method = contract
+        .method("checkIfTokenExist", token_id)
+        .map_err(|e| {
+            Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to check token: {:?}", e),
+            )
+        })?;
+
+    let exists: bool = rpc(|| async {
+        contract_method.call().await.map_err(|e| {
+            ChainError::from_contract_error(e, contract.abi(), "Failed to check token")
+        })
+    })
+    .await?;
+
+    Ok(exists)
+}
+
+/// Get the nonce the next burn authorization of an owner must use
+pub async fn get_burn_nonce(owner: Address) -> Result<U256, ChainError> {
+    let contract = get_reward_nft_contract()?;
+    let contract_method = contract.method("burnNonces", owner).map_err(|e| {
+        Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to get burn nonce: {:?}", e),
+        )
+    })?;
+
+    let nonce: U256 = rpc(|| async {
+        contract_method.call().await.map_err(|e| {
+            ChainError::from_contract_error(e, contract.abi(), "Failed to get burn nonce")
+        })
+    })
+    .await?;
+
+    Ok(nonce)
+}
+
+/// Get the reward value of an existing reward NFT
+pub async fn get_token_reward_value(token_id: U256) -> Result<U256, ChainError> {
+    let contract = get_reward_nft_contract()?;
+    let contract_method = contract.method("getRewardValue", token_id).map_err(|e| {
+        Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to get reward value: {:?}", e),
+        )
+    })?;
+
+    let value: U256 = rpc(|| async {
+        contract_method.call().await.map_err(|e| {
+            ChainError::from_contract_error(e, contract.abi(), "Failed to get reward value")
+        })
+    })
+    .await?;
+
+    Ok(value)
+}
+
+/// Get the owner of an existing reward NFT
+pub async fn get_token_owner(token_id: U256) -> Result<Address, ChainError> {
+    let contract = get_reward_nft_contract()?;
+    let contract_method = contract.method("ownerOf", token_id).map_err(|e| {
+        Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to get token owner: {:?}", e),
+        )
+    })?;
+
+    let owner: Address = rpc(|| async {
+        contract_method.call().await.map_err(|e| {
+            ChainError::from_contract_error(e, contract.abi(), "Failed to get token owner")
+        })
+    })
+    .await?;
+
+    Ok(owner)
 }
 
 #[cfg(test)]
@@ -277,4 +1303,86 @@ mod tests {
         // Check that the wallet can verify the signature
         assert_eq!(signature.recover(&message[..]).unwrap(), wallet.address());
     }
+
+    /// Encode revert data with a selector and arguments
+    fn revert_data(signature: &str, args: &[Token]) -> Vec<u8> {
+        let mut data = keccak256(signature)[..4].to_vec();
+        data.extend(ethers::abi::encode(args));
+        data
+    }
+
+    #[test]
+    fn test_decode_revert_message() {
+        let data = revert_data(
+            "Error(string)",
+            &[Token::String("Error: Token does not exist!".into())],
+        );
+        let reason = RevertReason::decode(&data, &Abi::default());
+
+        assert_eq!(
+            reason,
+            RevertReason::Message("Error: Token does not exist!".into())
+        );
+        assert!(matches!(
+            ChainError::from_revert(reason),
+            ChainError::TokenNotFound
+        ));
+
+        // Unknown messages are kept
+        let data = revert_data("Error(string)", &[Token::String("Paused".into())]);
+        let error = ChainError::from_revert(RevertReason::decode(&data, &Abi::default()));
+
+        assert!(error.is_revert());
+        assert_eq!(error.to_string(), "Transaction reverted: Paused");
+    }
+
+    #[test]
+    fn test_decode_revert_panic() {
+        let data = revert_data("Panic(uint256)", &[Token::Uint(U256::from(0x11))]);
+
+        assert_eq!(
+            RevertReason::decode(&data, &Abi::default()),
+            RevertReason::Panic(U256::from(0x11))
+        );
+    }
+
+    #[test]
+    fn test_decode_revert_custom() {
+        let abi: Abi = serde_json::from_str(
+            r#"[
+                {"type":"error","name":"ERC721NonexistentToken","inputs":[{"name":"tokenId","type":"uint256"}]},
+                {"type":"error","name":"OwnableUnauthorizedAccount","inputs":[{"name":"account","type":"address"}]}
+            ]"#,
+        )
+        .unwrap();
+        let data = revert_data(
+            "ERC721NonexistentToken(uint256)",
+            &[Token::Uint(U256::from(42))],
+        );
+
+        assert_eq!(
+            RevertReason::decode(&data, &abi),
+            RevertReason::Custom {
+                name: "ERC721NonexistentToken".into(),
+                args: vec!["42".into()],
+            }
+        );
+
+        let account = Address::random();
+        let data = revert_data(
+            "OwnableUnauthorizedAccount(address)",
+            &[Token::Address(account)],
+        );
+
+        assert!(matches!(
+            ChainError::from_revert(RevertReason::decode(&data, &abi)),
+            ChainError::NotContractOwner
+        ));
+
+        // Errors missing from the ABI cannot be decoded
+        assert!(matches!(
+            RevertReason::decode(&data, &Abi::default()),
+            RevertReason::Unknown(_)
+        ));
+    }
 }
@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+lazy_static! {
+    /// The event bus shared by this process.
+    static ref EVENT_BUS: EventBus = EventBus::new(parse_env("EVENT_LOG_SIZE", 1000));
+}
+
+/// Get the event bus shared by this process.
+pub fn event_bus() -> &'static EventBus {
+    &EVENT_BUS
+}
+
+/// EventKind is what happened to a reward.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum EventKind {
+    /// A reward was issued and its mint recorded.
+    #[serde(rename = "reward.issued")]
+    RewardIssued,
+    /// The mint of a reward was confirmed on-chain.
+    #[serde(rename = "reward.minted")]
+    RewardMinted,
+    /// A reward was redeemed.
+    #[serde(rename = "reward.redeemed")]
+    RewardRedeemed,
+}
+
+impl EventKind {
+    /// Get the name of the event, as sent to clients.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::RewardIssued => "reward.issued",
+            EventKind::RewardMinted => "reward.minted",
+            EventKind::RewardRedeemed => "reward.redeemed",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Event is a live update about a reward of a user.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Event {
+    /// The id of the event. Ids increase in the order events are published.
+    pub id: u64,
+    /// What happened.
+    pub kind: EventKind,
+    /// The user the reward belongs to.
+    pub user_id: Uuid,
+    /// The reward involved.
+    pub reward_id: Option<Uuid>,
+    /// The value involved, in reward token units.
+    pub amount: Option<String>,
+    /// The hash of the transaction, if one was sent.
+    pub tx_hash: Option<String>,
+    /// When it happened, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl Event {
+    /// Create a new event. Its id is set when it is published.
+    pub fn new(kind: EventKind, user_id: Uuid) -> Self {
+        Self {
+            id: 0,
+            kind,
+            user_id,
+            reward_id: None,
+            amount: None,
+            tx_hash: None,
+            created_at: now(),
+        }
+    }
+}
+
+/// EventLog is the bounded log of the latest events.
+struct EventLog {
+    events: VecDeque<Event>,
+    last_id: u64,
+}
+
+/// EventBus broadcasts events to the clients listening for them, and keeps
+/// the latest events so clients that reconnect can catch up on the ones they
+/// missed.
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+    log: Mutex<EventLog>,
+    capacity: usize,
+}
+
+impl EventBus {
+    /// Create a bus keeping up to `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+
+        Self {
+            sender,
+            log: Mutex::new(EventLog {
+                events: VecDeque::with_capacity(capacity),
+                last_id: 0,
+            }),
+            capacity,
+        }
+    }
+
+    /// Publish an event to the listening clients, returning it with its id.
+    /// Ids are the time in nanoseconds, raised when needed so they always
+    /// increase, so ids from before a restart are not handed out again.
+    pub fn publish(&self, mut event: Event) -> Event {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+
+        event.id = time.max(log.last_id + 1);
+        log.last_id = event.id;
+
+        if log.events.len() == self.capacity {
+            log.events.pop_front();
+        }
+        log.events.push_back(event.clone());
+
+        // Sending only fails when no client is listening
+        let _ = self.sender.send(event.clone());
+
+        event
+    }
+
+    /// Listen for events. The events in the log after `last_id` are returned
+    /// to be sent first, and the receiver gets every event published after
+    /// them.
+    pub fn subscribe(&self, last_id: Option<u64>) -> (Vec<Event>, broadcast::Receiver<Event>) {
+        // The log is locked so no event is published between the two
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        let receiver = self.sender.subscribe();
+        let missed = match last_id {
+            Some(last_id) => log
+                .events
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        (missed, receiver)
+    }
+
+    /// Get the events in the log after an id.
+    pub fn since(&self, last_id: u64) -> Vec<Event> {
+        self.subscribe(Some(last_id)).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> Event {
+        Event::new(EventKind::RewardIssued, Uuid::new_v4())
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let bus = EventBus::new(10);
+        let (missed, mut receiver) = bus.subscribe(None);
+        let first = bus.publish(event());
+        let second = bus.publish(event());
+
+        assert!(missed.is_empty());
+        assert!(second.id > first.id);
+        assert_eq!(receiver.recv().await.unwrap(), first);
+        assert_eq!(receiver.recv().await.unwrap(), second);
+    }
+
+    #[test]
+    fn test_subscribe_missed() {
+        let bus = EventBus::new(3);
+        let events: Vec<Event> = (0..5).map(|_| bus.publish(event())).collect();
+
+        // Events after the last one seen are sent first
+        let (missed, _) = bus.subscribe(Some(events[2].id));
+
+        assert_eq!(missed, events[3..]);
+
+        // The log only keeps the latest events
+        assert_eq!(bus.since(0), events[2..]);
+    }
+}
@@ -0,0 +1,323 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Eip1559TransactionRequest, Transaction, TransactionRequest, U256};
+
+use crate::utils::config::{parse_env, parse_network_env};
+
+use super::chain::ChainError;
+
+/// GasMode is how transaction fees are priced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GasMode {
+    /// A single gas price.
+    Legacy,
+    /// A maximum fee and priority fee on top of the block base fee.
+    Eip1559,
+}
+
+impl FromStr for GasMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(GasMode::Legacy),
+            "eip1559" => Ok(GasMode::Eip1559),
+            _ => Err(format!("Unknown gas mode: {}", s)),
+        }
+    }
+}
+
+/// Fees are the fees paid by a transaction, in wei per gas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fees {
+    Legacy {
+        gas_price: U256,
+    },
+    Eip1559 {
+        max_fee: U256,
+        max_priority_fee: U256,
+    },
+}
+
+impl Fees {
+    /// Read the fees of a sent transaction.
+    pub fn from_transaction(tx: &Transaction) -> Option<Self> {
+        match (
+            tx.max_fee_per_gas,
+            tx.max_priority_fee_per_gas,
+            tx.gas_price,
+        ) {
+            (Some(max_fee), Some(max_priority_fee), _) => Some(Fees::Eip1559 {
+                max_fee,
+                max_priority_fee,
+            }),
+            (_, _, Some(gas_price)) => Some(Fees::Legacy { gas_price }),
+            _ => None,
+        }
+    }
+
+    /// Get the most that can be paid per gas.
+    pub fn max_fee(&self) -> U256 {
+        match *self {
+            Fees::Legacy { gas_price } => gas_price,
+            Fees::Eip1559 { max_fee, .. } => max_fee,
+        }
+    }
+
+    /// Set the fees of a transaction, converting it to the matching type.
+    pub fn apply(&self, tx: &mut TypedTransaction) {
+        match *self {
+            Fees::Legacy { gas_price } => {
+                if !matches!(tx, TypedTransaction::Legacy(_)) {
+                    let inner: TransactionRequest = tx.clone().into();
+                    *tx = TypedTransaction::Legacy(inner);
+                }
+
+                tx.set_gas_price(gas_price);
+            }
+            Fees::Eip1559 {
+                max_fee,
+                max_priority_fee,
+            } => {
+                let mut inner: Eip1559TransactionRequest = tx.clone().into();
+
+                inner.max_fee_per_gas = Some(max_fee);
+                inner.max_priority_fee_per_gas = Some(max_priority_fee);
+                *tx = TypedTransaction::Eip1559(inner);
+            }
+        }
+    }
+}
+
+/// GasPolicy is how the fees and gas limit of transactions are chosen. Every
+/// setting can be overridden for a network by suffixing it with the chain id,
+/// e.g. `GAS_MODE_1`.
+#[derive(Clone, Debug)]
+pub struct GasPolicy {
+    /// How fees are priced.
+    pub mode: GasMode,
+    /// The priority fee paid to validators.
+    pub priority_fee: U256,
+    /// The most a transaction may pay per gas.
+    pub max_fee_cap: Option<U256>,
+    /// The factor the gas estimate is multiplied by for the gas limit.
+    pub gas_limit_multiplier: f64,
+    /// The base fee above which transactions are not sent.
+    pub base_fee_ceiling: Option<U256>,
+    /// How long a transaction may be pending before it is replaced.
+    pub bump_after: Duration,
+    /// The percentage the fees are raised by when a transaction is replaced.
+    pub bump_percent: u64,
+}
+
+impl GasPolicy {
+    /// Read the gas policy of the current network from the environment.
+    pub fn from_env() -> Self {
+        let chain_id = parse_env("CHAIN_ID", 0);
+
+        Self {
+            mode: parse_network_env("GAS_MODE", chain_id, GasMode::Eip1559),
+            priority_fee: gwei(parse_network_env("GAS_PRIORITY_FEE_GWEI", chain_id, 1.0)),
+            max_fee_cap: optional_gwei(parse_network_env("GAS_MAX_FEE_GWEI", chain_id, 0.0)),
+            gas_limit_multiplier: parse_network_env("GAS_LIMIT_MULTIPLIER", chain_id, 1.2f64)
+                .max(1.0),
+            base_fee_ceiling: optional_gwei(parse_network_env(
+                "GAS_BASE_FEE_CEILING_GWEI",
+                chain_id,
+                0.0,
+            )),
+            bump_after: Duration::from_secs(parse_network_env("GAS_BUMP_AFTER", chain_id, 120)),
+            // Nodes reject replacements that raise the fees by less than 10%
+            bump_percent: parse_network_env("GAS_BUMP_PERCENT", chain_id, 15u64).max(10),
+        }
+    }
+
+    /// Get the gas limit of a transaction from its gas estimate.
+    pub fn gas_limit(&self, estimate: U256) -> U256 {
+        let multiplier = (self.gas_limit_multiplier * 100.0).round() as u64;
+
+        estimate * U256::from(multiplier) / 100
+    }
+
+    /// Get the fees of a new transaction from the base fee, or from the gas
+    /// price for legacy transactions. Fails if the base fee is above the
+    /// ceiling.
+    pub fn fees(&self, base_fee: U256) -> Result<Fees, ChainError> {
+        if let Some(ceiling) = self.base_fee_ceiling {
+            if base_fee > ceiling {
+                return Err(ChainError::FeeTooHigh { base_fee, ceiling });
+            }
+        }
+
+        Ok(match self.mode {
+            GasMode::Legacy => Fees::Legacy {
+                gas_price: self.cap(base_fee),
+            },
+            // Leave room for the base fee to double before the transaction
+            // is mined
+            GasMode::Eip1559 => Fees::Eip1559 {
+                max_fee: self.cap(base_fee * 2 + self.priority_fee),
+                max_priority_fee: self.cap(self.priority_fee),
+            },
+        })
+    }
+
+    /// Get the fees of a replacement for a stuck transaction, or `None` if
+    /// they would exceed the fee cap.
+    pub fn bump(&self, fees: Fees) -> Option<Fees> {
+        let bump = |fee: U256| fee * (100 + self.bump_percent) / 100;
+        let fees = match fees {
+            Fees::Legacy { gas_price } => Fees::Legacy {
+                gas_price: bump(gas_price),
+            },
+            Fees::Eip1559 {
+                max_fee,
+                max_priority_fee,
+            } => Fees::Eip1559 {
+                max_fee: bump(max_fee),
+                max_priority_fee: bump(max_priority_fee),
+            },
+        };
+
+        match self.max_fee_cap {
+            Some(cap) if fees.max_fee() > cap => None,
+            _ => Some(fees),
+        }
+    }
+
+    /// Limit a fee to the fee cap.
+    fn cap(&self, fee: U256) -> U256 {
+        match self.max_fee_cap {
+            Some(cap) => fee.min(cap),
+            None => fee,
+        }
+    }
+}
+
+/// Convert gwei to wei.
+fn gwei(value: f64) -> U256 {
+    U256::from((value * 1e9).round() as u128)
+}
+
+/// Convert gwei to wei, treating zero as unset.
+fn optional_gwei(value: f64) -> Option<U256> {
+    (value > 0.0).then(|| gwei(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(mode: GasMode) -> GasPolicy {
+        GasPolicy {
+            mode,
+            priority_fee: gwei(2.0),
+            max_fee_cap: Some(gwei(100.0)),
+            gas_limit_multiplier: 1.5,
+            base_fee_ceiling: Some(gwei(50.0)),
+            bump_after: Duration::from_secs(60),
+            bump_percent: 20,
+        }
+    }
+
+    #[test]
+    fn test_gas_mode() {
+        assert_eq!("legacy".parse::<GasMode>().unwrap(), GasMode::Legacy);
+        assert_eq!("EIP1559".parse::<GasMode>().unwrap(), GasMode::Eip1559);
+        assert!("other".parse::<GasMode>().is_err());
+    }
+
+    #[test]
+    fn test_gas_limit() {
+        let policy = policy(GasMode::Eip1559);
+
+        assert_eq!(policy.gas_limit(U256::from(100_000)), U256::from(150_000));
+    }
+
+    #[test]
+    fn test_fees() {
+        let policy = policy(GasMode::Eip1559);
+
+        assert_eq!(
+            policy.fees(gwei(10.0)).unwrap(),
+            Fees::Eip1559 {
+                max_fee: gwei(22.0),
+                max_priority_fee: gwei(2.0),
+            }
+        );
+
+        // The max fee is capped
+        assert_eq!(policy.fees(gwei(50.0)).unwrap().max_fee(), gwei(100.0));
+
+        // Nothing is sent above the base fee ceiling
+        assert!(matches!(
+            policy.fees(gwei(51.0)),
+            Err(ChainError::FeeTooHigh { .. })
+        ));
+
+        let policy = GasPolicy {
+            max_fee_cap: Some(gwei(20.0)),
+            ..self::policy(GasMode::Legacy)
+        };
+
+        assert_eq!(
+            policy.fees(gwei(30.0)).unwrap(),
+            Fees::Legacy {
+                gas_price: gwei(20.0)
+            }
+        );
+    }
+
+    #[test]
+    fn test_bump() {
+        let policy = policy(GasMode::Eip1559);
+        let fees = Fees::Eip1559 {
+            max_fee: gwei(50.0),
+            max_priority_fee: gwei(2.0),
+        };
+
+        assert_eq!(
+            policy.bump(fees),
+            Some(Fees::Eip1559 {
+                max_fee: gwei(60.0),
+                max_priority_fee: gwei(2.4),
+            })
+        );
+
+        // Fees are not raised above the cap
+        let fees = Fees::Legacy {
+            gas_price: gwei(90.0),
+        };
+
+        assert_eq!(policy.bump(fees), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut tx = TypedTransaction::Eip1559(Eip1559TransactionRequest::new());
+
+        Fees::Legacy {
+            gas_price: gwei(5.0),
+        }
+        .apply(&mut tx);
+
+        assert!(matches!(tx, TypedTransaction::Legacy(_)));
+        assert_eq!(tx.gas_price(), Some(gwei(5.0)));
+
+        Fees::Eip1559 {
+            max_fee: gwei(30.0),
+            max_priority_fee: gwei(1.0),
+        }
+        .apply(&mut tx);
+
+        match tx {
+            TypedTransaction::Eip1559(inner) => {
+                assert_eq!(inner.max_fee_per_gas, Some(gwei(30.0)));
+                assert_eq!(inner.max_priority_fee_per_gas, Some(gwei(1.0)));
+            }
+            _ => panic!("Expected an EIP-1559 transaction"),
+        }
+    }
+}
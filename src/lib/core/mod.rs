@@ -1,3 +1,9 @@
+pub mod amount;
 pub mod chain;
+pub mod eip712;
+pub mod events;
+pub mod gas;
+pub mod nonce;
 pub mod repository;
+pub mod retry;
 pub mod reward;
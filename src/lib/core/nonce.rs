@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use ethers::types::{Address, U256};
+use lazy_static::lazy_static;
+use tokio::sync::Mutex;
+
+lazy_static! {
+    /// The nonce manager shared by all transactions sent from this process.
+    static ref NONCES: NonceManager = NonceManager::default();
+}
+
+/// Get the nonce manager shared by all transactions sent from this process.
+pub fn nonce_manager() -> &'static NonceManager {
+    &NONCES
+}
+
+/// NonceManager hands out consecutive nonces to the wallets sending
+/// transactions, so several transactions from one wallet can be sent without
+/// waiting for each to be mined.
+#[derive(Default)]
+pub struct NonceManager {
+    next: Mutex<HashMap<Address, U256>>,
+}
+
+impl NonceManager {
+    /// Send a transaction from a wallet with the next nonce. The nonce is the
+    /// later of the one after the last transaction sent from here and the
+    /// pending nonce reported by the node, so transactions sent elsewhere are
+    /// accounted for. Sends are serialized so nonces are used in order, and a
+    /// failed send releases its nonce.
+    pub async fn send<T, E, P, F, Fut>(
+        &self,
+        address: Address,
+        pending_nonce: P,
+        send: F,
+    ) -> Result<T, E>
+    where
+        P: Future<Output = Result<U256, E>>,
+        F: FnOnce(U256) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut next = self.next.lock().await;
+        let pending_nonce = pending_nonce.await?;
+        let nonce = match next.get(&address) {
+            Some(nonce) => pending_nonce.max(*nonce),
+            None => pending_nonce,
+        };
+
+        match send(nonce).await {
+            Ok(value) => {
+                next.insert(address, nonce + 1);
+                Ok(value)
+            }
+            Err(e) => {
+                next.remove(&address);
+                Err(e)
+            }
+        }
+    }
+
+    /// Forget the next nonce of a wallet, so the next transaction from it
+    /// uses the pending nonce reported by the node. Called when a transaction
+    /// signed with a nonce handed out here was not sent.
+    pub async fn release(&self, address: Address) {
+        self.next.lock().await.remove(&address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn send(manager: &NonceManager, address: Address, pending: u64) -> Result<U256, ()> {
+        manager
+            .send(
+                address,
+                async { Ok(U256::from(pending)) },
+                |nonce| async move { Ok(nonce) },
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let manager = NonceManager::default();
+        let address = Address::random();
+
+        // Nonces are handed out consecutively while the node lags behind
+        assert_eq!(send(&manager, address, 3).await, Ok(U256::from(3)));
+        assert_eq!(send(&manager, address, 3).await, Ok(U256::from(4)));
+        assert_eq!(send(&manager, address, 4).await, Ok(U256::from(5)));
+
+        // Transactions sent elsewhere move the nonce forward
+        assert_eq!(send(&manager, address, 9).await, Ok(U256::from(9)));
+
+        // Wallets have their own nonces
+        assert_eq!(send(&manager, Address::random(), 0).await, Ok(U256::zero()));
+    }
+
+    #[tokio::test]
+    async fn test_send_failed() {
+        let manager = NonceManager::default();
+        let address = Address::random();
+
+        send(&manager, address, 3).await.unwrap();
+
+        // A failed send falls back to the pending nonce of the node
+        let result: Result<(), ()> = manager
+            .send(address, async { Ok(U256::from(3)) }, |_| async { Err(()) })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(send(&manager, address, 3).await, Ok(U256::from(3)));
+
+        // So does a released nonce
+        manager.release(address).await;
+
+        assert_eq!(send(&manager, address, 3).await, Ok(U256::from(3)));
+    }
+}
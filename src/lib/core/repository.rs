@@ -26,6 +26,8 @@ pub trait Repository<M> {
     fn update(&self, key: String, value: M) -> Result<(), RepositoryError>;
     /// Delete a record from the repository.
     fn delete(&self, key: String) -> Result<M, RepositoryError>;
+    /// Read all records whose key starts with the prefix, ordered by key.
+    fn scan(&self, prefix: String) -> Result<Vec<(String, M)>, RepositoryError>;
 }
 
 #[cfg(test)]
@@ -93,6 +95,21 @@ mod tests {
                 None => Err(RepositoryError::DeletionError),
             }
         }
+
+        fn scan(&self, prefix: String) -> Result<Vec<(String, M)>, RepositoryError> {
+            let map = self.map.read().map_err(|_| RepositoryError::ReadError)?;
+            let mut records = map
+                .iter()
+                .filter(|(key, _)| key.starts_with(&prefix))
+                .map(|(key, value)| {
+                    let value =
+                        serde_json::from_slice(value).map_err(|_| RepositoryError::ReadError)?;
+                    Ok((key.clone(), value))
+                })
+                .collect::<Result<Vec<(String, M)>, RepositoryError>>()?;
+            records.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(records)
+        }
     }
 
     #[derive(Clone, Debug, Serialize, PartialEq, Deserialize)]
@@ -121,4 +138,24 @@ mod tests {
         // Test delete of non-existent key to throw error
         assert!(repo.delete(key.clone()).is_err());
     }
+
+    #[test]
+    fn test_scan() {
+        let repo: HashMapRepository<TestModel> = HashMapRepository::new();
+
+        repo.create("a:2".to_string(), TestModel(vec![2])).unwrap();
+        repo.create("a:1".to_string(), TestModel(vec![1])).unwrap();
+        repo.create("b:1".to_string(), TestModel(vec![3])).unwrap();
+
+        // Only records with the prefix are returned, ordered by key
+        let records = repo.scan("a:".to_string()).unwrap();
+
+        assert_eq!(
+            records,
+            vec![
+                ("a:1".to_string(), TestModel(vec![1])),
+                ("a:2".to_string(), TestModel(vec![2])),
+            ]
+        );
+    }
 }
@@ -0,0 +1,478 @@
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ethers::core::rand::{thread_rng, Rng};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::utils::config::parse_env;
+
+lazy_static! {
+    /// The circuit breaker shared by all RPC calls.
+    static ref RPC_CIRCUIT: CircuitBreaker = CircuitBreaker::from_env();
+}
+
+/// Get the circuit breaker shared by all RPC calls.
+pub fn rpc_circuit() -> &'static CircuitBreaker {
+    &RPC_CIRCUIT
+}
+
+/// ErrorClass is the kind of failure an RPC call ended with, which decides
+/// if the call is retried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorClass {
+    /// The node could not be reached or did not answer in time.
+    Connection,
+    /// The transaction nonce was already used or is too far ahead.
+    Nonce,
+    /// The transaction fee was too low to be accepted.
+    Underpriced,
+    /// The call was executed and reverted.
+    Revert,
+    /// Any other failure.
+    Other,
+}
+
+impl ErrorClass {
+    /// If a call that failed this way may succeed when retried as is. A
+    /// transaction rejected for its nonce or fees fails the same way when
+    /// sent again, so those are left to the caller to re-price or resend.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorClass::Connection)
+    }
+}
+
+/// Classify is implemented by errors that know how the call failed.
+pub trait Classify {
+    /// Get the class of the failure.
+    fn class(&self) -> ErrorClass;
+}
+
+impl Classify for Error {
+    fn class(&self) -> ErrorClass {
+        match self.kind() {
+            ErrorKind::TimedOut
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe => ErrorClass::Connection,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+/// The JSON-RPC error code of a rate limited request (EIP-1474).
+const LIMIT_EXCEEDED: i64 = -32005;
+
+/// The JSON-RPC error code some providers use for rate limited requests.
+const TOO_MANY_REQUESTS: i64 = 429;
+
+/// The JSON-RPC error code of a reverted call with revert data.
+const EXECUTION_REVERTED: i64 = 3;
+
+/// Classify a JSON-RPC error answered by the node from its code, and for
+/// the generic server error code from its message.
+pub fn classify(code: i64, message: &str) -> ErrorClass {
+    let message = message.to_lowercase();
+    let matches_any = |patterns: &[&str]| patterns.iter().any(|p| message.contains(p));
+
+    match code {
+        LIMIT_EXCEEDED | TOO_MANY_REQUESTS => ErrorClass::Connection,
+        EXECUTION_REVERTED => ErrorClass::Revert,
+        _ if matches_any(&["execution reverted"]) => ErrorClass::Revert,
+        _ if matches_any(&[
+            "nonce too low",
+            "nonce too high",
+            "invalid nonce",
+            "already known",
+        ]) =>
+        {
+            ErrorClass::Nonce
+        }
+        _ if matches_any(&["underpriced", "fee too low", "less than block base fee"]) => {
+            ErrorClass::Underpriced
+        }
+        _ => ErrorClass::Other,
+    }
+}
+
+/// RetryPolicy is how often and how quickly failed RPC calls are retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The maximum delay between retries.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Read the retry policy from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            max_attempts: parse_env("RPC_RETRY_MAX_ATTEMPTS", 3).max(1),
+            base_delay: Duration::from_millis(parse_env("RPC_RETRY_BASE_DELAY_MS", 200)),
+            max_delay: Duration::from_millis(parse_env("RPC_RETRY_MAX_DELAY_MS", 5000)),
+        }
+    }
+
+    /// Get the delay before a retry. The delay doubles with every attempt up
+    /// to the maximum, and a random jitter of up to half the delay is taken
+    /// off so clients do not retry in lockstep.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let jitter = thread_rng().gen_range(0.0..=0.5);
+
+        delay.mul_f64(1.0 - jitter)
+    }
+}
+
+/// CircuitState is the state of a circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum CircuitState {
+    /// Calls are allowed.
+    Closed,
+    /// Calls fail fast without reaching the node.
+    Open,
+    /// A trial call is allowed to check if the node has recovered.
+    HalfOpen,
+}
+
+struct CircuitInner {
+    failures: u32,
+    opened_at: Option<Instant>,
+    /// When the trial call of the half open circuit was let through.
+    trial_at: Option<Instant>,
+}
+
+/// CircuitBreaker stops calls to the node after repeated connection failures
+/// so requests fail fast while it is down.
+pub struct CircuitBreaker {
+    inner: Mutex<CircuitInner>,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+}
+
+impl CircuitBreaker {
+    /// Create a new closed circuit breaker.
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            inner: Mutex::new(CircuitInner {
+                failures: 0,
+                opened_at: None,
+                trial_at: None,
+            }),
+            failure_threshold: failure_threshold.max(1),
+            reset_timeout,
+        }
+    }
+
+    /// Read the circuit breaker configuration from the environment.
+    pub fn from_env() -> Self {
+        Self::new(
+            parse_env("RPC_CIRCUIT_FAILURE_THRESHOLD", 5),
+            Duration::from_secs(parse_env("RPC_CIRCUIT_RESET_TIMEOUT", 30)),
+        )
+    }
+
+    /// Get the current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        self.state_of(&self.inner.lock().unwrap())
+    }
+
+    fn state_of(&self, inner: &CircuitInner) -> CircuitState {
+        match inner.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() >= self.reset_timeout => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    /// Get the number of consecutive connection failures.
+    pub fn failures(&self) -> u32 {
+        self.inner.lock().unwrap().failures
+    }
+
+    /// If a call is allowed to reach the node. While the circuit is half open
+    /// only one trial call is let through, and another one only if the trial
+    /// did not finish within the reset timeout.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+
+        match self.state_of(&inner) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => match inner.trial_at {
+                Some(trial_at) if trial_at.elapsed() < self.reset_timeout => false,
+                _ => {
+                    inner.trial_at = Some(Instant::now());
+                    true
+                }
+            },
+        }
+    }
+
+    /// Record that the node answered, closing the circuit.
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.failures = 0;
+        inner.opened_at = None;
+        inner.trial_at = None;
+    }
+
+    /// Record that the node could not be reached, opening the circuit once
+    /// the failure threshold is reached.
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.failures += 1;
+        inner.trial_at = None;
+
+        if inner.failures >= self.failure_threshold {
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Run an RPC call, retrying retryable failures according to the policy.
+/// Fails fast without calling the node while the circuit is open.
+pub async fn with_retry<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    circuit: &CircuitBreaker,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: Classify + From<Error>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        if !circuit.allow() {
+            return Err(
+                Error::new(ErrorKind::ConnectionAborted, "RPC circuit breaker is open").into(),
+            );
+        }
+
+        attempt += 1;
+
+        match operation().await {
+            Ok(value) => {
+                circuit.record_success();
+                return Ok(value);
+            }
+            Err(e) => {
+                let class = e.class();
+
+                // Only failures to reach the node count towards the circuit
+                if class == ErrorClass::Connection {
+                    circuit.record_failure();
+                } else {
+                    circuit.record_success();
+                }
+
+                if !class.is_retryable() || attempt >= policy.max_attempts {
+                    return Err(e);
+                }
+
+                tokio::time::sleep(policy.delay(attempt)).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let cases = vec![
+            (3, "execution reverted", ErrorClass::Revert),
+            (
+                -32000,
+                "execution reverted: Error: Token does not exist!",
+                ErrorClass::Revert,
+            ),
+            (-32000, "nonce too low", ErrorClass::Nonce),
+            (-32000, "already known", ErrorClass::Nonce),
+            (
+                -32000,
+                "replacement transaction underpriced",
+                ErrorClass::Underpriced,
+            ),
+            (
+                -32000,
+                "max fee per gas less than block base fee",
+                ErrorClass::Underpriced,
+            ),
+            (-32005, "limit exceeded", ErrorClass::Connection),
+            (429, "too many requests", ErrorClass::Connection),
+            // Digits in addresses and amounts are not status codes
+            (
+                -32000,
+                "insufficient funds for gas * price + value: address 0x503502 have 502 want 503",
+                ErrorClass::Other,
+            ),
+        ];
+
+        for (code, message, class) in cases {
+            assert_eq!(classify(code, message), class, "{}", message);
+        }
+
+        let error = Error::new(ErrorKind::TimedOut, "operation timed out");
+        assert_eq!(error.class(), ErrorClass::Connection);
+        assert_eq!(Error::other("503").class(), ErrorClass::Other);
+
+        // Rejected transactions are left to the caller
+        assert!(ErrorClass::Connection.is_retryable());
+        assert!(!ErrorClass::Nonce.is_retryable());
+        assert!(!ErrorClass::Underpriced.is_retryable());
+        assert!(!ErrorClass::Revert.is_retryable());
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+
+        for _ in 0..10 {
+            let first = policy.delay(1);
+            let second = policy.delay(2);
+            let capped = policy.delay(10);
+
+            // The delay doubles with jitter of up to half
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+            // The delay never exceeds the maximum
+            assert!(capped <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let circuit = CircuitBreaker::new(2, Duration::from_millis(20));
+
+        assert_eq!(circuit.state(), CircuitState::Closed);
+
+        // The circuit opens once the threshold is reached
+        circuit.record_failure();
+        assert_eq!(circuit.state(), CircuitState::Closed);
+        circuit.record_failure();
+        assert_eq!(circuit.state(), CircuitState::Open);
+        assert!(!circuit.allow());
+
+        // One trial call is allowed after the reset timeout
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(circuit.state(), CircuitState::HalfOpen);
+        assert!(circuit.allow());
+        assert!(!circuit.allow());
+
+        // Another trial is allowed if the first one does not finish
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(circuit.allow());
+        assert!(!circuit.allow());
+
+        // A failed trial opens the circuit again
+        circuit.record_failure();
+        assert_eq!(circuit.state(), CircuitState::Open);
+
+        // A success closes the circuit
+        circuit.record_success();
+        assert_eq!(circuit.state(), CircuitState::Closed);
+        assert_eq!(circuit.failures(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_retry() {
+        let circuit = CircuitBreaker::new(10, Duration::from_secs(60));
+        let calls = AtomicU32::new(0);
+
+        // Retryable failures are retried until the call succeeds
+        let result: Result<_, Error> = with_retry(&policy(3), &circuit, || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(Error::new(ErrorKind::ConnectionReset, "connection reset")),
+                _ => Ok(1),
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Other failures are not retried
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), Error> = with_retry(&policy(3), &circuit, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(Error::other("execution reverted"))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Retries stop after the maximum attempts
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), Error> = with_retry(&policy(3), &circuit, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(Error::new(ErrorKind::TimedOut, "operation timed out"))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_circuit_open() {
+        let circuit = CircuitBreaker::new(2, Duration::from_secs(60));
+        let calls = AtomicU32::new(0);
+
+        // Connection failures open the circuit
+        let result: Result<(), Error> = with_retry(&policy(5), &circuit, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(Error::new(
+                ErrorKind::ConnectionRefused,
+                "connection refused",
+            ))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(circuit.state(), CircuitState::Open);
+
+        // Calls fail fast while the circuit is open
+        let result: Result<(), Error> = with_retry(&policy(5), &circuit, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ConnectionAborted);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,7 +1,13 @@
 use thiserror::Error;
+use uuid::Uuid;
 
+use crate::models::authorization::AuthorizationError;
+use crate::models::campaign::CampaignError;
+use crate::models::funding::FundingError;
 use crate::models::user::UserError;
 
+use super::chain::ChainError;
+
 use super::repository::RepositoryError;
 
 pub trait Reward {
@@ -21,12 +27,30 @@ pub enum RewardError {
     AlreadyExists,
     #[error("Reward already redeemed")]
     AlreadyRedeemed,
+    #[error("Reward expired")]
+    Expired,
+    #[error("Reward revoked")]
+    Revoked,
+    #[error("Reward already owned by the recipient")]
+    SameOwner,
+    #[error("Invalid reward value: {0}")]
+    InvalidValue(String),
     #[error("Repository error")]
     RepositoryError(#[from] RepositoryError),
-    #[error("Failed to mint reward")]
-    MintRewardError,
+    #[error("Failed to mint reward: {0}")]
+    MintRewardError(String),
     #[error("Unknown error")]
     UnknownError(#[from] std::io::Error),
     #[error("User error")]
     UserError(#[from] UserError),
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
+    #[error("Funding error")]
+    FundingError(#[from] FundingError),
+    #[error("Authorization error")]
+    AuthorizationError(#[from] AuthorizationError),
+    #[error("Campaign error")]
+    CampaignError(#[from] CampaignError),
+    #[error("Queued as outbox entry {0}")]
+    Queued(Uuid),
 }
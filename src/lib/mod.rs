@@ -1,8 +1,11 @@
+#[cfg(feature = "client")]
+pub mod client;
 pub mod core;
 pub mod models;
 pub mod rewards;
 pub mod services;
 pub mod storage;
 pub mod utils;
+pub mod workers;
 
 pub const VERSION: &str = "v1";
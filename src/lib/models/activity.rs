@@ -0,0 +1,274 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::core::events::{event_bus, Event, EventKind};
+use crate::core::repository::{Repository, RepositoryError};
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::helpers::now;
+
+/// The prefix of the activity entries in the repository.
+const ACTIVITY_KEY_PREFIX: &str = "activity:";
+
+/// The last sequence number handed out, so entries recorded in the same
+/// nanosecond keep their order.
+static LAST_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// ActivityKind is what happened to an account.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum ActivityKind {
+    /// A reward was issued to the user.
+    Issued,
+    /// The token of a reward was minted to the user's wallet.
+    Minted,
+    /// The user redeemed a reward.
+    Redeemed,
+    /// The token of a reward was burned and its value credited.
+    Burned,
+    /// A reward was split into new rewards.
+    Split,
+    /// The token of an expired reward was burned.
+    Expired,
+    /// The token of a reward was burned by an admin.
+    Revoked,
+    /// A reward was transferred to another user.
+    TransferSent,
+    /// A reward was transferred from another user.
+    TransferReceived,
+    /// Reward tokens were sent to an external address.
+    Withdrawn,
+    /// An on-chain action could not be completed.
+    Failed,
+}
+
+impl FromStr for ActivityKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "issued" => Ok(ActivityKind::Issued),
+            "minted" => Ok(ActivityKind::Minted),
+            "redeemed" => Ok(ActivityKind::Redeemed),
+            "burned" => Ok(ActivityKind::Burned),
+            "split" => Ok(ActivityKind::Split),
+            "expired" => Ok(ActivityKind::Expired),
+            "revoked" => Ok(ActivityKind::Revoked),
+            "transfer_sent" => Ok(ActivityKind::TransferSent),
+            "transfer_received" => Ok(ActivityKind::TransferReceived),
+            "withdrawn" => Ok(ActivityKind::Withdrawn),
+            "failed" => Ok(ActivityKind::Failed),
+            _ => Err(format!("Unknown activity kind: {}", s)),
+        }
+    }
+}
+
+/// Activity is an entry in the append-only history of a user's account.
+/// Entries are never updated or deleted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Activity {
+    /// The id of the entry.
+    pub id: Uuid,
+    /// The user the entry belongs to.
+    pub user_id: Uuid,
+    /// What happened.
+    pub kind: ActivityKind,
+    /// The reward involved, if any.
+    pub reward_id: Option<Uuid>,
+    /// The amount involved, in reward token units.
+    pub amount: Option<U256>,
+    /// The hash of the transaction, if one was sent.
+    pub tx_hash: Option<String>,
+    /// The other user or the external address involved, if any.
+    pub counterparty: Option<String>,
+    /// Why it happened, such as a revocation reason or an error.
+    pub detail: Option<String>,
+    /// The position of the entry in the history of the user.
+    pub sequence: u64,
+    /// When it happened, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl Activity {
+    /// Create a new entry.
+    pub fn new(user_id: Uuid, kind: ActivityKind) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            kind,
+            reward_id: None,
+            amount: None,
+            tx_hash: None,
+            counterparty: None,
+            detail: None,
+            sequence: next_sequence(),
+            created_at: now(),
+        }
+    }
+
+    /// Set the reward involved.
+    pub fn with_reward(mut self, reward_id: Uuid) -> Self {
+        self.reward_id = Some(reward_id);
+        self
+    }
+
+    /// Set the amount involved.
+    pub fn with_amount(mut self, amount: U256) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Set the hash of the transaction.
+    pub fn with_tx_hash(mut self, tx_hash: Option<String>) -> Self {
+        self.tx_hash = tx_hash;
+        self
+    }
+
+    /// Set the other user or external address involved.
+    pub fn with_counterparty(mut self, counterparty: String) -> Self {
+        self.counterparty = Some(counterparty);
+        self
+    }
+
+    /// Set why it happened.
+    pub fn with_detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// Get the history of a user, oldest first.
+    pub async fn for_user(user_id: Uuid) -> Result<Vec<Self>, RepositoryError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| RepositoryError::ConnectionError)?;
+        let entries: Vec<(String, Activity)> =
+            db.scan(format!("{}{}:", ACTIVITY_KEY_PREFIX, user_id))?;
+
+        // Keys are ordered by sequence
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// Append the entry to the history of its user, and publish it to the
+    /// event bus if clients listen for it.
+    pub async fn record(&self) -> Result<(), RepositoryError> {
+        {
+            let connection = get_sled_db()?;
+            let db = connection
+                .write()
+                .map_err(|_| RepositoryError::ConnectionError)?;
+
+            db.create(activity_key(&self.user_id, self.sequence), self.clone())?;
+        }
+
+        if let Some(event) = self.event() {
+            event_bus().publish(event);
+        }
+
+        Ok(())
+    }
+
+    /// Get the live event of the entry, if it has one.
+    pub fn event(&self) -> Option<Event> {
+        let kind = match self.kind {
+            ActivityKind::Issued => EventKind::RewardIssued,
+            ActivityKind::Minted => EventKind::RewardMinted,
+            ActivityKind::Redeemed => EventKind::RewardRedeemed,
+            _ => return None,
+        };
+
+        Some(Event {
+            reward_id: self.reward_id,
+            amount: self.amount.map(|amount| amount.to_string()),
+            tx_hash: self.tx_hash.clone(),
+            ..Event::new(kind, self.user_id)
+        })
+    }
+}
+
+impl SledModel for Activity {}
+
+/// Get the repository key of an entry. The sequence is zero padded so the
+/// keys of a user sort in the order the entries were recorded.
+fn activity_key(user_id: &Uuid, sequence: u64) -> String {
+    format!("{}{}:{:020}", ACTIVITY_KEY_PREFIX, user_id, sequence)
+}
+
+/// Get the next sequence number. Sequence numbers are the time in
+/// nanoseconds, raised when needed so they always increase.
+fn next_sequence() -> u64 {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let previous = LAST_SEQUENCE
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+            Some(time.max(last + 1))
+        })
+        .unwrap_or_default();
+
+    time.max(previous + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_sequence() {
+        let a = next_sequence();
+        let b = next_sequence();
+
+        assert!(b > a);
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(
+            "transfer_sent".parse::<ActivityKind>(),
+            Ok(ActivityKind::TransferSent)
+        );
+        assert!("unknown".parse::<ActivityKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record() {
+        let user_id = Uuid::new_v4();
+        let reward_id = Uuid::new_v4();
+
+        Activity::new(user_id, ActivityKind::Issued)
+            .with_reward(reward_id)
+            .with_amount(U256::from(100))
+            .record()
+            .await
+            .unwrap();
+        Activity::new(user_id, ActivityKind::Minted)
+            .with_reward(reward_id)
+            .with_tx_hash(Some("0x1".into()))
+            .record()
+            .await
+            .unwrap();
+
+        // Entries are listed in the order they were recorded
+        let history = Activity::for_user(user_id).await.unwrap();
+        let kinds: Vec<ActivityKind> = history.iter().map(|entry| entry.kind).collect();
+
+        assert_eq!(kinds, [ActivityKind::Issued, ActivityKind::Minted]);
+
+        // Reward events are published to the event bus
+        let events: Vec<Event> = event_bus()
+            .since(0)
+            .into_iter()
+            .filter(|event| event.user_id == user_id)
+            .collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].kind, EventKind::RewardMinted);
+        assert_eq!(events[1].tx_hash.as_deref(), Some("0x1"));
+        assert!(Activity::for_user(Uuid::new_v4()).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+use std::str::FromStr;
+
+use ethers::signers::Signer;
+use ethers::types::{Address, Signature, U256};
+use thiserror::Error;
+
+use crate::core::chain::ChainError;
+use crate::core::eip712::BurnAuthorization;
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+use super::user::{User, UserError};
+
+#[derive(Debug, Error)]
+pub enum AuthorizationError {
+    #[error("Authorization is not signed by the token owner")]
+    InvalidSignature,
+    #[error("Authorization is for another chain or contract")]
+    InvalidDomain,
+    #[error("Authorization has expired")]
+    Expired,
+    #[error("Authorization nonce is not the next nonce")]
+    InvalidNonce { expected: U256, actual: U256 },
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
+    #[error("User error")]
+    UserError(#[from] UserError),
+}
+
+/// RedeemMode is who signs and pays for the burn of a redeemed reward.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RedeemMode {
+    /// The owner's wallet sends the burn, topped up with gas as needed.
+    Owner,
+    /// The owner signs a burn authorization and the admin relayer submits it
+    /// to the contract, so the owner's wallet never needs gas.
+    Relayer,
+}
+
+impl RedeemMode {
+    /// Read the redeem mode from the environment.
+    pub fn from_env() -> Self {
+        parse_env("REDEEM_MODE", RedeemMode::Owner)
+    }
+}
+
+impl FromStr for RedeemMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "owner" => Ok(RedeemMode::Owner),
+            "relayer" => Ok(RedeemMode::Relayer),
+            _ => Err(format!("Unknown redeem mode: {}", s)),
+        }
+    }
+}
+
+/// Get the nonce the next burn authorization of a wallet must use. The
+/// contract keeps the nonces and uses each one up when it accepts an
+/// authorization, so a signed authorization cannot be replayed.
+#[cfg(not(test))]
+pub async fn next_nonce(address: Address) -> Result<U256, AuthorizationError> {
+    Ok(crate::core::chain::get_burn_nonce(address).await?)
+}
+
+#[cfg(test)]
+pub async fn next_nonce(_address: Address) -> Result<U256, AuthorizationError> {
+    Ok(U256::zero())
+}
+
+/// Have a user sign an authorization for the relayer to burn one of their
+/// tokens. The authorization uses their next nonce and expires after
+/// `BURN_AUTHORIZATION_TTL` seconds.
+pub async fn authorize_burn(
+    owner: &User,
+    token_id: U256,
+) -> Result<(BurnAuthorization, Signature), AuthorizationError> {
+    let address = owner.get_wallet()?.address();
+    let (chain_id, verifying_contract) = get_domain();
+
+    let authorization = BurnAuthorization {
+        owner: address,
+        token_id,
+        nonce: next_nonce(address).await?,
+        deadline: now() + parse_env("BURN_AUTHORIZATION_TTL", 300),
+        chain_id,
+        verifying_contract,
+    };
+    let signature = owner.sign_burn_authorization(&authorization).await?;
+
+    Ok((authorization, signature))
+}
+
+/// Check a burn authorization signed by the owner of a token before it is
+/// submitted, so an authorization the contract would reject is not sent.
+/// The contract checks it again and uses up its nonce.
+pub async fn verify_burn_authorization(
+    authorization: &BurnAuthorization,
+    signature: &Signature,
+    owner: Address,
+) -> Result<(), AuthorizationError> {
+    if (authorization.chain_id, authorization.verifying_contract) != get_domain() {
+        return Err(AuthorizationError::InvalidDomain);
+    }
+
+    if authorization.owner != owner || !authorization.is_signed_by_owner(signature) {
+        return Err(AuthorizationError::InvalidSignature);
+    }
+
+    if authorization.deadline < now() {
+        return Err(AuthorizationError::Expired);
+    }
+
+    let expected = next_nonce(owner).await?;
+
+    if authorization.nonce != expected {
+        return Err(AuthorizationError::InvalidNonce {
+            expected,
+            actual: authorization.nonce,
+        });
+    }
+
+    Ok(())
+}
+
+/// Get the chain id and NFT contract address authorizations are bound to.
+#[cfg(not(test))]
+fn get_domain() -> (u64, Address) {
+    (
+        crate::core::chain::get_chain_id(),
+        crate::core::chain::get_reward_nft_address(),
+    )
+}
+
+#[cfg(test)]
+fn get_domain() -> (u64, Address) {
+    (31337, Address::repeat_byte(0x11))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::core::chain::generate_secret_key;
+
+    use super::*;
+
+    #[test]
+    fn test_redeem_mode() {
+        assert_eq!(
+            "relayer".parse::<RedeemMode>().unwrap(),
+            RedeemMode::Relayer
+        );
+        assert_eq!("Owner".parse::<RedeemMode>().unwrap(), RedeemMode::Owner);
+        assert!("other".parse::<RedeemMode>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_burn_authorization() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        let address = user.get_wallet().unwrap().address();
+        let (authorization, signature) = authorize_burn(&user, U256::from(1)).await.unwrap();
+
+        // The authorization uses the owner's next nonce on the contract
+        assert_eq!(authorization.nonce, next_nonce(address).await.unwrap());
+
+        // The authorization is only accepted for its owner
+        assert!(matches!(
+            verify_burn_authorization(&authorization, &signature, Address::random()).await,
+            Err(AuthorizationError::InvalidSignature)
+        ));
+
+        verify_burn_authorization(&authorization, &signature, address)
+            .await
+            .unwrap();
+
+        // An authorization for any nonce but the next one is rejected
+        let replayed = BurnAuthorization {
+            nonce: U256::one(),
+            ..authorization
+        };
+        let signature = user.sign_burn_authorization(&replayed).await.unwrap();
+
+        assert!(matches!(
+            verify_burn_authorization(&replayed, &signature, address).await,
+            Err(AuthorizationError::InvalidNonce { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_burn_authorization_rejected() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        let address = user.get_wallet().unwrap().address();
+        let (authorization, signature) = authorize_burn(&user, U256::from(1)).await.unwrap();
+
+        // Changing the authorization invalidates the signature
+        let tampered = BurnAuthorization {
+            token_id: U256::from(2),
+            ..authorization.clone()
+        };
+
+        assert!(matches!(
+            verify_burn_authorization(&tampered, &signature, address).await,
+            Err(AuthorizationError::InvalidSignature)
+        ));
+
+        // Authorizations for another contract are rejected
+        let other_contract = BurnAuthorization {
+            verifying_contract: Address::random(),
+            ..authorization.clone()
+        };
+
+        assert!(matches!(
+            verify_burn_authorization(&other_contract, &signature, address).await,
+            Err(AuthorizationError::InvalidDomain)
+        ));
+
+        // Expired authorizations are rejected even with a valid signature
+        let expired = BurnAuthorization {
+            deadline: now() - 1,
+            ..authorization
+        };
+        let signature = user.sign_burn_authorization(&expired).await.unwrap();
+
+        assert!(matches!(
+            verify_burn_authorization(&expired, &signature, address).await,
+            Err(AuthorizationError::Expired)
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use ethers::types::U256;
+use ethers::utils::format_units;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::core::chain::TokenMetadata;
+use crate::core::reward::RewardError;
+use crate::rewards::Reward;
+use crate::utils::helpers::now;
+
+use super::reward::{RewardNFT, RewardStatus};
+use super::user::User;
+
+/// RewardCounts is the number of rewards of a user in each state.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RewardCounts {
+    /// Rewards that can be redeemed.
+    pub active: usize,
+    pub redeemed: usize,
+    /// Rewards past their expiry, whether or not their token was burned yet.
+    pub expired: usize,
+    pub revoked: usize,
+    pub split: usize,
+}
+
+impl RewardCounts {
+    /// Count rewards by state at a unix timestamp, returning the counts and
+    /// the total value of the rewards that can still be redeemed.
+    pub fn from_rewards(rewards: &[RewardNFT], at: u64) -> (Self, U256) {
+        let mut counts = Self::default();
+        let mut unredeemed = U256::zero();
+
+        for reward in rewards {
+            match reward.get_status() {
+                RewardStatus::Active if reward.is_expired(at) => counts.expired += 1,
+                RewardStatus::Active => {
+                    counts.active += 1;
+                    unredeemed += reward.get_value();
+                }
+                RewardStatus::Redeemed => counts.redeemed += 1,
+                RewardStatus::Expired => counts.expired += 1,
+                RewardStatus::Revoked => counts.revoked += 1,
+                RewardStatus::Split => counts.split += 1,
+            }
+        }
+
+        (counts, unredeemed)
+    }
+}
+
+/// Balance is what a user holds: reward tokens in their wallet and rewards
+/// not redeemed yet.
+#[derive(Clone, Debug)]
+pub struct Balance {
+    /// The reward token balance of the user's wallet.
+    pub tokens: U256,
+    /// The symbol and decimals of the reward token.
+    pub metadata: TokenMetadata,
+    /// The total value of the rewards that can still be redeemed.
+    pub unredeemed: U256,
+    /// The number of rewards in each state.
+    pub counts: RewardCounts,
+}
+
+impl Balance {
+    /// Get the balance of a user.
+    pub async fn for_user(user: &User) -> Result<Self, RewardError> {
+        let tokens = user.get_reward_balance().await?;
+        let metadata = get_token_metadata().await?;
+        let rewards = RewardNFT::by_owner(user.id).await?;
+        let (counts, unredeemed) = RewardCounts::from_rewards(&rewards, now());
+
+        Ok(Self {
+            tokens,
+            metadata,
+            unredeemed,
+            counts,
+        })
+    }
+
+    /// Get the token balance in whole tokens with the token symbol.
+    pub fn formatted(&self) -> String {
+        format!(
+            "{} {}",
+            format_amount(self.tokens, self.metadata.decimals),
+            self.metadata.symbol
+        )
+    }
+}
+
+/// Format an amount in the smallest unit as a decimal number of whole
+/// tokens, without trailing zeros.
+pub fn format_amount(amount: U256, decimals: u8) -> String {
+    let formatted = format_units(amount, decimals as u32).unwrap_or_else(|_| amount.to_string());
+
+    match formatted.split_once('.') {
+        Some((whole, fraction)) => match fraction.trim_end_matches('0') {
+            "" => whole.to_string(),
+            fraction => format!("{}.{}", whole, fraction),
+        },
+        None => formatted,
+    }
+}
+
+/// Get the symbol and decimals of the reward token.
+#[cfg(not(test))]
+pub(crate) async fn get_token_metadata() -> Result<TokenMetadata, RewardError> {
+    Ok(crate::core::chain::get_reward_token_metadata().await?)
+}
+
+#[cfg(test)]
+pub(crate) async fn get_token_metadata() -> Result<TokenMetadata, RewardError> {
+    Ok(TokenMetadata {
+        symbol: "RWD".into(),
+        decimals: 18,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::models::reward::Revocation;
+    use crate::utils::helpers::random_u256;
+
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        let ether = U256::exp10(18);
+
+        assert_eq!(format_amount(ether * 3 / 2, 18), "1.5");
+        assert_eq!(format_amount(ether * 2, 18), "2");
+        assert_eq!(format_amount(U256::from(1), 18), "0.000000000000000001");
+        assert_eq!(format_amount(U256::from(1337), 0), "1337");
+    }
+
+    #[test]
+    fn test_from_rewards() {
+        let owner = User::new(Uuid::new_v4(), "test".to_string());
+        let reward = |value: u64| RewardNFT::new(owner.clone(), U256::from(value), random_u256());
+        let mut redeemed = reward(20);
+        redeemed.set_redeemed(true);
+        let mut revoked = reward(40);
+        revoked.set_revoked(Revocation {
+            reason: "test".into(),
+            actor: "admin".into(),
+            revoked_at: now(),
+        });
+        let expired = reward(30).with_expiry(Some(now() - 1));
+
+        let (counts, unredeemed) = RewardCounts::from_rewards(
+            &[reward(10), reward(15), redeemed, revoked, expired],
+            now(),
+        );
+
+        // Only active rewards that have not expired count towards the value
+        assert_eq!(unredeemed, U256::from(25));
+        assert_eq!(
+            counts,
+            RewardCounts {
+                active: 2,
+                redeemed: 1,
+                expired: 1,
+                revoked: 1,
+                split: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::core::repository::{Repository, RepositoryError};
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::helpers::now;
+
+/// The prefix of the campaigns in the repository.
+const CAMPAIGN_KEY_PREFIX: &str = "campaign:";
+
+#[derive(Debug, Error)]
+pub enum CampaignError {
+    #[error("Campaign not found")]
+    NotFound,
+    #[error("Campaign is not active")]
+    NotActive,
+    #[error("Campaign budget exceeded")]
+    BudgetExceeded { requested: U256, remaining: U256 },
+    #[error("Campaign cap per user exceeded")]
+    UserCapExceeded { requested: U256, remaining: U256 },
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+}
+
+/// CampaignStatus is whether a campaign accepts new rewards.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum CampaignStatus {
+    /// Rewards are issued while the campaign is running.
+    Active,
+    /// Rewards are refused until the campaign is resumed.
+    Paused,
+    /// Rewards are refused for good.
+    Closed,
+}
+
+impl FromStr for CampaignStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "active" => Ok(CampaignStatus::Active),
+            "paused" => Ok(CampaignStatus::Paused),
+            "closed" => Ok(CampaignStatus::Closed),
+            _ => Err(format!("Unknown campaign status: {}", s)),
+        }
+    }
+}
+
+/// Campaign is a budget rewards are issued from, with a cap on how much a
+/// single user may receive. Amounts are in reward token units.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Campaign {
+    /// The id of the campaign.
+    pub id: Uuid,
+    /// The name of the campaign.
+    pub name: String,
+    /// The total value of the rewards the campaign may issue.
+    pub budget: U256,
+    /// The most a single user may receive from the campaign.
+    pub per_user_cap: U256,
+    /// When the campaign starts, as a unix timestamp.
+    pub starts_at: u64,
+    /// When the campaign ends, as a unix timestamp.
+    pub ends_at: u64,
+    /// If the campaign accepts new rewards.
+    pub status: CampaignStatus,
+    /// The value of the rewards issued or being issued.
+    pub spent: U256,
+    /// The value issued or being issued to each user.
+    pub spent_by_user: HashMap<Uuid, U256>,
+    /// How long rewards issued from the campaign can be redeemed, in seconds.
+    pub reward_ttl: Option<u64>,
+    /// When the campaign was created, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl Campaign {
+    /// Create a new active campaign. Fails if the campaign could never issue
+    /// a reward.
+    pub fn new(
+        name: String,
+        budget: U256,
+        per_user_cap: U256,
+        starts_at: u64,
+        ends_at: u64,
+    ) -> Result<Self, String> {
+        if name.trim().is_empty() {
+            return Err("Campaign name must not be empty".into());
+        }
+
+        if budget.is_zero() || per_user_cap.is_zero() {
+            return Err("Campaign budget and cap must be greater than zero".into());
+        }
+
+        if ends_at <= starts_at {
+            return Err("Campaign must end after it starts".into());
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            name,
+            budget,
+            per_user_cap,
+            starts_at,
+            ends_at,
+            status: CampaignStatus::Active,
+            spent: U256::zero(),
+            spent_by_user: HashMap::new(),
+            reward_ttl: None,
+            created_at: now(),
+        })
+    }
+
+    /// Set how long rewards issued from the campaign can be redeemed.
+    pub fn with_reward_ttl(mut self, reward_ttl: Option<u64>) -> Self {
+        self.reward_ttl = reward_ttl;
+        self
+    }
+
+    /// Get when a reward issued from the campaign at the given time expires.
+    pub fn reward_expiry(&self, issued_at: u64) -> Option<u64> {
+        self.reward_ttl.map(|ttl| issued_at.saturating_add(ttl))
+    }
+
+    /// Look up a campaign by id from the repository.
+    pub async fn from_id(id: Uuid) -> Result<Self, CampaignError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| CampaignError::RepositoryError(RepositoryError::ConnectionError))?;
+        let campaign: Option<Campaign> = db.read(campaign_key(&id))?;
+
+        campaign.ok_or(CampaignError::NotFound)
+    }
+
+    /// Save the campaign to the repository.
+    pub async fn save(&self) -> Result<(), CampaignError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| CampaignError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(campaign_key(&self.id), self.clone())?)
+    }
+
+    /// Get the budget left to issue.
+    pub fn remaining(&self) -> U256 {
+        self.budget.saturating_sub(self.spent)
+    }
+
+    /// Get the value a user may still receive.
+    pub fn remaining_for(&self, user_id: &Uuid) -> U256 {
+        let spent = self.spent_by_user.get(user_id).copied().unwrap_or_default();
+
+        self.per_user_cap
+            .saturating_sub(spent)
+            .min(self.remaining())
+    }
+
+    /// If the campaign accepts rewards at the given time.
+    pub fn is_active(&self, at: u64) -> bool {
+        self.status == CampaignStatus::Active && self.starts_at <= at && at < self.ends_at
+    }
+
+    /// Check that a reward to a user fits the campaign.
+    pub fn check(&self, user_id: &Uuid, value: U256) -> Result<(), CampaignError> {
+        if !self.is_active(now()) {
+            return Err(CampaignError::NotActive);
+        }
+
+        if value > self.remaining() {
+            return Err(CampaignError::BudgetExceeded {
+                requested: value,
+                remaining: self.remaining(),
+            });
+        }
+
+        let remaining = self.remaining_for(user_id);
+
+        if value > remaining {
+            return Err(CampaignError::UserCapExceeded {
+                requested: value,
+                remaining,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reserve part of the budget of a campaign for a reward to a user. The
+    /// campaign is checked and updated under one lock, so concurrent rewards
+    /// cannot overspend it.
+    pub async fn reserve(id: Uuid, user_id: Uuid, value: U256) -> Result<Self, CampaignError> {
+        Self::update(id, |campaign| {
+            campaign.check(&user_id, value)?;
+            campaign.spent += value;
+            *campaign.spent_by_user.entry(user_id).or_default() += value;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Return a reservation to the budget after its reward failed.
+    pub async fn release(id: Uuid, user_id: Uuid, value: U256) -> Result<Self, CampaignError> {
+        Self::update(id, |campaign| {
+            campaign.spent = campaign.spent.saturating_sub(value);
+
+            if let Some(spent) = campaign.spent_by_user.get_mut(&user_id) {
+                *spent = spent.saturating_sub(value);
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Set the status of a campaign.
+    pub async fn set_status(id: Uuid, status: CampaignStatus) -> Result<Self, CampaignError> {
+        Self::update(id, |campaign| {
+            campaign.status = status;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Read, change and save a campaign while holding the write lock.
+    async fn update<F>(id: Uuid, change: F) -> Result<Self, CampaignError>
+    where
+        F: FnOnce(&mut Campaign) -> Result<(), CampaignError>,
+    {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| CampaignError::RepositoryError(RepositoryError::ConnectionError))?;
+        let mut campaign: Campaign = db.read(campaign_key(&id))?.ok_or(CampaignError::NotFound)?;
+
+        change(&mut campaign)?;
+        db.update(campaign_key(&id), campaign.clone())?;
+
+        Ok(campaign)
+    }
+}
+
+impl SledModel for Campaign {}
+
+/// Get the repository key of a campaign.
+fn campaign_key(id: &Uuid) -> String {
+    format!("{}{}", CAMPAIGN_KEY_PREFIX, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn create(budget: u64, per_user_cap: u64) -> Campaign {
+        let campaign = Campaign::new(
+            "Launch".into(),
+            U256::from(budget),
+            U256::from(per_user_cap),
+            now() - 60,
+            now() + 60,
+        )
+        .unwrap();
+        campaign.save().await.unwrap();
+
+        campaign
+    }
+
+    #[test]
+    fn test_new() {
+        let new = |name: &str, budget: u64, starts_at, ends_at| {
+            Campaign::new(
+                name.into(),
+                U256::from(budget),
+                U256::one(),
+                starts_at,
+                ends_at,
+            )
+        };
+
+        assert!(new("Launch", 100, 10, 20).is_ok());
+        assert!(new(" ", 100, 10, 20).is_err());
+        assert!(new("Launch", 0, 10, 20).is_err());
+        assert!(new("Launch", 100, 20, 20).is_err());
+    }
+
+    #[test]
+    fn test_is_active() {
+        let mut campaign =
+            Campaign::new("Launch".into(), U256::from(100), U256::one(), 10, 20).unwrap();
+
+        assert!(!campaign.is_active(9));
+        assert!(campaign.is_active(10));
+        assert!(!campaign.is_active(20));
+
+        campaign.status = CampaignStatus::Paused;
+        assert!(!campaign.is_active(15));
+    }
+
+    #[test]
+    fn test_reward_expiry() {
+        let campaign =
+            Campaign::new("Launch".into(), U256::from(100), U256::one(), 10, 20).unwrap();
+
+        assert_eq!(campaign.reward_expiry(15), None);
+        assert_eq!(
+            campaign.with_reward_ttl(Some(60)).reward_expiry(15),
+            Some(75)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let campaign = create(100, 60).await;
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+
+        Campaign::reserve(campaign.id, alice, U256::from(50))
+            .await
+            .unwrap();
+
+        // A user cannot receive more than the cap
+        assert!(matches!(
+            Campaign::reserve(campaign.id, alice, U256::from(20)).await,
+            Err(CampaignError::UserCapExceeded { remaining, .. }) if remaining == U256::from(10)
+        ));
+
+        // The campaign cannot issue more than its budget
+        Campaign::reserve(campaign.id, bob, U256::from(40))
+            .await
+            .unwrap();
+        assert!(matches!(
+            Campaign::reserve(campaign.id, Uuid::new_v4(), U256::from(20)).await,
+            Err(CampaignError::BudgetExceeded { remaining, .. }) if remaining == U256::from(10)
+        ));
+
+        // Released reservations can be spent again
+        let campaign = Campaign::release(campaign.id, alice, U256::from(50))
+            .await
+            .unwrap();
+
+        assert_eq!(campaign.spent, U256::from(40));
+        assert_eq!(campaign.remaining_for(&alice), U256::from(60));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_inactive() {
+        let campaign = create(100, 60).await;
+
+        Campaign::set_status(campaign.id, CampaignStatus::Paused)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            Campaign::reserve(campaign.id, Uuid::new_v4(), U256::one()).await,
+            Err(CampaignError::NotActive)
+        ));
+        assert!(matches!(
+            Campaign::reserve(Uuid::new_v4(), Uuid::new_v4(), U256::one()).await,
+            Err(CampaignError::NotFound)
+        ));
+    }
+}
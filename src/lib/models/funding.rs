@@ -0,0 +1,315 @@
+use ethers::signers::Signer;
+use ethers::types::{Address, U256};
+use ethers::utils::parse_ether;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::core::chain::ChainError;
+use crate::core::repository::{Repository, RepositoryError};
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::helpers::now;
+
+use super::user::{User, UserError};
+
+/// The prefix of the top-up records in the repository.
+const TOP_UP_KEY_PREFIX: &str = "topup:";
+
+/// The number of seconds the daily limit applies to.
+const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Error)]
+pub enum FundingError {
+    #[error("Daily gas limit exceeded")]
+    LimitExceeded { requested: U256, remaining: U256 },
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+    #[error("User error")]
+    UserError(#[from] UserError),
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
+}
+
+/// FundingConfig controls how user wallets are topped up with gas.
+#[derive(Clone, Debug)]
+pub struct FundingConfig {
+    /// The balance below which a wallet is topped up.
+    pub threshold: U256,
+    /// The balance a wallet is topped up to.
+    pub target: U256,
+    /// The most sent to a single user in a day.
+    pub daily_limit: U256,
+}
+
+impl FundingConfig {
+    /// Read the funding configuration from the environment. Amounts are in
+    /// ETH.
+    pub fn from_env() -> Self {
+        let ether = |name: &str, default: &str| {
+            let value = std::env::var(name).unwrap_or_else(|_| default.into());
+
+            parse_ether(value).unwrap_or_else(|_| panic!("{} must be an amount of ETH", name))
+        };
+
+        Self {
+            threshold: ether("GAS_TOPUP_THRESHOLD", "0.005"),
+            target: ether("GAS_TOPUP_TARGET", "0.01"),
+            daily_limit: ether("GAS_TOPUP_DAILY_LIMIT", "0.05"),
+        }
+    }
+
+    /// Get the amount a wallet with the given balance is topped up by, or
+    /// `None` if it has enough.
+    pub fn top_up_amount(&self, balance: U256) -> Option<U256> {
+        (balance < self.threshold && balance < self.target).then(|| self.target - balance)
+    }
+
+    /// Check that sending an amount keeps a user within the daily limit,
+    /// given what they were sent in the last day.
+    pub fn check_limit(&self, spent: U256, amount: U256) -> Result<(), FundingError> {
+        let remaining = self.daily_limit.saturating_sub(spent);
+
+        if amount > remaining {
+            return Err(FundingError::LimitExceeded {
+                requested: amount,
+                remaining,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// TopUp is an audit record of gas sent from the admin wallet to a user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopUp {
+    /// The id of the top-up.
+    pub id: Uuid,
+    /// The user that was topped up.
+    pub user_id: Uuid,
+    /// The wallet the gas was sent to.
+    pub address: Address,
+    /// The amount sent, in wei.
+    pub amount: U256,
+    /// The balance of the wallet before the top-up.
+    pub balance_before: U256,
+    /// The hash of the transaction, once sent.
+    pub tx_hash: Option<String>,
+    /// The error if the transaction could not be sent.
+    pub error: Option<String>,
+    /// When the top-up was made, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl TopUp {
+    /// Create a new top-up record.
+    pub fn new(user_id: Uuid, address: Address, amount: U256, balance_before: U256) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            address,
+            amount,
+            balance_before,
+            tx_hash: None,
+            error: None,
+            created_at: now(),
+        }
+    }
+
+    /// Get all top-ups of a user, oldest first.
+    pub async fn for_user(user_id: Uuid) -> Result<Vec<Self>, FundingError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| FundingError::RepositoryError(RepositoryError::ConnectionError))?;
+        let top_ups: Vec<(String, TopUp)> =
+            db.scan(format!("{}{}:", TOP_UP_KEY_PREFIX, user_id))?;
+        let mut top_ups: Vec<TopUp> = top_ups.into_iter().map(|(_, top_up)| top_up).collect();
+
+        top_ups.sort_by_key(|top_up| top_up.created_at);
+
+        Ok(top_ups)
+    }
+
+    /// Save the top-up to the repository.
+    pub async fn save(&self) -> Result<(), FundingError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| FundingError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(top_up_key(&self.user_id, &self.id), self.clone())?)
+    }
+}
+
+impl SledModel for TopUp {}
+
+/// Get the repository key of a top-up.
+fn top_up_key(user_id: &Uuid, id: &Uuid) -> String {
+    format!("{}{}:{}", TOP_UP_KEY_PREFIX, user_id, id)
+}
+
+/// Get the total sent in top-ups since a unix timestamp. Top-ups that could
+/// not be sent are not counted.
+pub fn spent_since(top_ups: &[TopUp], since: u64) -> U256 {
+    top_ups
+        .iter()
+        .filter(|top_up| top_up.created_at >= since && top_up.error.is_none())
+        .fold(U256::zero(), |total, top_up| total + top_up.amount)
+}
+
+/// Make sure a user's wallet can pay for gas, topping it up from the admin
+/// wallet when it is below the threshold. Waits for the top-up to be mined so
+/// the wallet can send right away.
+pub async fn ensure_gas(
+    user: &User,
+    config: &FundingConfig,
+) -> Result<Option<TopUp>, FundingError> {
+    let address = user.get_wallet()?.address();
+    let balance = get_balance(address).await?;
+
+    let amount = match config.top_up_amount(balance) {
+        Some(amount) => amount,
+        None => return Ok(None),
+    };
+
+    let spent = spent_since(&TopUp::for_user(user.id).await?, now().saturating_sub(DAY));
+    config.check_limit(spent, amount)?;
+
+    // Record the top-up before sending it so it is counted if we crash
+    let mut top_up = TopUp::new(user.id, address, amount, balance);
+    top_up.save().await?;
+
+    match send(address, amount).await {
+        Ok(tx_hash) => {
+            top_up.tx_hash = Some(tx_hash.clone());
+            top_up.save().await?;
+
+            wait(&tx_hash).await?;
+
+            Ok(Some(top_up))
+        }
+        Err(e) => {
+            top_up.error = Some(format!("{:?}", e));
+            top_up.save().await?;
+
+            Err(e)
+        }
+    }
+}
+
+/// Get the ETH balance of a wallet.
+#[cfg(not(test))]
+async fn get_balance(address: Address) -> Result<U256, FundingError> {
+    Ok(crate::core::chain::get_eth_balance(address).await?)
+}
+
+#[cfg(test)]
+async fn get_balance(_address: Address) -> Result<U256, FundingError> {
+    Ok(U256::zero())
+}
+
+/// Send ETH from the admin wallet.
+#[cfg(not(test))]
+async fn send(to: Address, amount: U256) -> Result<String, FundingError> {
+    let wallet = crate::core::chain::get_admin_wallet().map_err(ChainError::from)?;
+
+    Ok(crate::core::chain::send_eth(wallet, to, amount).await?)
+}
+
+#[cfg(test)]
+async fn send(_to: Address, _amount: U256) -> Result<String, FundingError> {
+    Ok(format!("{:#x}", crate::utils::helpers::random_u256()))
+}
+
+/// Wait for a transaction to be mined.
+#[cfg(not(test))]
+async fn wait(tx_hash: &str) -> Result<(), FundingError> {
+    match crate::core::chain::wait_for_transaction(tx_hash).await? {
+        Some(_) => Ok(()),
+        None => Err(ChainError::RpcError(format!("Transaction {} was dropped", tx_hash)).into()),
+    }
+}
+
+#[cfg(test)]
+async fn wait(_tx_hash: &str) -> Result<(), FundingError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::chain::generate_secret_key;
+
+    use super::*;
+
+    fn config() -> FundingConfig {
+        FundingConfig {
+            threshold: U256::from(50),
+            target: U256::from(100),
+            daily_limit: U256::from(250),
+        }
+    }
+
+    #[test]
+    fn test_top_up_amount() {
+        let config = config();
+
+        assert_eq!(config.top_up_amount(U256::from(20)), Some(U256::from(80)));
+        // Wallets at or above the threshold are not topped up
+        assert_eq!(config.top_up_amount(U256::from(50)), None);
+    }
+
+    #[test]
+    fn test_check_limit() {
+        let config = config();
+
+        assert!(config.check_limit(U256::from(150), U256::from(100)).is_ok());
+        assert!(matches!(
+            config.check_limit(U256::from(200), U256::from(100)),
+            Err(FundingError::LimitExceeded { remaining, .. }) if remaining == U256::from(50)
+        ));
+    }
+
+    #[test]
+    fn test_spent_since() {
+        let user_id = Uuid::new_v4();
+        let mut old = TopUp::new(user_id, Address::random(), U256::from(10), U256::zero());
+        old.created_at -= DAY + 1;
+        let mut failed = TopUp::new(user_id, Address::random(), U256::from(20), U256::zero());
+        failed.error = Some("failed".into());
+        let recent = TopUp::new(user_id, Address::random(), U256::from(30), U256::zero());
+
+        // Only recent top-ups that were sent are counted
+        assert_eq!(
+            spent_since(&[old, failed, recent], now() - DAY),
+            U256::from(30)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ensure_gas() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        let config = config();
+
+        // The empty wallet is topped up to the target
+        let top_up = ensure_gas(&user, &config).await.unwrap().unwrap();
+
+        assert_eq!(top_up.amount, U256::from(100));
+        assert!(top_up.tx_hash.is_some());
+
+        // The top-up is recorded
+        let top_ups = TopUp::for_user(user.id).await.unwrap();
+
+        assert_eq!(top_ups.len(), 1);
+        assert_eq!(top_ups[0].id, top_up.id);
+
+        // Top-ups stop once the daily limit is reached
+        ensure_gas(&user, &config).await.unwrap();
+
+        assert!(matches!(
+            ensure_gas(&user, &config).await,
+            Err(FundingError::LimitExceeded { .. })
+        ));
+        assert_eq!(TopUp::for_user(user.id).await.unwrap().len(), 2);
+    }
+}
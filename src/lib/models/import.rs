@@ -0,0 +1,710 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use ethers::types::U256;
+use ethers::utils::keccak256;
+use lazy_static::lazy_static;
+use log::error;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::core::repository::{Repository, RepositoryError};
+use crate::core::reward::RewardError;
+use crate::rewards::Reward;
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+use super::outbox::{OutboxEntry, OutboxStatus};
+use super::reward::{IssueOptions, RewardNFT};
+use super::user::{User, UserError};
+
+/// The prefix of the import records in the repository.
+const IMPORT_KEY_PREFIX: &str = "import:";
+
+lazy_static! {
+    /// The imports being run in the background.
+    static ref RUNNING: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("Import not found")]
+    NotFound,
+    #[error("Unknown import format: {0}")]
+    UnknownFormat(String),
+    #[error("Import has {} issues", .0.issues.len())]
+    Invalid(ImportReport),
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+    #[error("User error")]
+    UserError(#[from] UserError),
+    #[error("Reward error")]
+    RewardError(#[from] RewardError),
+}
+
+/// ImportFormat is the file format of a grant list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    /// Comma separated `user_id,value` rows with an optional header.
+    Csv,
+    /// One `{"user_id": ..., "value": ...}` object per line.
+    Jsonl,
+}
+
+impl ImportFormat {
+    /// Get the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Result<Self, ImportError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        extension.parse()
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = ImportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ImportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ImportFormat::Jsonl),
+            _ => Err(ImportError::UnknownFormat(s.into())),
+        }
+    }
+}
+
+/// Grant is a reward to issue to a user, read from a line of a grant list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Grant {
+    /// The line of the grant in the list, starting at 1.
+    pub line: usize,
+    /// The user to reward.
+    pub user_id: Uuid,
+    /// The value of the reward.
+    pub value: U256,
+}
+
+/// IssueKind is why a grant cannot be imported.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum IssueKind {
+    /// The line could not be parsed.
+    Invalid { message: String },
+    /// The user is not registered.
+    UnknownUser,
+    /// The user already has a grant on an earlier line.
+    Duplicate { first_line: usize },
+    /// The value is above the most a single grant may be worth.
+    OverCap { cap: U256 },
+}
+
+/// ImportIssue is a problem found with a line of a grant list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImportIssue {
+    /// The line of the issue, starting at 1.
+    pub line: usize,
+    /// The user of the grant, if the line could be parsed.
+    pub user_id: Option<Uuid>,
+    #[serde(flatten)]
+    pub kind: IssueKind,
+}
+
+/// ImportReport is the outcome of validating a grant list without issuing
+/// anything.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// The number of grants in the list.
+    pub total: usize,
+    /// The number of grants without issues.
+    pub valid: usize,
+    /// The total value of the grants without issues.
+    pub total_value: U256,
+    /// The issues found.
+    pub issues: Vec<ImportIssue>,
+}
+
+impl ImportReport {
+    /// If the list can be imported.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Parse a grant list, returning the grants and the lines that could not be
+/// parsed. Blank lines and lines starting with `#` are skipped.
+pub fn parse_grants(content: &str, format: ImportFormat) -> (Vec<Grant>, Vec<ImportIssue>) {
+    let mut grants = Vec::new();
+    let mut issues = Vec::new();
+    // The CSV columns, which a header may reorder
+    let mut columns = (0, 1);
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parsed = match format {
+            ImportFormat::Csv => {
+                let fields: Vec<&str> = line
+                    .split(',')
+                    .map(|field| field.trim().trim_matches('"'))
+                    .collect();
+
+                if grants.is_empty() && issues.is_empty() && fields.contains(&"user_id") {
+                    let position = |name| fields.iter().position(|field| *field == name);
+
+                    match (position("user_id"), position("value")) {
+                        (Some(user_id), Some(value)) => {
+                            columns = (user_id, value);
+                            continue;
+                        }
+                        _ => Err("Header must have user_id and value columns".to_string()),
+                    }
+                } else {
+                    parse_grant(
+                        fields.get(columns.0).copied().unwrap_or_default(),
+                        fields.get(columns.1).copied().unwrap_or_default(),
+                    )
+                }
+            }
+            ImportFormat::Jsonl => match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(object) => {
+                    let field = |name| match &object[name] {
+                        serde_json::Value::String(value) => value.clone(),
+                        serde_json::Value::Number(value) => value.to_string(),
+                        _ => String::new(),
+                    };
+
+                    parse_grant(&field("user_id"), &field("value"))
+                }
+                Err(e) => Err(format!("Invalid JSON: {}", e)),
+            },
+        };
+
+        match parsed {
+            Ok((user_id, value)) => grants.push(Grant {
+                line: number,
+                user_id,
+                value,
+            }),
+            Err(message) => issues.push(ImportIssue {
+                line: number,
+                user_id: None,
+                kind: IssueKind::Invalid { message },
+            }),
+        }
+    }
+
+    (grants, issues)
+}
+
+/// Parse the user id and value of a grant.
+fn parse_grant(user_id: &str, value: &str) -> Result<(Uuid, U256), String> {
+    let user_id = Uuid::parse_str(user_id).map_err(|_| format!("Invalid user id: {}", user_id))?;
+    let value = U256::from_dec_str(value).map_err(|_| format!("Invalid value: {}", value))?;
+
+    if value.is_zero() {
+        return Err("Value must be greater than zero".into());
+    }
+
+    Ok((user_id, value))
+}
+
+/// Check parsed grants for unknown users, duplicate users and values above
+/// the cap, and build the report.
+pub fn check_grants(
+    grants: &[Grant],
+    mut issues: Vec<ImportIssue>,
+    known_users: &HashSet<Uuid>,
+    cap: U256,
+) -> ImportReport {
+    let mut first_lines: HashMap<Uuid, usize> = HashMap::new();
+    let mut report = ImportReport {
+        total: grants.len() + issues.len(),
+        ..Default::default()
+    };
+
+    for grant in grants {
+        let kind = if !known_users.contains(&grant.user_id) {
+            Some(IssueKind::UnknownUser)
+        } else if let Some(first_line) = first_lines.get(&grant.user_id) {
+            Some(IssueKind::Duplicate {
+                first_line: *first_line,
+            })
+        } else if grant.value > cap {
+            Some(IssueKind::OverCap { cap })
+        } else {
+            None
+        };
+
+        first_lines.entry(grant.user_id).or_insert(grant.line);
+
+        match kind {
+            Some(kind) => issues.push(ImportIssue {
+                line: grant.line,
+                user_id: Some(grant.user_id),
+                kind,
+            }),
+            None => {
+                report.valid += 1;
+                report.total_value += grant.value;
+            }
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+    report.issues = issues;
+
+    report
+}
+
+/// Validate a grant list against the registered users without issuing
+/// anything. Values above `REWARD_IMPORT_MAX_VALUE` are reported.
+pub async fn validate(
+    content: &str,
+    format: ImportFormat,
+) -> Result<(Vec<Grant>, ImportReport), ImportError> {
+    let (grants, issues) = parse_grants(content, format);
+    let mut known_users = HashSet::new();
+
+    for grant in &grants {
+        match User::from_id(grant.user_id.to_string()).await {
+            Ok(user) => {
+                known_users.insert(user.id);
+            }
+            Err(UserError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let cap = U256::from(parse_env("REWARD_IMPORT_MAX_VALUE", 1_000_000u128));
+    let report = check_grants(&grants, issues, &known_users, cap);
+
+    Ok((grants, report))
+}
+
+/// GrantStatus is the progress of a single grant of an import.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GrantStatus {
+    /// Nothing has been recorded for the grant.
+    Pending,
+    /// The mint was assigned an outbox entry, which may not have been sent.
+    Started { entry_id: Uuid },
+    /// The reward was issued.
+    Issued { reward_id: String },
+    /// The mint failed and will not be retried.
+    Failed { error: String },
+}
+
+/// GrantProgress is a grant of an import and its progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrantProgress {
+    pub grant: Grant,
+    pub status: GrantStatus,
+}
+
+/// GrantImport is the resumable progress of importing a grant list. The id
+/// is derived from the content of the list, so importing the same list again
+/// continues where it stopped instead of issuing the rewards twice.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrantImport {
+    /// The id of the import.
+    pub id: String,
+    /// The grants and their progress, in the order of the list.
+    pub grants: Vec<GrantProgress>,
+    /// When the import was started, as a unix timestamp.
+    pub created_at: u64,
+    /// When the import last made progress, as a unix timestamp.
+    pub updated_at: u64,
+}
+
+/// ImportProgress is a summary of an import.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub id: String,
+    pub total: usize,
+    pub issued: usize,
+    pub failed: usize,
+    pub pending: usize,
+    pub grants: Vec<GrantProgress>,
+}
+
+impl GrantImport {
+    /// Validate a grant list and load its import, creating it if the list
+    /// has not been imported before. Fails with the report if the list has
+    /// any issues.
+    pub async fn start(content: &str, format: ImportFormat) -> Result<Self, ImportError> {
+        let (grants, report) = validate(content, format).await?;
+
+        if !report.is_valid() {
+            return Err(ImportError::Invalid(report));
+        }
+
+        let id = hex::encode(keccak256(content.trim()));
+
+        if let Some(import) = Self::from_id(&id).await? {
+            return Ok(import);
+        }
+
+        let mut import = Self {
+            id,
+            grants: grants
+                .into_iter()
+                .map(|grant| GrantProgress {
+                    grant,
+                    status: GrantStatus::Pending,
+                })
+                .collect(),
+            created_at: now(),
+            updated_at: now(),
+        };
+        import.save().await?;
+
+        Ok(import)
+    }
+
+    /// Look up an import by id from the repository.
+    pub async fn find(id: &str) -> Result<Self, ImportError> {
+        Self::from_id(id).await?.ok_or(ImportError::NotFound)
+    }
+
+    /// Look up an import by id from the repository, if it exists.
+    pub async fn from_id(id: &str) -> Result<Option<Self>, ImportError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| ImportError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.read(import_key(id))?)
+    }
+
+    /// Save the import to the repository.
+    pub async fn save(&mut self) -> Result<(), ImportError> {
+        self.updated_at = now();
+
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| ImportError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(import_key(&self.id), self.clone())?)
+    }
+
+    /// Issue every grant that has not been issued yet. The outbox entry of
+    /// each mint is derived from the import and the line of the grant, so a
+    /// run that was interrupted, or that overlaps another run of the same
+    /// list, resumes the recorded mint instead of minting again. Stops at the
+    /// first error that may succeed when retried.
+    pub async fn run(&mut self) -> Result<ImportProgress, ImportError> {
+        for index in 0..self.grants.len() {
+            let entry_id = match &self.grants[index].status {
+                GrantStatus::Issued { .. } | GrantStatus::Failed { .. } => continue,
+                GrantStatus::Pending => grant_entry_id(&self.id, self.grants[index].grant.line),
+                GrantStatus::Started { entry_id } => *entry_id,
+            };
+
+            let mut entry = match OutboxEntry::from_id(entry_id).await {
+                Ok(entry) => entry,
+                // The run stopped before the entry was saved
+                Err(RewardError::NotFound) => {
+                    let grant = &self.grants[index].grant;
+                    let owner = User::from_id(grant.user_id.to_string()).await?;
+                    let mut entry =
+                        RewardNFT::issue_entry(owner, grant.value, IssueOptions::default()).await?;
+                    entry.id = entry_id;
+
+                    // Record the entry before saving it, so a saved entry
+                    // always belongs to the import
+                    self.grants[index].status = GrantStatus::Started { entry_id };
+                    self.save().await?;
+                    entry.save().await?;
+
+                    entry
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            match RewardNFT::complete_issue(&mut entry).await {
+                Ok(reward) => {
+                    self.grants[index].status = GrantStatus::Issued {
+                        reward_id: reward.get_id(),
+                    };
+                }
+                Err(e) if entry.status == OutboxStatus::Failed => {
+                    self.grants[index].status = GrantStatus::Failed {
+                        error: e.to_string(),
+                    };
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            self.save().await?;
+        }
+
+        Ok(self.progress())
+    }
+
+    /// Run the import in the background, unless it is already running.
+    /// Returns false if it was already running.
+    pub fn spawn(mut self) -> bool {
+        if !RUNNING.lock().unwrap().insert(self.id.clone()) {
+            return false;
+        }
+
+        tokio::spawn(async move {
+            if let Err(e) = self.run().await {
+                error!("Failed to run import {}: {:?}", self.id, e);
+            }
+
+            RUNNING.lock().unwrap().remove(&self.id);
+        });
+
+        true
+    }
+
+    /// Summarize the progress of the import.
+    pub fn progress(&self) -> ImportProgress {
+        let count = |f: fn(&GrantStatus) -> bool| {
+            self.grants.iter().filter(|grant| f(&grant.status)).count()
+        };
+
+        ImportProgress {
+            id: self.id.clone(),
+            total: self.grants.len(),
+            issued: count(|status| matches!(status, GrantStatus::Issued { .. })),
+            failed: count(|status| matches!(status, GrantStatus::Failed { .. })),
+            pending: count(|status| {
+                matches!(status, GrantStatus::Pending | GrantStatus::Started { .. })
+            }),
+            grants: self.grants.clone(),
+        }
+    }
+}
+
+impl SledModel for GrantImport {}
+
+/// Get the id of the outbox entry minting the grant on the given line of an
+/// import.
+fn grant_entry_id(import_id: &str, line: usize) -> Uuid {
+    let hash = keccak256(format!("{}:{}", import_id, line));
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Get the repository key of an import.
+fn import_key(id: &str) -> String {
+    format!("{}{}", IMPORT_KEY_PREFIX, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::chain::generate_secret_key;
+
+    use super::*;
+
+    async fn register() -> Uuid {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+
+        user.id
+    }
+
+    #[test]
+    fn test_import_format() {
+        assert_eq!(
+            ImportFormat::from_path(Path::new("grants.csv")).unwrap(),
+            ImportFormat::Csv
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("grants.JSONL")).unwrap(),
+            ImportFormat::Jsonl
+        );
+        assert!(ImportFormat::from_path(Path::new("grants.xlsx")).is_err());
+    }
+
+    #[test]
+    fn test_parse_grants_csv() {
+        let user_id = Uuid::new_v4();
+        let content = format!(
+            "value,user_id\n# comment\n100,{}\n\n\"25\",\"{}\"\nabc,{}\n",
+            user_id, user_id, user_id
+        );
+
+        let (grants, issues) = parse_grants(&content, ImportFormat::Csv);
+
+        // The header sets the column order
+        assert_eq!(grants.len(), 2);
+        assert_eq!(grants[0].line, 3);
+        assert_eq!(grants[0].user_id, user_id);
+        assert_eq!(grants[0].value, U256::from(100));
+        assert_eq!(grants[1].value, U256::from(25));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 6);
+    }
+
+    #[test]
+    fn test_parse_grants_jsonl() {
+        let user_id = Uuid::new_v4();
+        let content = format!(
+            "{{\"user_id\": \"{}\", \"value\": 10}}\n{{\"user_id\": \"{}\", \"value\": \"20\"}}\nnot json\n{{\"user_id\": \"{}\", \"value\": 0}}",
+            user_id, user_id, user_id
+        );
+
+        let (grants, issues) = parse_grants(&content, ImportFormat::Jsonl);
+
+        // Values may be numbers or strings
+        assert_eq!(grants.len(), 2);
+        assert_eq!(grants[1].value, U256::from(20));
+        // Lines that are not JSON and zero values are rejected
+        assert_eq!(
+            issues.iter().map(|issue| issue.line).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn test_check_grants() {
+        let known = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let grant = |line, user_id, value: u64| Grant {
+            line,
+            user_id,
+            value: U256::from(value),
+        };
+        let grants = vec![
+            grant(1, known, 10),
+            grant(2, Uuid::new_v4(), 10),
+            grant(3, known, 10),
+            grant(4, other, 500),
+        ];
+
+        let report = check_grants(
+            &grants,
+            vec![],
+            &HashSet::from([known, other]),
+            U256::from(100),
+        );
+
+        assert_eq!(report.total, 4);
+        assert_eq!(report.valid, 1);
+        assert_eq!(report.total_value, U256::from(10));
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|issue| issue.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                IssueKind::UnknownUser,
+                IssueKind::Duplicate { first_line: 1 },
+                IssueKind::OverCap {
+                    cap: U256::from(100)
+                },
+            ]
+        );
+        assert!(!report.is_valid());
+    }
+
+    #[tokio::test]
+    async fn test_start_invalid() {
+        let content = format!("user_id,value\n{},10\n", Uuid::new_v4());
+
+        // Lists with issues are not imported
+        assert!(matches!(
+            GrantImport::start(&content, ImportFormat::Csv).await,
+            Err(ImportError::Invalid(report)) if report.issues.len() == 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let users = [register().await, register().await];
+        let content = format!("user_id,value\n{},10\n{},20\n", users[0], users[1]);
+
+        let mut import = GrantImport::start(&content, ImportFormat::Csv)
+            .await
+            .unwrap();
+        let progress = import.run().await.unwrap();
+
+        assert_eq!(progress.total, 2);
+        assert_eq!(progress.issued, 2);
+        assert_eq!(progress.pending, 0);
+
+        // Importing the same list again issues nothing new
+        let mut import = GrantImport::start(&content, ImportFormat::Csv)
+            .await
+            .unwrap();
+
+        assert_eq!(import.progress().issued, 2);
+        assert_eq!(import.run().await.unwrap().issued, 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_overlapping() {
+        let user_id = register().await;
+        let content = format!("user_id,value\n{},10\n", user_id);
+        let mut import = GrantImport::start(&content, ImportFormat::Csv)
+            .await
+            .unwrap();
+        // A second run loaded before the first one recorded anything
+        let mut overlapping = import.clone();
+
+        let first = import.run().await.unwrap();
+        let second = overlapping.run().await.unwrap();
+
+        // Both runs complete the same mint
+        assert_eq!(first.grants[0].status, second.grants[0].status);
+        assert_eq!(
+            OutboxEntry::from_id(grant_entry_id(&import.id, 2))
+                .await
+                .unwrap()
+                .status,
+            OutboxStatus::Confirmed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_resume() {
+        let user_id = register().await;
+        let content = format!("user_id,value\n{},10\n", user_id);
+        let mut import = GrantImport::start(&content, ImportFormat::Csv)
+            .await
+            .unwrap();
+
+        // Simulate a run interrupted after the mint was recorded
+        let owner = User::from_id(user_id.to_string()).await.unwrap();
+        let mut entry = RewardNFT::issue_entry(owner, U256::from(10), IssueOptions::default())
+            .await
+            .unwrap();
+        import.grants[0].status = GrantStatus::Started { entry_id: entry.id };
+        import.save().await.unwrap();
+        entry.save().await.unwrap();
+
+        let progress = import.run().await.unwrap();
+
+        // The recorded mint is completed instead of minting again
+        match &progress.grants[0].status {
+            GrantStatus::Issued { reward_id } => {
+                let reward = RewardNFT::from_id(reward_id.clone()).await.unwrap();
+
+                assert_eq!(
+                    OutboxEntry::from_id(entry.id).await.unwrap().status,
+                    OutboxStatus::Confirmed
+                );
+                assert_eq!(reward.get_owner(), user_id);
+            }
+            status => panic!("Expected the grant to be issued, got {:?}", status),
+        }
+    }
+}
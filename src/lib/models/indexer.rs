@@ -0,0 +1,203 @@
+use ethers::types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::core::chain::ChainError;
+use crate::core::repository::{Repository, RepositoryError};
+use crate::core::reward::RewardError;
+use crate::storage::sled::{get_sled_db, SledModel};
+
+/// The repository key of the indexer checkpoint.
+const CHECKPOINT_KEY: &str = "indexer:checkpoint";
+
+/// The repository key of the minted token cursor.
+const MINT_CURSOR_KEY: &str = "reconcile:mints";
+
+#[derive(Debug, Error)]
+pub enum IndexerError {
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+    #[error("Reward error")]
+    RewardError(#[from] RewardError),
+    #[error("Unknown error")]
+    UnknownError(#[from] std::io::Error),
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
+}
+
+/// Checkpoint is the last block processed by the indexer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The number of the last processed block.
+    pub block: u64,
+    /// The hash of the last processed block, used to detect reorgs.
+    pub hash: H256,
+}
+
+impl Checkpoint {
+    /// Create a new checkpoint.
+    pub fn new(block: u64, hash: H256) -> Self {
+        Self { block, hash }
+    }
+
+    /// Load the checkpoint from the repository, if the indexer has run before.
+    pub async fn load() -> Result<Option<Self>, IndexerError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| IndexerError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.read(CHECKPOINT_KEY.to_string())?)
+    }
+
+    /// Save the checkpoint to the repository.
+    pub async fn save(&self) -> Result<(), IndexerError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| IndexerError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(CHECKPOINT_KEY.to_string(), self.clone())?)
+    }
+}
+
+impl SledModel for Checkpoint {}
+
+/// MintCursor is the ids of the tokens minted up to a block, so later scans
+/// only fetch the logs of newer blocks.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MintCursor {
+    /// The last block scanned, if any block was scanned.
+    pub block: Option<u64>,
+    /// The ids of the tokens minted up to the block.
+    pub token_ids: Vec<U256>,
+}
+
+impl MintCursor {
+    /// Load the cursor from the repository, or an empty cursor if there is none.
+    pub async fn load() -> Result<Self, IndexerError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| IndexerError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        let cursor: Option<Self> = db.read(MINT_CURSOR_KEY.to_string())?;
+
+        Ok(cursor.unwrap_or_default())
+    }
+
+    /// Save the cursor to the repository.
+    pub async fn save(&self) -> Result<(), IndexerError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| IndexerError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(MINT_CURSOR_KEY.to_string(), self.clone())?)
+    }
+}
+
+impl SledModel for MintCursor {}
+
+/// TokenBalance is the cached reward token balance of an address.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenBalance {
+    /// The address holding the tokens.
+    pub address: Address,
+    /// The balance of the address.
+    pub balance: U256,
+    /// The block the balance was read at.
+    pub block: u64,
+}
+
+impl TokenBalance {
+    /// Create a new token balance.
+    pub fn new(address: Address, balance: U256, block: u64) -> Self {
+        Self {
+            address,
+            balance,
+            block,
+        }
+    }
+
+    /// Look up the cached balance of an address.
+    pub async fn from_address(address: Address) -> Result<Option<Self>, IndexerError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| IndexerError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.read(balance_key(&address))?)
+    }
+
+    /// Save the balance to the repository.
+    pub async fn save(&self) -> Result<(), IndexerError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| IndexerError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(balance_key(&self.address), self.clone())?)
+    }
+}
+
+impl SledModel for TokenBalance {}
+
+/// Get the repository key of the cached balance of an address.
+fn balance_key(address: &Address) -> String {
+    format!("balance:{:#x}", address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let checkpoint = Checkpoint::new(42, H256::random());
+
+        // Save the checkpoint
+        assert!(checkpoint.save().await.is_ok());
+
+        // Load the checkpoint
+        let loaded = Checkpoint::load().await.unwrap();
+
+        assert_eq!(loaded, Some(checkpoint));
+    }
+
+    #[tokio::test]
+    async fn test_mint_cursor() {
+        let cursor = MintCursor {
+            block: Some(42),
+            token_ids: vec![U256::from(1), U256::from(2)],
+        };
+
+        // Save the cursor
+        assert!(cursor.save().await.is_ok());
+
+        // Load the cursor
+        let loaded = MintCursor::load().await.unwrap();
+
+        assert_eq!(loaded, cursor);
+    }
+
+    #[tokio::test]
+    async fn test_token_balance() {
+        let address = Address::random();
+
+        // An unknown address has no cached balance
+        assert!(TokenBalance::from_address(address).await.unwrap().is_none());
+
+        // Save the balance
+        let balance = TokenBalance::new(address, U256::from(100), 1);
+        assert!(balance.save().await.is_ok());
+
+        // Update the balance
+        let balance = TokenBalance::new(address, U256::from(200), 2);
+        assert!(balance.save().await.is_ok());
+
+        let loaded = TokenBalance::from_address(address).await.unwrap();
+
+        assert_eq!(loaded, Some(balance));
+    }
+}
@@ -0,0 +1,319 @@
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::core::repository::{Repository, RepositoryError};
+use crate::core::reward::RewardError;
+use crate::rewards::Reward;
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::helpers::now;
+
+use super::outbox::OutboxEntry;
+use super::reward::{IssueOptions, RewardNFT};
+use super::user::User;
+
+/// The prefix of the jobs in the repository.
+const JOB_KEY_PREFIX: &str = "job:";
+
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("Job not found")]
+    NotFound,
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+    #[error("Reward error")]
+    RewardError(#[from] RewardError),
+}
+
+/// JobStatus is the progress of a job.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum JobStatus {
+    /// The job is waiting for a worker.
+    Queued,
+    /// A worker is processing the job.
+    Running,
+    /// The job completed successfully.
+    Succeeded,
+    /// The job failed and will not be retried.
+    Failed,
+}
+
+/// Job is a reward issuance processed in the background.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    /// The id of the job.
+    pub id: Uuid,
+    /// The user receiving the reward.
+    pub user_id: Uuid,
+    /// The value of the reward.
+    pub value: U256,
+    /// The optional settings of the reward.
+    pub options: IssueOptions,
+    /// The progress of the job.
+    pub status: JobStatus,
+    /// How many times the job has been run.
+    pub attempts: u32,
+    /// The outbox entry of the mint, once it has been recorded.
+    pub outbox_id: Option<Uuid>,
+    /// The id of the issued reward.
+    pub reward_id: Option<String>,
+    /// The url of the issued reward.
+    pub url: Option<String>,
+    /// The last error encountered.
+    pub error: Option<String>,
+    /// The unix timestamp before which the job should not run.
+    pub run_after: u64,
+    /// When the job was created, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl Job {
+    /// Create a new queued job.
+    pub fn new(user_id: Uuid, value: U256) -> Self {
+        let created_at = now();
+
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            value,
+            options: IssueOptions::default(),
+            status: JobStatus::Queued,
+            attempts: 0,
+            outbox_id: None,
+            reward_id: None,
+            url: None,
+            error: None,
+            run_after: created_at,
+            created_at,
+        }
+    }
+
+    /// Set the optional settings of the reward.
+    pub fn with_options(mut self, options: IssueOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Look up a job by id from the repository.
+    pub async fn from_id(id: Uuid) -> Result<Self, JobError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| JobError::RepositoryError(RepositoryError::ConnectionError))?;
+        let job: Option<Job> = db.read(job_key(&id))?;
+
+        job.ok_or(JobError::NotFound)
+    }
+
+    /// Get all jobs with the given status, oldest first.
+    pub async fn with_status(status: JobStatus) -> Result<Vec<Self>, JobError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| JobError::RepositoryError(RepositoryError::ConnectionError))?;
+        let jobs: Vec<(String, Job)> = db.scan(JOB_KEY_PREFIX.to_string())?;
+        let mut jobs: Vec<Job> = jobs
+            .into_iter()
+            .map(|(_, job)| job)
+            .filter(|job| job.status == status)
+            .collect();
+
+        jobs.sort_by_key(|job| job.created_at);
+
+        Ok(jobs)
+    }
+
+    /// Save the job to the repository.
+    pub async fn save(&self) -> Result<(), JobError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| JobError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(job_key(&self.id), self.clone())?)
+    }
+
+    /// Run the job once. On failure the job is queued again after the retry
+    /// delay until it has been attempted `max_attempts` times.
+    pub async fn run(&mut self, max_attempts: u32, retry_delay: u64) -> Result<(), JobError> {
+        self.status = JobStatus::Running;
+        self.attempts += 1;
+        self.save().await?;
+
+        match self.issue().await {
+            Ok(reward) => {
+                self.status = JobStatus::Succeeded;
+                self.reward_id = Some(reward.get_id());
+                self.url = Some(reward.get_url());
+                self.error = None;
+            }
+            // The mint is waiting in the outbox, for example until fees
+            // drop, so the attempt does not count
+            Err(JobError::RewardError(RewardError::Queued(_))) => {
+                self.attempts -= 1;
+                self.status = JobStatus::Queued;
+                self.run_after = now() + retry_delay;
+            }
+            Err(e) => {
+                self.error = Some(format!("{:?}", e));
+
+                if self.attempts >= max_attempts {
+                    self.status = JobStatus::Failed;
+                } else {
+                    self.status = JobStatus::Queued;
+                    self.run_after = now() + retry_delay;
+                }
+            }
+        }
+
+        self.save().await
+    }
+
+    /// Issue the reward. The outbox entry is reused on retries so the reward
+    /// is never minted twice. The id of the entry is recorded on the job
+    /// before the entry is saved, so a retry after a crash in between
+    /// creates the entry under the same id.
+    async fn issue(&mut self) -> Result<RewardNFT, JobError> {
+        let entry_id = *self.outbox_id.get_or_insert(self.id);
+
+        let mut entry = match OutboxEntry::from_id(entry_id).await {
+            Ok(entry) => entry,
+            Err(RewardError::NotFound) => {
+                let user = User::from_id(self.user_id.to_string())
+                    .await
+                    .map_err(RewardError::from)?;
+                let mut entry = RewardNFT::issue_entry(user, self.value, self.options).await?;
+                entry.id = entry_id;
+
+                self.save().await?;
+                entry.save().await?;
+
+                entry
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(RewardNFT::complete_issue(&mut entry).await?)
+    }
+}
+
+impl SledModel for Job {}
+
+/// Get the repository key of a job.
+fn job_key(id: &Uuid) -> String {
+    format!("{}{}", JOB_KEY_PREFIX, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::chain::generate_secret_key;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_from_id() {
+        // An unknown job is not found
+        assert!(matches!(
+            Job::from_id(Uuid::new_v4()).await,
+            Err(JobError::NotFound)
+        ));
+
+        let job = Job::new(Uuid::new_v4(), U256::from(100));
+        job.save().await.unwrap();
+
+        let loaded = Job::from_id(job.id).await.unwrap();
+
+        assert_eq!(loaded.id, job.id);
+        assert_eq!(loaded.status, JobStatus::Queued);
+        assert!(Job::with_status(JobStatus::Queued)
+            .await
+            .unwrap()
+            .iter()
+            .any(|j| j.id == job.id));
+    }
+
+    #[tokio::test]
+    async fn test_run_success() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+
+        let mut job = Job::new(user.id, U256::from(100));
+        job.run(3, 0).await.unwrap();
+
+        // The reward was issued, minted by an outbox entry sharing the id of
+        // the job
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.attempts, 1);
+        assert_eq!(job.outbox_id, Some(job.id));
+
+        let reward = RewardNFT::from_id(job.reward_id.clone().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(reward.get_owner(), user.id);
+        assert_eq!(reward.get_value(), U256::from(100));
+    }
+
+    #[tokio::test]
+    async fn test_run_retry() {
+        // The user does not exist so the job fails
+        let mut job = Job::new(Uuid::new_v4(), U256::from(100));
+
+        job.run(2, 60).await.unwrap();
+
+        // The job is queued again after the retry delay
+        assert_eq!(job.status, JobStatus::Queued);
+        assert!(job.run_after > now());
+        assert!(job.error.is_some());
+
+        job.run(2, 60).await.unwrap();
+
+        // The job fails once it runs out of attempts
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_recover() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+
+        // A crash after the entry id was recorded but before the entry was
+        // saved
+        let mut job = Job::new(user.id, U256::from(100));
+        job.outbox_id = Some(job.id);
+        job.save().await.unwrap();
+
+        job.run(3, 0).await.unwrap();
+
+        // The entry is created under the recorded id
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert!(OutboxEntry::from_id(job.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_queued() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+
+        // Another process holds the mint, so it stays queued
+        let mut entry =
+            RewardNFT::prepare_issue(user.clone(), U256::from(100), IssueOptions::default())
+                .await
+                .unwrap();
+        entry.owner = Some(Uuid::new_v4());
+        entry.heartbeat = now();
+        entry.save().await.unwrap();
+
+        let mut job = Job::new(user.id, U256::from(100));
+        job.outbox_id = Some(entry.id);
+        job.run(1, 60).await.unwrap();
+
+        // The job waits for the mint without using up its attempts
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.attempts, 0);
+        assert!(job.run_after > now());
+    }
+}
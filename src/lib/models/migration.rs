@@ -0,0 +1,80 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::core::repository::{Repository, RepositoryError};
+use crate::core::reward::RewardError;
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::helpers::now;
+
+use super::reward::RewardNFT;
+use super::user::User;
+
+/// The key of the record of the index backfill.
+const BACKFILL_INDEXES_KEY: &str = "migration:backfill_indexes";
+
+/// Migration records a one-time change to the repository once it is done.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Migration {
+    /// When the change was done, as a unix timestamp.
+    completed_at: u64,
+}
+
+impl SledModel for Migration {}
+
+/// Index the rewards by token id and the users by wallet address for the
+/// records stored before the indexes were written, which is done once at
+/// startup. Lookups by token id or address miss records without an index
+/// entry.
+pub async fn backfill_indexes() -> Result<(), RewardError> {
+    if is_done(BACKFILL_INDEXES_KEY)? {
+        return Ok(());
+    }
+
+    let rewards = RewardNFT::backfill_index().await?;
+    let users = User::backfill_index().await?;
+
+    info!("Indexed {} rewards and {} users", rewards, users);
+
+    Ok(mark_done(BACKFILL_INDEXES_KEY)?)
+}
+
+/// Check if a migration was done.
+fn is_done(key: &str) -> Result<bool, RepositoryError> {
+    let connection = get_sled_db()?;
+    let db = connection
+        .read()
+        .map_err(|_| RepositoryError::ConnectionError)?;
+    let migration: Option<Migration> = db.read(key.to_string())?;
+
+    Ok(migration.is_some())
+}
+
+/// Record that a migration was done.
+fn mark_done(key: &str) -> Result<(), RepositoryError> {
+    let connection = get_sled_db()?;
+    let db = connection
+        .write()
+        .map_err(|_| RepositoryError::ConnectionError)?;
+
+    db.update(
+        key.to_string(),
+        Migration {
+            completed_at: now(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backfill_indexes() {
+        backfill_indexes().await.unwrap();
+
+        // The backfill is recorded, so it does not run again
+        assert!(is_done(BACKFILL_INDEXES_KEY).unwrap());
+
+        backfill_indexes().await.unwrap();
+    }
+}
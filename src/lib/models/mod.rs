@@ -1,2 +1,14 @@
+pub mod activity;
+pub mod authorization;
+pub mod balance;
+pub mod campaign;
+pub mod funding;
+pub mod import;
+pub mod indexer;
+pub mod job;
+pub mod migration;
+pub mod outbox;
 pub mod reward;
 pub mod user;
+pub mod webhook;
+pub mod withdrawal;
@@ -0,0 +1,1541 @@
+use ethers::types::{Address, U256};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::core::chain::{ChainError, SignedTransaction};
+use crate::core::gas::GasPolicy;
+use crate::core::repository::{Repository, RepositoryError};
+use crate::core::reward::RewardError;
+use crate::rewards::Reward;
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+use super::activity::{Activity, ActivityKind};
+use super::campaign::Campaign;
+use super::reward::{Revocation, RewardNFT, RewardStatus, RewardTransfer};
+use super::webhook::{self, WebhookEvent};
+use super::withdrawal::{Withdrawal, WithdrawalStatus};
+
+/// The prefix of the outbox entries in the repository.
+const OUTBOX_KEY_PREFIX: &str = "outbox:";
+
+/// OutboxAction is an on-chain action and the record change that follows it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum OutboxAction {
+    /// Mint the token of a new reward, then create the reward record.
+    Mint { reward: Box<RewardNFT>, to: Address },
+    /// Burn the token of a reward, then mark the reward as redeemed.
+    Burn { reward_id: Uuid, token_id: U256 },
+    /// Burn the token of an expired reward without crediting its holder,
+    /// then mark the reward as expired.
+    Expire { reward_id: Uuid, token_id: U256 },
+    /// Burn the token of a reward revoked by an admin without crediting its
+    /// holder, then record the revocation.
+    Revoke {
+        reward_id: Uuid,
+        token_id: U256,
+        revocation: Revocation,
+    },
+    /// Transfer the token of a reward from the wallet of one registered user
+    /// to another, then record the transfer.
+    Transfer {
+        reward_id: Uuid,
+        token_id: U256,
+        from: Uuid,
+        to: Uuid,
+        to_address: Address,
+    },
+    /// Burn the token of a reward, crediting the redeemed value to its
+    /// holder, and mint child rewards carrying the rest of its value. The
+    /// child records are created and the parent marked as split.
+    Split {
+        reward_id: Uuid,
+        token_id: U256,
+        redeemed: U256,
+        children: Vec<RewardNFT>,
+    },
+    /// Transfer reward tokens from the custodial wallet of a user to an
+    /// external address, then confirm the withdrawal.
+    Withdraw {
+        withdrawal_id: Uuid,
+        user_id: Uuid,
+        to: Address,
+        amount: U256,
+    },
+}
+
+impl OutboxAction {
+    /// Get the token id the action applies to, if it applies to a reward.
+    pub fn token_id(&self) -> Option<U256> {
+        match self {
+            OutboxAction::Mint { reward, .. } => Some(reward.get_token_id()),
+            OutboxAction::Burn { token_id, .. }
+            | OutboxAction::Expire { token_id, .. }
+            | OutboxAction::Revoke { token_id, .. }
+            | OutboxAction::Transfer { token_id, .. }
+            | OutboxAction::Split { token_id, .. } => Some(*token_id),
+            OutboxAction::Withdraw { .. } => None,
+        }
+    }
+
+    /// Get the webhook event sent once the action is confirmed. Withdrawals
+    /// are not about a reward, so they have no webhook events.
+    fn event(&self) -> Option<WebhookEvent> {
+        match self {
+            OutboxAction::Mint { .. } => Some(WebhookEvent::RewardMinted),
+            OutboxAction::Burn { .. } => Some(WebhookEvent::RewardRedeemed),
+            OutboxAction::Expire { .. } => Some(WebhookEvent::RewardExpired),
+            OutboxAction::Revoke { .. } => Some(WebhookEvent::RewardRevoked),
+            OutboxAction::Transfer { .. } => Some(WebhookEvent::RewardTransferred),
+            OutboxAction::Split { .. } => Some(WebhookEvent::RewardSplit),
+            OutboxAction::Withdraw { .. } => None,
+        }
+    }
+
+    /// Get the reward the action applies to.
+    async fn reward(&self) -> Result<RewardNFT, RewardError> {
+        match self {
+            OutboxAction::Mint { reward, .. } => Ok(*reward.clone()),
+            OutboxAction::Burn { reward_id, .. }
+            | OutboxAction::Expire { reward_id, .. }
+            | OutboxAction::Revoke { reward_id, .. }
+            | OutboxAction::Transfer { reward_id, .. }
+            | OutboxAction::Split { reward_id, .. } => {
+                RewardNFT::from_id(reward_id.to_string()).await
+            }
+            OutboxAction::Withdraw { .. } => Err(RewardError::NotFound),
+        }
+    }
+}
+
+/// OutboxStatus is the progress of an outbox entry.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum OutboxStatus {
+    /// The action has not been sent yet.
+    Pending,
+    /// The transaction was sent and is waiting to be mined.
+    Sent,
+    /// The transaction was mined and the record change applied.
+    Confirmed,
+    /// The action could not be completed.
+    Failed,
+}
+
+/// OutboxEntry records an on-chain action before its transaction is sent, so
+/// the action can be recovered and replayed after a restart. The process
+/// working on an entry holds a lease on it, which it renews with a heartbeat,
+/// so only one process sends and finalizes the entry at a time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// The id of the entry.
+    pub id: Uuid,
+    /// The action to perform.
+    pub action: OutboxAction,
+    /// The progress of the entry.
+    pub status: OutboxStatus,
+    /// The hash of the last transaction sent for the action.
+    pub tx_hash: Option<String>,
+    /// The hashes of all transactions sent for the action, including the
+    /// ones replaced with higher fees, any of which may be mined.
+    pub tx_hashes: Vec<String>,
+    /// The wallet sending the transactions of the action.
+    pub sender: Option<Address>,
+    /// The nonce of the transactions sent for the action. An action sent
+    /// again reuses it, so only one of its transactions can be mined.
+    pub nonce: Option<U256>,
+    /// How many transactions have been sent for the action.
+    pub attempts: u32,
+    /// The last error encountered.
+    pub error: Option<String>,
+    /// The process holding the lease on the entry.
+    pub owner: Option<Uuid>,
+    /// When the holder of the lease last renewed it, as a unix timestamp.
+    pub heartbeat: u64,
+    /// The lease held by this copy of the entry, which is not stored, so
+    /// copies loaded by other processes do not share it.
+    #[serde(skip)]
+    lease: Option<Uuid>,
+    /// When the entry was last updated, as a unix timestamp.
+    pub updated_at: u64,
+}
+
+impl OutboxEntry {
+    /// Create a new pending entry.
+    pub fn new(action: OutboxAction) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            action,
+            status: OutboxStatus::Pending,
+            tx_hash: None,
+            tx_hashes: Vec::new(),
+            sender: None,
+            nonce: None,
+            attempts: 0,
+            error: None,
+            owner: None,
+            heartbeat: 0,
+            lease: None,
+            updated_at: now(),
+        }
+    }
+
+    /// Look up an entry by id from the repository.
+    pub async fn from_id(id: Uuid) -> Result<Self, RewardError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+        let entry: Option<OutboxEntry> = db.read(outbox_key(&id))?;
+
+        entry.ok_or(RewardError::NotFound)
+    }
+
+    /// Get all entries that are not confirmed or failed.
+    pub async fn unfinished() -> Result<Vec<Self>, RewardError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+        let entries: Vec<(String, OutboxEntry)> = db.scan(OUTBOX_KEY_PREFIX.to_string())?;
+
+        Ok(entries
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| !entry.is_finished())
+            .collect())
+    }
+
+    /// Save the entry to the repository.
+    pub async fn save(&mut self) -> Result<(), RewardError> {
+        self.updated_at = now();
+
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(outbox_key(&self.id), self.clone())?)
+    }
+
+    /// If the entry is confirmed or failed.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, OutboxStatus::Confirmed | OutboxStatus::Failed)
+    }
+
+    /// Take or renew the lease on the entry for `owner`. The stored entry is
+    /// loaded first, so progress made by another process is picked up.
+    /// Returns false if the entry is finished or another process holds an
+    /// unexpired lease on it.
+    async fn claim(&mut self, owner: Uuid) -> Result<bool, RewardError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+        let stored: Option<OutboxEntry> = db.read(outbox_key(&self.id))?;
+
+        if let Some(stored) = stored {
+            let held = stored.owner.is_some_and(|holder| holder != owner)
+                && now().saturating_sub(stored.heartbeat) < lease_duration();
+            *self = OutboxEntry {
+                lease: self.lease,
+                ..stored
+            };
+
+            if held || self.is_finished() {
+                return Ok(false);
+            }
+        }
+
+        self.owner = Some(owner);
+        self.lease = Some(owner);
+        self.heartbeat = now();
+        self.updated_at = self.heartbeat;
+
+        db.update(outbox_key(&self.id), self.clone())?;
+
+        Ok(true)
+    }
+
+    /// Renew the lease held on the entry, or take a new one if none is held.
+    async fn renew(&mut self) -> Result<bool, RewardError> {
+        let owner = self.lease.unwrap_or_else(Uuid::new_v4);
+
+        self.claim(owner).await
+    }
+
+    /// Record a transaction sent for the action as the one to wait for.
+    fn record_transaction(&mut self, tx_hash: String) {
+        self.tx_hashes.push(tx_hash.clone());
+        self.tx_hash = Some(tx_hash);
+    }
+
+    /// Give up the lease on the entry. The entry is saved by the caller.
+    fn release(&mut self) {
+        self.owner = None;
+        self.lease = None;
+    }
+
+    /// Process the entry until it is confirmed or failed. Before anything is
+    /// sent the chain is checked, so an action whose transaction was sent
+    /// without being recorded is not sent twice. If the action could not be
+    /// sent yet, or another process holds the entry, `RewardError::Queued`
+    /// is returned with the id of the entry, which the outbox worker retries.
+    pub async fn process(&mut self) -> Result<(), RewardError> {
+        let bump_after = GasPolicy::from_env().bump_after;
+        let owner = self.lease.unwrap_or_else(Uuid::new_v4);
+
+        while !self.is_finished() {
+            // The heartbeat is saved before each wait for a receipt, so the
+            // worker does not take the entry over while it is waited on
+            if !self.claim(owner).await? {
+                break;
+            }
+
+            let step = match self.status {
+                OutboxStatus::Pending => self.submit().await,
+                OutboxStatus::Sent => self.wait(bump_after).await,
+                OutboxStatus::Confirmed | OutboxStatus::Failed => Ok(()),
+            };
+
+            match step {
+                Ok(()) => {}
+                // A failed entry reports its own error
+                Err(e) if self.is_finished() => return Err(e),
+                Err(RewardError::Queued(id)) => return Err(RewardError::Queued(id)),
+                // Anything else, such as fees above the ceiling or the RPC
+                // going away, leaves the entry queued for the worker
+                Err(e) => {
+                    self.error = Some(format!("{:?}", e));
+                    self.release();
+                    self.save().await?;
+
+                    return Err(RewardError::Queued(self.id));
+                }
+            }
+        }
+
+        match self.status {
+            OutboxStatus::Confirmed => Ok(()),
+            OutboxStatus::Failed => Err(RewardError::MintRewardError(
+                self.error.clone().unwrap_or_default(),
+            )),
+            OutboxStatus::Pending | OutboxStatus::Sent => Err(RewardError::Queued(self.id)),
+        }
+    }
+
+    /// Wait up to `timeout` for the transaction of a sent entry to be mined,
+    /// then confirm or fail the entry. A transaction it replaced may be mined
+    /// instead, so a dropped transaction is only sent again if none of the
+    /// transactions sent for the entry was mined. A stuck one is replaced
+    /// with higher fees.
+    async fn wait(&mut self, timeout: std::time::Duration) -> Result<(), RewardError> {
+        let tx_hash = self.tx_hash.clone().unwrap_or_default();
+        let mut state = wait_for_transaction(&tx_hash, timeout).await?;
+
+        if let TransactionState::Dropped = state {
+            if let Some((mined, success)) = find_mined(&self.tx_hashes).await? {
+                self.tx_hash = Some(mined);
+                self.save().await?;
+                state = TransactionState::Mined(success);
+            }
+        }
+
+        match state {
+            // Mined, but a revert may still mean the action was applied by an
+            // earlier transaction
+            TransactionState::Mined(success) if success || is_applied(&self.action).await? => {
+                self.finalize().await
+            }
+            TransactionState::Mined(_) => {
+                let tx_hash = self.tx_hash.clone().unwrap_or_default();
+
+                self.fail(format!("Transaction {} reverted", tx_hash)).await
+            }
+            // Dropped from the mempool, so send it again
+            TransactionState::Dropped => {
+                self.status = OutboxStatus::Pending;
+                self.save().await
+            }
+            // Stuck in the mempool, so replace it with higher fees
+            TransactionState::Pending => {
+                if let Some(replacement) = bump_transaction(&self.action, &tx_hash).await? {
+                    self.record_transaction(replacement);
+                    self.save().await?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Send the action of a pending entry without waiting for it to be
+    /// mined. The entry is left sent, or confirmed or failed if nothing had to
+    /// be sent. If the action could not be sent for a reason other than a
+    /// revert, the entry is left pending and `RewardError::Queued` returned.
+    ///
+    /// The transaction is recorded before it is broadcast, so an action that
+    /// was sent before is only sent again if none of its transactions was
+    /// mined, and then with the same nonce.
+    pub async fn submit(&mut self) -> Result<(), RewardError> {
+        let max_attempts = parse_env("OUTBOX_MAX_ATTEMPTS", 5);
+
+        if !self.renew().await? {
+            return match self.is_finished() {
+                true => Ok(()),
+                false => Err(RewardError::Queued(self.id)),
+            };
+        }
+
+        if self.status != OutboxStatus::Pending {
+            return Ok(());
+        }
+
+        if is_applied(&self.action).await? {
+            return self.finalize().await;
+        }
+
+        if let (Some(sender), Some(nonce)) = (self.sender, self.nonce) {
+            // Checked before the receipts, so a transaction mined meanwhile
+            // is not mistaken for another one taking the nonce
+            let used = is_nonce_used(sender, nonce).await?;
+
+            // A transaction sent before was mined, so wait on it instead
+            if let Some((mined, _)) = find_mined(&self.tx_hashes).await? {
+                self.tx_hash = Some(mined);
+                self.status = OutboxStatus::Sent;
+
+                return self.save().await;
+            }
+
+            // Another transaction took the nonce, so none of the ones sent
+            // for the action can be mined and it is sent with a new nonce
+            if used {
+                self.sender = None;
+                self.nonce = None;
+            }
+        }
+
+        if self.attempts >= max_attempts {
+            return self.fail("Too many attempts".into()).await;
+        }
+
+        self.attempts += 1;
+
+        let resent = self.nonce.is_some();
+        let sent = match sign(&self.action, self.nonce).await {
+            Ok(signed) => {
+                self.sender = Some(signed.from);
+                self.nonce = Some(signed.nonce);
+                self.record_transaction(signed.hash.clone());
+                self.save().await?;
+
+                broadcast(&signed).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match sent {
+            Ok(()) => {
+                self.status = OutboxStatus::Sent;
+                self.save().await
+            }
+            // The node holds a transaction with the nonce already, which may
+            // be one sent before, so wait for it
+            Err(RewardError::ChainError(
+                ChainError::NonceRejected(_) | ChainError::Underpriced(_),
+            )) if resent => {
+                self.status = OutboxStatus::Sent;
+                self.save().await
+            }
+            Err(e) => {
+                match &e {
+                    // A revert will not succeed when sent again
+                    RewardError::ChainError(chain_error) if chain_error.is_revert() => {
+                        self.fail(format!("{:?}", e)).await?;
+
+                        return Err(e);
+                    }
+                    // Nothing was sent, so the entry stays queued until fees
+                    // drop
+                    RewardError::ChainError(ChainError::FeeTooHigh { .. }) => {
+                        self.attempts -= 1;
+                    }
+                    _ => {}
+                }
+
+                // Release the lease, so the outbox worker retries the entry
+                self.error = Some(format!("{:?}", e));
+                self.release();
+                self.save().await?;
+
+                Err(RewardError::Queued(self.id))
+            }
+        }
+    }
+
+    /// Apply the record change of a mined action, record it in the history
+    /// of the users involved and confirm the entry.
+    async fn finalize(&mut self) -> Result<(), RewardError> {
+        // Apply the change once, even if another process finalized the entry
+        // or took it over meanwhile
+        if !self.renew().await? {
+            return Ok(());
+        }
+
+        let tx_hash = self.tx_hash.clone();
+        let mut activities = Vec::new();
+
+        match &self.action {
+            OutboxAction::Mint { reward, to } => {
+                let mut reward = reward.clone();
+                reward.transfer_to(*to, None);
+
+                match reward.save(true).await {
+                    // The record was created before a restart
+                    Ok(()) | Err(RewardError::AlreadyExists) => {}
+                    Err(e) => return Err(e),
+                }
+
+                activities.push(reward.activity(ActivityKind::Issued));
+                activities.push(reward.activity(ActivityKind::Minted));
+            }
+            OutboxAction::Burn { reward_id, .. } => {
+                let mut reward = RewardNFT::from_id(reward_id.to_string()).await?;
+                reward.set_redeemed(true);
+                reward.save(false).await?;
+
+                activities.push(reward.activity(ActivityKind::Redeemed));
+                activities.push(reward.activity(ActivityKind::Burned));
+            }
+            OutboxAction::Expire { reward_id, .. } => {
+                let mut reward = RewardNFT::from_id(reward_id.to_string()).await?;
+                reward.set_status(RewardStatus::Expired);
+                reward.save(false).await?;
+
+                activities.push(reward.activity(ActivityKind::Expired));
+            }
+            OutboxAction::Revoke {
+                reward_id,
+                revocation,
+                ..
+            } => {
+                let mut reward = RewardNFT::from_id(reward_id.to_string()).await?;
+                reward.set_revoked(revocation.clone());
+                reward.save(false).await?;
+
+                activities.push(
+                    reward
+                        .activity(ActivityKind::Revoked)
+                        .with_detail(revocation.reason.clone()),
+                );
+
+                // A reward issued by mistake does not count against its
+                // campaign
+                if let Some(campaign_id) = reward.get_campaign_id() {
+                    Campaign::release(campaign_id, reward.get_owner(), reward.get_value()).await?;
+                }
+            }
+            OutboxAction::Transfer {
+                reward_id,
+                from,
+                to,
+                to_address,
+                ..
+            } => {
+                let mut reward = RewardNFT::from_id(reward_id.to_string()).await?;
+                reward.record_transfer(
+                    RewardTransfer {
+                        from: *from,
+                        to: *to,
+                        tx_hash: self.tx_hash.clone(),
+                        transferred_at: now(),
+                    },
+                    *to_address,
+                );
+                reward.save(false).await?;
+
+                activities.push(
+                    Activity::new(*from, ActivityKind::TransferSent)
+                        .with_reward(*reward_id)
+                        .with_amount(reward.get_value())
+                        .with_counterparty(to.to_string()),
+                );
+                activities.push(
+                    reward
+                        .activity(ActivityKind::TransferReceived)
+                        .with_counterparty(from.to_string()),
+                );
+            }
+            OutboxAction::Split {
+                reward_id,
+                redeemed,
+                children,
+                ..
+            } => {
+                for child in children {
+                    match child.save(true).await {
+                        // The record was created before a restart
+                        Ok(()) | Err(RewardError::AlreadyExists) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                let mut reward = RewardNFT::from_id(reward_id.to_string()).await?;
+                reward.set_split(*redeemed, children);
+                reward.save(false).await?;
+
+                if !redeemed.is_zero() {
+                    activities.push(
+                        reward
+                            .activity(ActivityKind::Redeemed)
+                            .with_amount(*redeemed),
+                    );
+                }
+
+                activities.push(
+                    reward
+                        .activity(ActivityKind::Split)
+                        .with_amount(*redeemed)
+                        .with_detail(format!("Split into {} rewards", children.len())),
+                );
+            }
+            OutboxAction::Withdraw {
+                withdrawal_id,
+                user_id,
+                to,
+                amount,
+            } => {
+                let mut withdrawal = Withdrawal::from_id(*user_id, *withdrawal_id).await?;
+                withdrawal.status = WithdrawalStatus::Confirmed;
+                withdrawal.tx_hash = tx_hash.clone();
+                withdrawal.error = None;
+                withdrawal.save().await?;
+
+                activities.push(
+                    Activity::new(*user_id, ActivityKind::Withdrawn)
+                        .with_amount(*amount)
+                        .with_counterparty(format!("{:#x}", to)),
+                );
+            }
+        }
+
+        for activity in activities {
+            activity.with_tx_hash(tx_hash.clone()).record().await?;
+        }
+
+        self.status = OutboxStatus::Confirmed;
+        self.error = None;
+        self.release();
+        self.save().await?;
+
+        if let Some(event) = self.action.event() {
+            self.publish(event).await;
+        }
+
+        Ok(())
+    }
+
+    /// Mark the entry as failed. A failed mint returns its value to the
+    /// budget of its campaign, and a failed withdrawal frees its tokens.
+    async fn fail(&mut self, error: String) -> Result<(), RewardError> {
+        // Fail the entry once, so its campaign budget is released once
+        if !self.renew().await? {
+            return Ok(());
+        }
+
+        self.status = OutboxStatus::Failed;
+        self.error = Some(error.clone());
+        self.release();
+        self.save().await?;
+
+        if let OutboxAction::Withdraw {
+            withdrawal_id,
+            user_id,
+            amount,
+            ..
+        } = &self.action
+        {
+            let mut withdrawal = Withdrawal::from_id(*user_id, *withdrawal_id).await?;
+            withdrawal.status = WithdrawalStatus::Failed;
+            withdrawal.tx_hash = self.tx_hash.clone();
+            withdrawal.error = Some(error.clone());
+            withdrawal.save().await?;
+
+            Activity::new(*user_id, ActivityKind::Failed)
+                .with_amount(*amount)
+                .with_tx_hash(self.tx_hash.clone())
+                .with_detail(format!("Withdrawal failed: {}", error))
+                .record()
+                .await?;
+
+            return Ok(());
+        }
+
+        if let Ok(reward) = self.action.reward().await {
+            reward
+                .activity(ActivityKind::Failed)
+                .with_tx_hash(self.tx_hash.clone())
+                .with_detail(error)
+                .record()
+                .await?;
+        }
+
+        self.publish(WebhookEvent::RewardFailed).await;
+
+        if let OutboxAction::Mint { reward, .. } = &self.action {
+            if let Some(campaign_id) = reward.get_campaign_id() {
+                Campaign::release(campaign_id, reward.get_owner(), reward.get_value()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queue the webhook deliveries of an event about the reward of the
+    /// entry. Webhooks are best effort, so a failure is logged rather than
+    /// failing the action.
+    async fn publish(&self, event: WebhookEvent) {
+        let reward = match self.action.reward().await {
+            Ok(reward) => reward,
+            Err(e) => {
+                warn!("Failed to load the reward of entry {}: {:?}", self.id, e);
+                return;
+            }
+        };
+        let mut data = webhook::reward_data(&reward, self.tx_hash.clone());
+        data["error"] = serde_json::json!(self.error);
+
+        if let Err(e) = webhook::publish(event, data).await {
+            warn!(
+                "Failed to queue {} webhooks for entry {}: {:?}",
+                event, self.id, e
+            );
+        }
+    }
+}
+
+/// OutboxEntryV1 is the layout entries were stored with before the hashes
+/// of replaced transactions were recorded.
+#[derive(Deserialize)]
+struct OutboxEntryV1 {
+    id: Uuid,
+    action: OutboxAction,
+    status: OutboxStatus,
+    tx_hash: Option<String>,
+    attempts: u32,
+    error: Option<String>,
+    owner: Option<Uuid>,
+    heartbeat: u64,
+    updated_at: u64,
+}
+
+impl From<OutboxEntryV1> for OutboxEntryV2 {
+    fn from(v1: OutboxEntryV1) -> Self {
+        Self {
+            id: v1.id,
+            action: v1.action,
+            status: v1.status,
+            tx_hashes: v1.tx_hash.iter().cloned().collect(),
+            tx_hash: v1.tx_hash,
+            attempts: v1.attempts,
+            error: v1.error,
+            owner: v1.owner,
+            heartbeat: v1.heartbeat,
+            updated_at: v1.updated_at,
+        }
+    }
+}
+
+/// OutboxEntryV2 is the layout entries were stored with before the nonce of
+/// their transactions was recorded.
+#[derive(Deserialize)]
+struct OutboxEntryV2 {
+    id: Uuid,
+    action: OutboxAction,
+    status: OutboxStatus,
+    tx_hash: Option<String>,
+    tx_hashes: Vec<String>,
+    attempts: u32,
+    error: Option<String>,
+    owner: Option<Uuid>,
+    heartbeat: u64,
+    updated_at: u64,
+}
+
+impl From<OutboxEntryV2> for OutboxEntry {
+    fn from(v2: OutboxEntryV2) -> Self {
+        Self {
+            id: v2.id,
+            action: v2.action,
+            status: v2.status,
+            tx_hash: v2.tx_hash,
+            tx_hashes: v2.tx_hashes,
+            sender: None,
+            nonce: None,
+            attempts: v2.attempts,
+            error: v2.error,
+            owner: v2.owner,
+            heartbeat: v2.heartbeat,
+            lease: None,
+            updated_at: v2.updated_at,
+        }
+    }
+}
+
+impl SledModel for OutboxEntry {
+    const VERSION: u8 = 3;
+
+    fn migrate(version: u8, data: &[u8]) -> Result<Self, RepositoryError> {
+        match version {
+            0 | 1 => bincode::deserialize::<OutboxEntryV1>(data)
+                .map(|v1| Self::from(OutboxEntryV2::from(v1)))
+                .map_err(|_| RepositoryError::ReadError),
+            2 => bincode::deserialize::<OutboxEntryV2>(data)
+                .map(Self::from)
+                .map_err(|_| RepositoryError::ReadError),
+            _ => Err(RepositoryError::ReadError),
+        }
+    }
+}
+
+/// How long a lease on an entry lasts without a heartbeat, in seconds. A
+/// heartbeat is saved before each wait for a receipt, so the lease outlasts
+/// the longest wait by the retry delay of the outbox worker.
+fn lease_duration() -> u64 {
+    GasPolicy::from_env().bump_after.as_secs() + parse_env("OUTBOX_RETRY_AFTER", 60)
+}
+
+/// Get the repository key of an outbox entry.
+fn outbox_key(id: &Uuid) -> String {
+    format!("{}{}", OUTBOX_KEY_PREFIX, id)
+}
+
+/// Check if the action has already been applied on-chain.
+#[cfg(not(test))]
+async fn is_applied(action: &OutboxAction) -> Result<bool, RewardError> {
+    // A transfer of reward tokens leaves nothing on-chain to check, so a
+    // withdrawal is only confirmed by the receipts of its transactions
+    let Some(token_id) = action.token_id() else {
+        return Ok(false);
+    };
+    let exists = crate::core::chain::check_token_exists(token_id).await?;
+
+    match action {
+        OutboxAction::Mint { .. } => Ok(exists),
+        OutboxAction::Burn { .. }
+        | OutboxAction::Expire { .. }
+        | OutboxAction::Revoke { .. }
+        | OutboxAction::Split { .. } => Ok(!exists),
+        OutboxAction::Transfer { to_address, .. } => {
+            Ok(exists && crate::core::chain::get_token_owner(token_id).await? == *to_address)
+        }
+        OutboxAction::Withdraw { .. } => Ok(false),
+    }
+}
+
+#[cfg(test)]
+async fn is_applied(_action: &OutboxAction) -> Result<bool, RewardError> {
+    Ok(false)
+}
+
+/// Sign the transaction of an action without sending it, with `nonce` if
+/// given and the next nonce of the signing wallet otherwise.
+#[cfg(not(test))]
+async fn sign(
+    action: &OutboxAction,
+    nonce: Option<U256>,
+) -> Result<SignedTransaction, RewardError> {
+    use crate::core::chain::{self, SplitToken};
+
+    use super::funding::{ensure_gas, FundingConfig};
+    use super::user::User;
+
+    let (prepared, context) = match action {
+        OutboxAction::Mint { reward, to } => (
+            chain::prepare_mint(
+                *to,
+                reward.get_token_id(),
+                reward.token_uri(),
+                reward.get_value(),
+            )?,
+            "Failed to mint reward",
+        ),
+        OutboxAction::Burn {
+            reward_id,
+            token_id,
+        } => (
+            prepare_burn(reward_id, *token_id).await?,
+            "Failed to burn reward",
+        ),
+        OutboxAction::Split {
+            reward_id,
+            token_id,
+            redeemed,
+            children,
+        } => {
+            let wallet = get_split_signer(reward_id).await?;
+            let tokens: Vec<SplitToken> = children.iter().map(RewardNFT::split_token).collect();
+
+            (
+                chain::prepare_split(wallet, *token_id, *redeemed, &tokens)?,
+                "Failed to split reward",
+            )
+        }
+        OutboxAction::Expire { token_id, .. } | OutboxAction::Revoke { token_id, .. } => (
+            chain::prepare_forfeit(*token_id)?,
+            "Failed to forfeit reward",
+        ),
+        OutboxAction::Transfer {
+            token_id,
+            from,
+            to_address,
+            ..
+        } => {
+            let owner = User::from_id(from.to_string()).await?;
+
+            // The owner signs the transfer, so make sure they can pay for the
+            // gas
+            ensure_gas(&owner, &FundingConfig::from_env()).await?;
+
+            (
+                chain::prepare_transfer(owner.get_wallet()?, *to_address, *token_id)?,
+                "Failed to transfer reward",
+            )
+        }
+        OutboxAction::Withdraw {
+            user_id,
+            to,
+            amount,
+            ..
+        } => {
+            let user = User::from_id(user_id.to_string()).await?;
+
+            // The user signs the transfer, so make sure they can pay for the
+            // gas
+            ensure_gas(&user, &FundingConfig::from_env()).await?;
+
+            (
+                chain::prepare_token_transfer(user.get_wallet()?, *to, *amount)?,
+                "Failed to transfer reward tokens",
+            )
+        }
+    };
+
+    Ok(chain::simulate_and_sign(prepared, context, nonce).await?)
+}
+
+/// Prepare the burn of a reward in the configured redeem mode. In owner mode
+/// the owner sends the burn, so they are topped up with gas first. In relayer
+/// mode the owner signs a burn authorization, which the admin wallet submits
+/// to the contract and pays for.
+#[cfg(not(test))]
+async fn prepare_burn(
+    reward_id: &Uuid,
+    token_id: U256,
+) -> Result<crate::core::chain::PreparedCall, RewardError> {
+    use ethers::signers::Signer;
+
+    use super::authorization::{authorize_burn, verify_burn_authorization, RedeemMode};
+    use super::funding::{ensure_gas, FundingConfig};
+
+    let owner = RewardNFT::from_id(reward_id.to_string())
+        .await?
+        .fetch_owner()
+        .await?;
+
+    match RedeemMode::from_env() {
+        RedeemMode::Owner => {
+            ensure_gas(&owner, &FundingConfig::from_env()).await?;
+
+            Ok(crate::core::chain::prepare_burn(
+                owner.get_wallet()?,
+                token_id,
+            )?)
+        }
+        RedeemMode::Relayer => {
+            let (authorization, signature) = authorize_burn(&owner, token_id).await?;
+            verify_burn_authorization(&authorization, &signature, owner.get_wallet()?.address())
+                .await?;
+
+            Ok(crate::core::chain::prepare_relay_burn(
+                &authorization,
+                &signature,
+            )?)
+        }
+    }
+}
+
+/// Get the wallet sending the split of a reward in the configured redeem
+/// mode. In owner mode the owner sends the split, so they are topped up with
+/// gas first. In relayer mode the admin wallet sends it as the contract owner.
+#[cfg(not(test))]
+async fn get_split_signer(reward_id: &Uuid) -> Result<ethers::signers::LocalWallet, RewardError> {
+    use super::authorization::RedeemMode;
+    use super::funding::{ensure_gas, FundingConfig};
+
+    match RedeemMode::from_env() {
+        RedeemMode::Owner => {
+            let owner = RewardNFT::from_id(reward_id.to_string())
+                .await?
+                .fetch_owner()
+                .await?;
+            ensure_gas(&owner, &FundingConfig::from_env()).await?;
+
+            Ok(owner.get_wallet()?)
+        }
+        RedeemMode::Relayer => Ok(crate::core::chain::get_admin_wallet()?),
+    }
+}
+
+#[cfg(test)]
+async fn sign(
+    action: &OutboxAction,
+    nonce: Option<U256>,
+) -> Result<SignedTransaction, RewardError> {
+    let hash = match action {
+        OutboxAction::Mint { reward, to } => reward.mint(*to).await?,
+        OutboxAction::Burn { .. }
+        | OutboxAction::Expire { .. }
+        | OutboxAction::Revoke { .. }
+        | OutboxAction::Transfer { .. }
+        | OutboxAction::Split { .. } => format!("{:#x}", crate::utils::helpers::random_u256()),
+        OutboxAction::Withdraw { user_id, .. } => {
+            // The user signs the transfer
+            super::user::User::from_id(user_id.to_string()).await?;
+
+            format!("{:#x}", crate::utils::helpers::random_u256())
+        }
+    };
+
+    Ok(SignedTransaction {
+        from: Address::zero(),
+        nonce: nonce.unwrap_or_default(),
+        hash,
+        raw: Default::default(),
+    })
+}
+
+/// Broadcast a signed transaction.
+#[cfg(not(test))]
+async fn broadcast(signed: &SignedTransaction) -> Result<(), RewardError> {
+    Ok(crate::core::chain::broadcast(signed).await?)
+}
+
+#[cfg(test)]
+async fn broadcast(_signed: &SignedTransaction) -> Result<(), RewardError> {
+    Ok(())
+}
+
+/// Check if a transaction from `sender` with `nonce` was mined.
+#[cfg(not(test))]
+async fn is_nonce_used(sender: Address, nonce: U256) -> Result<bool, RewardError> {
+    Ok(crate::core::chain::get_confirmed_nonce(sender).await? > nonce)
+}
+
+#[cfg(test)]
+async fn is_nonce_used(_sender: Address, _nonce: U256) -> Result<bool, RewardError> {
+    Ok(false)
+}
+
+/// TransactionState is the state of a sent transaction.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, allow(dead_code))]
+enum TransactionState {
+    /// The transaction was mined, and whether it succeeded.
+    Mined(bool),
+    /// The transaction was dropped from the mempool.
+    Dropped,
+    /// The transaction is still waiting to be mined.
+    Pending,
+}
+
+/// Wait up to `timeout` for a transaction to be mined.
+#[cfg(not(test))]
+async fn wait_for_transaction(
+    tx_hash: &str,
+    timeout: std::time::Duration,
+) -> Result<TransactionState, RewardError> {
+    let receipt =
+        tokio::time::timeout(timeout, crate::core::chain::wait_for_transaction(tx_hash)).await;
+
+    Ok(match receipt {
+        Ok(receipt) => match receipt? {
+            Some(receipt) => TransactionState::Mined(receipt.status == Some(1.into())),
+            None => TransactionState::Dropped,
+        },
+        Err(_) => TransactionState::Pending,
+    })
+}
+
+#[cfg(test)]
+lazy_static::lazy_static! {
+    /// The state of transactions sent in tests by hash. Transactions missing
+    /// from it are mined successfully.
+    static ref TRANSACTIONS: std::sync::Mutex<std::collections::HashMap<String, TransactionState>> =
+        Default::default();
+}
+
+#[cfg(test)]
+fn transaction_state(tx_hash: &str) -> TransactionState {
+    TRANSACTIONS
+        .lock()
+        .unwrap()
+        .get(tx_hash)
+        .copied()
+        .unwrap_or(TransactionState::Mined(true))
+}
+
+#[cfg(test)]
+async fn wait_for_transaction(
+    tx_hash: &str,
+    _timeout: std::time::Duration,
+) -> Result<TransactionState, RewardError> {
+    Ok(transaction_state(tx_hash))
+}
+
+/// Find a mined transaction among the transactions sent for an action,
+/// returning its hash and whether it succeeded.
+#[cfg(not(test))]
+async fn find_mined(tx_hashes: &[String]) -> Result<Option<(String, bool)>, RewardError> {
+    for tx_hash in tx_hashes {
+        if let Some(receipt) = crate::core::chain::get_transaction_receipt(tx_hash).await? {
+            return Ok(Some((tx_hash.clone(), receipt.status == Some(1.into()))));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+async fn find_mined(tx_hashes: &[String]) -> Result<Option<(String, bool)>, RewardError> {
+    Ok(tx_hashes
+        .iter()
+        .find_map(|tx_hash| match transaction_state(tx_hash) {
+            TransactionState::Mined(success) => Some((tx_hash.clone(), success)),
+            _ => None,
+        }))
+}
+
+/// Replace a stuck transaction with one paying higher fees, returning the
+/// hash of the replacement. The replacement is signed by the same wallet:
+/// the contract owner for mints, relayed burns and splits, expiries and
+/// revocations, and the token owner for other burns, splits and transfers.
+/// Withdrawals are signed by the custodial wallet of the user.
+#[cfg(not(test))]
+async fn bump_transaction(
+    action: &OutboxAction,
+    tx_hash: &str,
+) -> Result<Option<String>, RewardError> {
+    use super::authorization::RedeemMode;
+
+    let wallet = match action {
+        OutboxAction::Mint { .. } | OutboxAction::Expire { .. } | OutboxAction::Revoke { .. } => {
+            crate::core::chain::get_admin_wallet()?
+        }
+        OutboxAction::Burn { .. } | OutboxAction::Split { .. }
+            if RedeemMode::from_env() == RedeemMode::Relayer =>
+        {
+            crate::core::chain::get_admin_wallet()?
+        }
+        OutboxAction::Transfer { from, .. } => super::user::User::from_id(from.to_string())
+            .await?
+            .get_wallet()?,
+        OutboxAction::Withdraw { user_id, .. } => super::user::User::from_id(user_id.to_string())
+            .await?
+            .get_wallet()?,
+        OutboxAction::Burn { reward_id, .. } | OutboxAction::Split { reward_id, .. } => {
+            RewardNFT::from_id(reward_id.to_string())
+                .await?
+                .fetch_owner()
+                .await?
+                .get_wallet()?
+        }
+    };
+
+    Ok(crate::core::chain::bump_transaction(wallet, tx_hash).await?)
+}
+
+#[cfg(test)]
+async fn bump_transaction(
+    _action: &OutboxAction,
+    _tx_hash: &str,
+) -> Result<Option<String>, RewardError> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::chain::generate_secret_key;
+    use crate::models::reward::generate_reward;
+    use crate::models::user::User;
+    use crate::utils::helpers::random_u256;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process_mint() {
+        let reward = generate_reward(100);
+        let to = Address::random();
+        let mut entry = OutboxEntry::new(OutboxAction::Mint {
+            reward: Box::new(reward.clone()),
+            to,
+        });
+
+        // The entry is saved before it is processed
+        entry.save().await.unwrap();
+        assert!(OutboxEntry::unfinished()
+            .await
+            .unwrap()
+            .iter()
+            .any(|e| e.id == entry.id));
+
+        entry.process().await.unwrap();
+
+        // The entry is confirmed and no longer unfinished
+        assert_eq!(entry.status, OutboxStatus::Confirmed);
+        assert_eq!(entry.attempts, 1);
+        assert!(entry.tx_hash.is_some());
+        assert!(!OutboxEntry::unfinished()
+            .await
+            .unwrap()
+            .iter()
+            .any(|e| e.id == entry.id));
+
+        // The reward record was created
+        let saved = RewardNFT::from_id(reward.get_id()).await.unwrap();
+
+        assert_eq!(saved.get_holder(), Some(to));
+    }
+
+    #[tokio::test]
+    async fn test_process_burn() {
+        let reward = generate_reward(100);
+        reward.save(true).await.unwrap();
+
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: reward.get_id().parse().unwrap(),
+            token_id: reward.get_token_id(),
+        });
+        entry.save().await.unwrap();
+        entry.process().await.unwrap();
+
+        // The reward is redeemed once the burn is confirmed
+        let saved = RewardNFT::from_id(reward.get_id()).await.unwrap();
+
+        assert!(saved.is_redeemed());
+        assert_eq!(
+            OutboxEntry::from_id(entry.id).await.unwrap().status,
+            OutboxStatus::Confirmed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_replay() {
+        let reward = generate_reward(100);
+        let mut entry = OutboxEntry::new(OutboxAction::Mint {
+            reward: Box::new(reward.clone()),
+            to: Address::random(),
+        });
+
+        // Simulate a restart after the transaction was sent
+        entry.status = OutboxStatus::Sent;
+        entry.tx_hash = Some(format!("{:#x}", random_u256()));
+        entry.attempts = 1;
+        entry.save().await.unwrap();
+
+        let mut entry = OutboxEntry::from_id(entry.id).await.unwrap();
+        entry.process().await.unwrap();
+
+        // The transaction is not sent again
+        assert_eq!(entry.status, OutboxStatus::Confirmed);
+        assert_eq!(entry.attempts, 1);
+        assert!(RewardNFT::from_id(reward.get_id()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_process_replaced() {
+        let reward = generate_reward(100);
+        reward.save(true).await.unwrap();
+
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: reward.get_id().parse().unwrap(),
+            token_id: reward.get_token_id(),
+        });
+
+        // The transaction was replaced with higher fees, but mined before
+        // its replacement
+        let (sent, replacement) = (
+            format!("{:#x}", random_u256()),
+            format!("{:#x}", random_u256()),
+        );
+        TRANSACTIONS
+            .lock()
+            .unwrap()
+            .insert(replacement.clone(), TransactionState::Dropped);
+        entry.status = OutboxStatus::Sent;
+        entry.attempts = 1;
+        entry.record_transaction(sent.clone());
+        entry.record_transaction(replacement.clone());
+        entry.save().await.unwrap();
+        entry.process().await.unwrap();
+
+        // The burn is confirmed with the mined transaction, not sent again
+        assert_eq!(entry.status, OutboxStatus::Confirmed);
+        assert_eq!(entry.attempts, 1);
+        assert_eq!(entry.tx_hash, Some(sent.clone()));
+
+        // If none of them was mined the action is sent again
+        let reward = generate_reward(100);
+        reward.save(true).await.unwrap();
+
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: reward.get_id().parse().unwrap(),
+            token_id: reward.get_token_id(),
+        });
+        let sent = format!("{:#x}", random_u256());
+        TRANSACTIONS
+            .lock()
+            .unwrap()
+            .insert(sent.clone(), TransactionState::Dropped);
+        entry.status = OutboxStatus::Sent;
+        entry.attempts = 1;
+        entry.record_transaction(sent.clone());
+        entry.record_transaction(replacement);
+        entry.save().await.unwrap();
+        entry.process().await.unwrap();
+
+        assert_eq!(entry.status, OutboxStatus::Confirmed);
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(entry.tx_hashes.len(), 3);
+    }
+
+    #[test]
+    fn test_migrate() {
+        #[derive(Serialize)]
+        struct V1 {
+            id: Uuid,
+            action: OutboxAction,
+            status: OutboxStatus,
+            tx_hash: Option<String>,
+            attempts: u32,
+            error: Option<String>,
+            owner: Option<Uuid>,
+            heartbeat: u64,
+            updated_at: u64,
+        }
+
+        let v1 = V1 {
+            id: Uuid::new_v4(),
+            action: OutboxAction::Burn {
+                reward_id: Uuid::new_v4(),
+                token_id: random_u256(),
+            },
+            status: OutboxStatus::Sent,
+            tx_hash: Some(format!("{:#x}", random_u256())),
+            attempts: 1,
+            error: None,
+            owner: None,
+            heartbeat: 0,
+            updated_at: now(),
+        };
+
+        // The transaction of entries stored before its replacements were
+        // recorded is the only one sent
+        let mut data = vec![0xff, 1];
+        data.extend(bincode::serialize(&v1).unwrap());
+        let entry = OutboxEntry::from_vec(data).unwrap();
+
+        assert_eq!(entry.id, v1.id);
+        assert_eq!(entry.status, OutboxStatus::Sent);
+        assert_eq!(entry.tx_hashes, v1.tx_hash.into_iter().collect::<Vec<_>>());
+        assert_eq!(entry.nonce, None);
+    }
+
+    #[tokio::test]
+    async fn test_process_resend() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+
+        let withdraw = || OutboxAction::Withdraw {
+            withdrawal_id: Uuid::new_v4(),
+            user_id: user.id,
+            to: Address::random(),
+            amount: U256::from(100),
+        };
+
+        // The transfer was signed, but the broadcast failed with no way of
+        // knowing if it reached the node
+        let mut entry = OutboxEntry::new(withdraw());
+        let sent = format!("{:#x}", random_u256());
+        entry.sender = Some(Address::random());
+        entry.nonce = Some(U256::from(7));
+        entry.attempts = 1;
+        entry.record_transaction(sent.clone());
+        entry.save().await.unwrap();
+
+        // The transfer was mined, so it is not sent again
+        assert!(matches!(entry.submit().await, Ok(())));
+        assert_eq!(entry.status, OutboxStatus::Sent);
+        assert_eq!(entry.attempts, 1);
+        assert_eq!(entry.tx_hash, Some(sent));
+
+        // A transfer that was not mined is sent again with its nonce
+        let mut entry = OutboxEntry::new(withdraw());
+        let sent = format!("{:#x}", random_u256());
+        TRANSACTIONS
+            .lock()
+            .unwrap()
+            .insert(sent.clone(), TransactionState::Dropped);
+        entry.sender = Some(Address::random());
+        entry.nonce = Some(U256::from(7));
+        entry.attempts = 1;
+        entry.record_transaction(sent);
+        entry.save().await.unwrap();
+
+        assert!(matches!(entry.submit().await, Ok(())));
+        assert_eq!(entry.status, OutboxStatus::Sent);
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(entry.nonce, Some(U256::from(7)));
+        assert_eq!(entry.tx_hashes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_process_queued() {
+        // The transfer cannot be sent for a user missing from the repository
+        let mut entry = OutboxEntry::new(OutboxAction::Withdraw {
+            withdrawal_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            to: Address::random(),
+            amount: U256::from(100),
+        });
+        entry.save().await.unwrap();
+
+        // The entry is left pending for the worker and released
+        assert!(matches!(
+            entry.process().await,
+            Err(RewardError::Queued(id)) if id == entry.id
+        ));
+        assert_eq!(entry.status, OutboxStatus::Pending);
+        assert_eq!(entry.attempts, 1);
+        assert!(entry.error.is_some());
+        assert!(entry.owner.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_process_leased() {
+        let reward = generate_reward(100);
+        reward.save(true).await.unwrap();
+
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: reward.get_id().parse().unwrap(),
+            token_id: reward.get_token_id(),
+        });
+
+        // Another process holds the entry
+        entry.owner = Some(Uuid::new_v4());
+        entry.heartbeat = now();
+        entry.save().await.unwrap();
+
+        assert!(matches!(entry.process().await, Err(RewardError::Queued(_))));
+        assert_eq!(entry.status, OutboxStatus::Pending);
+        assert_eq!(entry.attempts, 0);
+
+        // Once the lease expires the entry is taken over
+        entry.heartbeat = 0;
+        entry.save().await.unwrap();
+        entry.process().await.unwrap();
+
+        assert_eq!(entry.status, OutboxStatus::Confirmed);
+        assert!(entry.owner.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_finalize_once() {
+        let reward = generate_reward(100);
+        reward.save(true).await.unwrap();
+
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: reward.get_id().parse().unwrap(),
+            token_id: reward.get_token_id(),
+        });
+        entry.save().await.unwrap();
+
+        // A copy of the entry waiting on the transaction as well
+        let mut stale = entry.clone();
+        stale.status = OutboxStatus::Sent;
+        stale.tx_hash = Some(format!("{:#x}", random_u256()));
+
+        entry.process().await.unwrap();
+        stale.process().await.unwrap();
+
+        // The burn is only applied and recorded once
+        let burned = Activity::for_user(reward.get_owner())
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|activity| activity.kind == ActivityKind::Burned)
+            .count();
+
+        assert_eq!(burned, 1);
+        assert_eq!(stale.status, OutboxStatus::Confirmed);
+        assert_eq!(stale.tx_hash, entry.tx_hash);
+    }
+
+    #[tokio::test]
+    async fn test_process_history() {
+        let reward = generate_reward(100);
+        reward.save(true).await.unwrap();
+
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: reward.get_id().parse().unwrap(),
+            token_id: reward.get_token_id(),
+        });
+        entry.attempts = 5;
+
+        // A burn that never happened is not recorded as a redemption
+        assert!(entry.process().await.is_err());
+
+        let kinds: Vec<ActivityKind> = Activity::for_user(reward.get_owner())
+            .await
+            .unwrap()
+            .iter()
+            .map(|activity| activity.kind)
+            .collect();
+
+        assert_eq!(kinds, [ActivityKind::Failed]);
+
+        // A mint is recorded as issued once it is confirmed
+        let reward = generate_reward(100);
+        let mut entry = OutboxEntry::new(OutboxAction::Mint {
+            reward: Box::new(reward.clone()),
+            to: Address::random(),
+        });
+        entry.process().await.unwrap();
+
+        let kinds: Vec<ActivityKind> = Activity::for_user(reward.get_owner())
+            .await
+            .unwrap()
+            .iter()
+            .map(|activity| activity.kind)
+            .collect();
+
+        assert_eq!(kinds, [ActivityKind::Issued, ActivityKind::Minted]);
+    }
+
+    #[tokio::test]
+    async fn test_process_too_many_attempts() {
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: Uuid::new_v4(),
+            token_id: random_u256(),
+        });
+        entry.attempts = 5;
+
+        // The entry fails instead of sending again
+        assert!(entry.process().await.is_err());
+        assert_eq!(entry.status, OutboxStatus::Failed);
+        assert!(entry.error.is_some());
+    }
+}
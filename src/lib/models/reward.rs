@@ -1,24 +1,98 @@
-use ethers::{signers::LocalWallet, types::U256};
+use ethers::{
+    signers::Signer,
+    types::{Address, U256},
+};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
     core::{
+        chain::{Simulation, SplitToken},
         repository::{Repository, RepositoryError},
         reward::RewardError,
     },
     rewards::Reward,
-    storage::sled::{get_sled_db, SledModel},
-    utils::helpers::random_u256,
+    storage::sled::{get_sled_db, SledModel, SledRepository},
+    utils::{
+        config::parse_env,
+        helpers::{now, random_u256},
+    },
 };
 
+use super::activity::{Activity, ActivityKind};
+use super::campaign::Campaign;
+use super::outbox::{OutboxAction, OutboxEntry};
 use super::user::User;
 
 /// TODO Improve
 const REWARD_NFT_URL: &str = "https://localhost:3001/api/v1/reward";
 
+/// RewardStatus is where a reward is in its lifecycle.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum RewardStatus {
+    /// The token exists and can be redeemed.
+    Active,
+    /// The token was burned and its value credited to the holder.
+    Redeemed,
+    /// The token was burned after the reward expired, without crediting the
+    /// holder.
+    Expired,
+    /// The token was burned by an admin, without crediting the holder.
+    Revoked,
+    /// The token was burned and its value carried over to child rewards,
+    /// less any part that was redeemed.
+    Split,
+}
+
+/// Revocation records why and by whom a reward was revoked.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Revocation {
+    /// Why the reward was revoked.
+    pub reason: String,
+    /// Who revoked the reward.
+    pub actor: String,
+    /// When the reward was revoked, as a unix timestamp.
+    pub revoked_at: u64,
+}
+
+/// IssueOptions are the optional settings of a new reward.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IssueOptions {
+    /// The campaign the reward is issued from.
+    pub campaign_id: Option<Uuid>,
+    /// When the reward expires, as a unix timestamp. Defaults to the reward
+    /// lifetime of the campaign, if any.
+    pub expires_at: Option<u64>,
+}
+
+impl IssueOptions {
+    /// Check that the options could issue a reward.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.expires_at {
+            Some(expires_at) if expires_at <= now() => {
+                Err("Rewards must expire in the future".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// RewardTransfer records a transfer of a reward between registered users.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RewardTransfer {
+    /// The user the reward was transferred from.
+    pub from: Uuid,
+    /// The user the reward was transferred to.
+    pub to: Uuid,
+    /// The hash of the transfer transaction.
+    pub tx_hash: Option<String>,
+    /// When the transfer was confirmed, as a unix timestamp.
+    pub transferred_at: u64,
+}
+
 /// A simple reward that can be redeemed.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RewardNFT {
     /// The id of the reward.
     id: Uuid,
@@ -30,8 +104,24 @@ pub struct RewardNFT {
     value: U256,
     /// The url of NFT reward data.
     url: String,
-    /// If the reward is redeemed.
-    redeemed: bool,
+    /// Where the reward is in its lifecycle.
+    status: RewardStatus,
+    /// The address last seen holding the token on-chain.
+    holder: Option<Address>,
+    /// The campaign the reward was issued from.
+    campaign_id: Option<Uuid>,
+    /// When the reward expires, as a unix timestamp.
+    expires_at: Option<u64>,
+    /// Why and by whom the reward was revoked.
+    revocation: Option<Revocation>,
+    /// The transfers of the reward between registered users, oldest first.
+    transfers: Vec<RewardTransfer>,
+    /// The reward this reward was split from.
+    parent_id: Option<Uuid>,
+    /// The rewards this reward was split into.
+    children: Vec<Uuid>,
+    /// The value credited to the holder when the reward was redeemed.
+    redeemed_value: U256,
 }
 
 impl RewardNFT {
@@ -39,7 +129,6 @@ impl RewardNFT {
     pub fn new(owner: User, value: U256, token_id: U256) -> Self {
         let id = Uuid::new_v4();
         let url = format!("{}/{}", REWARD_NFT_URL, id);
-        let redeemed = false;
         let owner = owner.id;
 
         Self {
@@ -48,8 +137,576 @@ impl RewardNFT {
             token_id,
             value,
             url,
+            status: RewardStatus::Active,
+            holder: None,
+            campaign_id: None,
+            expires_at: None,
+            revocation: None,
+            transfers: Vec::new(),
+            parent_id: None,
+            children: Vec::new(),
+            redeemed_value: U256::zero(),
+        }
+    }
+
+    /// Link the reward to the campaign it is issued from.
+    pub fn with_campaign(mut self, campaign_id: Option<Uuid>) -> Self {
+        self.campaign_id = campaign_id;
+        self
+    }
+
+    /// Set when the reward expires.
+    pub fn with_expiry(mut self, expires_at: Option<u64>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    /// Issue a new reward to a user. The mint is recorded in the outbox
+    /// before it is sent, and the reward is saved once the mint is confirmed.
+    pub async fn issue(
+        owner: User,
+        value: U256,
+        options: IssueOptions,
+    ) -> Result<Self, RewardError> {
+        let mut entry = Self::prepare_issue(owner, value, options).await?;
+
+        Self::complete_issue(&mut entry).await
+    }
+
+    /// Issue rewards to several users. Every mint is recorded and sent before
+    /// any is waited on, so the mints use consecutive nonces and are mined
+    /// together. Each mint succeeds or fails on its own.
+    pub async fn issue_batch(
+        items: Vec<(User, U256, IssueOptions)>,
+    ) -> Vec<Result<Self, RewardError>> {
+        let mut entries = Vec::with_capacity(items.len());
+
+        for (owner, value, options) in items {
+            entries.push(Self::prepare_issue(owner, value, options).await);
+        }
+
+        for entry in entries.iter_mut() {
+            let submitted = match entry {
+                Ok(entry) => entry.submit().await,
+                Err(_) => Ok(()),
+            };
+
+            if let Err(e) = submitted {
+                *entry = Err(e);
+            }
+        }
+
+        let mut rewards = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            rewards.push(match entry {
+                Ok(mut entry) => Self::complete_issue(&mut entry).await,
+                Err(e) => Err(e),
+            });
+        }
+
+        rewards
+    }
+
+    /// Record the mint of a new reward in the outbox without sending it.
+    pub async fn prepare_issue(
+        owner: User,
+        value: U256,
+        options: IssueOptions,
+    ) -> Result<OutboxEntry, RewardError> {
+        let mut entry = Self::issue_entry(owner, value, options).await?;
+        entry.save().await?;
+
+        Ok(entry)
+    }
+
+    /// Create the outbox entry minting a new reward without saving it. The
+    /// value of the reward is reserved from the budget of its campaign, and
+    /// returned if the mint fails.
+    pub async fn issue_entry(
+        owner: User,
+        value: U256,
+        options: IssueOptions,
+    ) -> Result<OutboxEntry, RewardError> {
+        let to = owner.get_wallet()?.address();
+        let mut expires_at = options.expires_at;
+
+        if let Some(campaign_id) = options.campaign_id {
+            let campaign = Campaign::reserve(campaign_id, owner.id, value).await?;
+
+            expires_at = expires_at.or(campaign.reward_expiry(now()));
+        }
+
+        let reward = Self::new(owner, value, random_u256())
+            .with_campaign(options.campaign_id)
+            .with_expiry(expires_at);
+
+        Ok(OutboxEntry::new(OutboxAction::Mint {
+            reward: Box::new(reward),
+            to,
+        }))
+    }
+
+    /// Burn the token of an expired reward without crediting its holder and
+    /// mark the reward as expired.
+    pub async fn expire(&mut self) -> Result<(), RewardError> {
+        // Record the burn before it is sent so it can be recovered
+        let mut entry = OutboxEntry::new(OutboxAction::Expire {
+            reward_id: self.id,
+            token_id: self.token_id,
+        });
+        entry.save().await?;
+        entry.process().await?;
+
+        self.status = RewardStatus::Expired;
+
+        Ok(())
+    }
+
+    /// Burn the token of a reward issued by mistake without crediting its
+    /// holder and mark the reward as revoked.
+    pub async fn revoke(&mut self, reason: String, actor: String) -> Result<(), RewardError> {
+        self.check_redeemable()?;
+
+        let revocation = Revocation {
+            reason,
+            actor,
+            revoked_at: now(),
+        };
+
+        // Record the burn before it is sent so it can be recovered
+        let mut entry = OutboxEntry::new(OutboxAction::Revoke {
+            reward_id: self.id,
+            token_id: self.token_id,
+            revocation: revocation.clone(),
+        });
+        entry.save().await?;
+        entry.process().await?;
+
+        self.set_revoked(revocation);
+
+        Ok(())
+    }
+
+    /// Transfer the reward to another registered user. The token is sent
+    /// from the wallet of the current owner, which is topped up with gas
+    /// first.
+    pub async fn transfer(&mut self, to: &User) -> Result<RewardTransfer, RewardError> {
+        self.check_redeemable()?;
+
+        if to.id == self.owner {
+            return Err(RewardError::SameOwner);
+        }
+
+        // Record the transfer before it is sent so it can be recovered
+        let mut entry = OutboxEntry::new(OutboxAction::Transfer {
+            reward_id: self.id,
+            token_id: self.token_id,
+            from: self.owner,
+            to: to.id,
+            to_address: to.get_wallet()?.address(),
+        });
+        entry.save().await?;
+        entry.process().await?;
+
+        *self = Self::from_id(self.get_id()).await?;
+
+        self.transfers.last().cloned().ok_or(RewardError::NotFound)
+    }
+
+    /// Redeem part of the value of the reward. The token is burned, the
+    /// redeemed value credited to the holder, and the rest carried over to a
+    /// new reward, which is returned.
+    pub async fn redeem_partial(&mut self, value: U256) -> Result<Self, RewardError> {
+        self.check_partial(value)?;
+
+        let mut children = self.split_into(value, vec![self.value - value]).await?;
+
+        Ok(children.remove(0))
+    }
+
+    /// Split the reward into new rewards of the given values, which must add
+    /// up to the value of the reward. Nothing is credited to the holder.
+    pub async fn split(&mut self, values: Vec<U256>) -> Result<Vec<Self>, RewardError> {
+        if values.len() < 2 {
+            return Err(RewardError::InvalidValue(
+                "A reward must be split into at least two rewards".into(),
+            ));
+        }
+
+        self.split_into(U256::zero(), values).await
+    }
+
+    /// Burn the token of the reward and mint child rewards of the given
+    /// values, crediting the holder with the redeemed value. The children
+    /// keep the owner, campaign and expiry of the reward.
+    async fn split_into(
+        &mut self,
+        redeemed: U256,
+        values: Vec<U256>,
+    ) -> Result<Vec<Self>, RewardError> {
+        self.check_redeemable()?;
+        self.check_split(redeemed, &values)?;
+
+        let children: Vec<Self> = values.into_iter().map(|value| self.child(value)).collect();
+
+        // Record the split before it is sent so it can be recovered
+        let mut entry = OutboxEntry::new(OutboxAction::Split {
+            reward_id: self.id,
+            token_id: self.token_id,
             redeemed,
+            children: children.clone(),
+        });
+        entry.save().await?;
+        entry.process().await?;
+
+        *self = Self::from_id(self.get_id()).await?;
+
+        let mut saved = Vec::with_capacity(children.len());
+
+        for child in children {
+            saved.push(Self::from_id(child.get_id()).await?);
+        }
+
+        Ok(saved)
+    }
+
+    /// Check that part of the reward can be redeemed, leaving some value.
+    fn check_partial(&self, value: U256) -> Result<(), RewardError> {
+        if value.is_zero() || value >= self.value {
+            return Err(RewardError::InvalidValue(format!(
+                "The redeemed value must be greater than 0 and less than {}",
+                self.value
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the redeemed value and the values of the children add up
+    /// to the value of the reward.
+    fn check_split(&self, redeemed: U256, values: &[U256]) -> Result<(), RewardError> {
+        let max_parts = max_split_parts();
+
+        if values.len() > max_parts {
+            return Err(RewardError::InvalidValue(format!(
+                "A reward can be split into at most {} rewards",
+                max_parts
+            )));
+        }
+
+        if values.iter().any(|value| value.is_zero()) {
+            return Err(RewardError::InvalidValue(
+                "Split rewards must have a value greater than 0".into(),
+            ));
+        }
+
+        let total = values
+            .iter()
+            .try_fold(redeemed, |total, value| total.checked_add(*value));
+
+        if total != Some(self.value) {
+            return Err(RewardError::InvalidValue(format!(
+                "Split values must add up to {}",
+                self.value
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Create a new reward split from this one.
+    fn child(&self, value: U256) -> Self {
+        let id = Uuid::new_v4();
+
+        Self {
+            id,
+            owner: self.owner,
+            token_id: random_u256(),
+            value,
+            url: format!("{}/{}", REWARD_NFT_URL, id),
+            status: RewardStatus::Active,
+            holder: self.holder,
+            campaign_id: self.campaign_id,
+            expires_at: self.expires_at,
+            revocation: None,
+            transfers: Vec::new(),
+            parent_id: Some(self.id),
+            children: Vec::new(),
+            redeemed_value: U256::zero(),
+        }
+    }
+
+    /// Get the token minted for the reward when it is split from its parent.
+    #[cfg_attr(test, allow(dead_code))]
+    pub(crate) fn split_token(&self) -> SplitToken {
+        SplitToken {
+            token_id: self.token_id,
+            uri: self.token_uri(),
+            value: self.value,
+        }
+    }
+
+    /// Simulate issuing a new reward to a user without sending or saving
+    /// anything.
+    pub async fn simulate_issue(owner: User, value: U256) -> Result<Simulation, RewardError> {
+        let to = owner.get_wallet()?.address();
+        let reward = Self::new(owner, value, random_u256());
+
+        simulate_mint(&reward, to).await
+    }
+
+    /// Simulate redeeming the reward without sending anything.
+    pub async fn simulate_redeem(&self) -> Result<Simulation, RewardError> {
+        self.check_redeemable()?;
+
+        simulate_burn(self).await
+    }
+
+    /// Simulate redeeming part of the reward without sending anything.
+    pub async fn simulate_redeem_partial(&self, value: U256) -> Result<Simulation, RewardError> {
+        self.check_redeemable()?;
+        self.check_partial(value)?;
+
+        simulate_split(self, value, &[self.child(self.value - value)]).await
+    }
+
+    /// Check that the reward can still be redeemed.
+    fn check_redeemable(&self) -> Result<(), RewardError> {
+        match self.status {
+            RewardStatus::Redeemed | RewardStatus::Split => {
+                return Err(RewardError::AlreadyRedeemed)
+            }
+            RewardStatus::Revoked => return Err(RewardError::Revoked),
+            RewardStatus::Active | RewardStatus::Expired => {}
+        }
+
+        if self.is_expired(now()) {
+            return Err(RewardError::Expired);
+        }
+
+        Ok(())
+    }
+
+    /// Create an activity entry about the reward for its owner, with the
+    /// value of the reward as the amount.
+    pub fn activity(&self, kind: ActivityKind) -> Activity {
+        Activity::new(self.owner, kind)
+            .with_reward(self.id)
+            .with_amount(self.value)
+    }
+
+    /// Look up the user owning the reward.
+    pub async fn fetch_owner(&self) -> Result<User, RewardError> {
+        Ok(User::from_id(self.owner.to_string()).await?)
+    }
+
+    /// Get the metadata uri of the token.
+    pub(crate) fn token_uri(&self) -> String {
+        format!("{}/{}", REWARD_NFT_URL, self.token_id)
+    }
+
+    /// Process a mint recorded by `prepare_issue` and return the reward.
+    pub async fn complete_issue(entry: &mut OutboxEntry) -> Result<Self, RewardError> {
+        entry.process().await?;
+
+        match &entry.action {
+            OutboxAction::Mint { reward, .. } => Self::from_id(reward.get_id()).await,
+            OutboxAction::Burn { .. }
+            | OutboxAction::Expire { .. }
+            | OutboxAction::Revoke { .. }
+            | OutboxAction::Transfer { .. }
+            | OutboxAction::Split { .. }
+            | OutboxAction::Withdraw { .. } => Err(RewardError::NotFound),
+        }
+    }
+
+    /// Get the token id of the reward.
+    pub fn get_token_id(&self) -> U256 {
+        self.token_id
+    }
+
+    /// Get the campaign the reward was issued from.
+    pub fn get_campaign_id(&self) -> Option<Uuid> {
+        self.campaign_id
+    }
+
+    /// Get where the reward is in its lifecycle.
+    pub fn get_status(&self) -> RewardStatus {
+        self.status
+    }
+
+    /// Get when the reward expires, as a unix timestamp.
+    pub fn get_expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    /// If the reward has expired at the given time, whether or not its token
+    /// has been burned yet.
+    pub fn is_expired(&self, at: u64) -> bool {
+        match self.status {
+            RewardStatus::Expired => true,
+            RewardStatus::Redeemed | RewardStatus::Revoked | RewardStatus::Split => false,
+            RewardStatus::Active => self.expires_at.is_some_and(|expires_at| expires_at <= at),
+        }
+    }
+
+    /// Get why and by whom the reward was revoked.
+    pub fn get_revocation(&self) -> Option<&Revocation> {
+        self.revocation.as_ref()
+    }
+
+    /// Record that the reward was revoked.
+    pub fn set_revoked(&mut self, revocation: Revocation) {
+        self.status = RewardStatus::Revoked;
+        self.revocation = Some(revocation);
+    }
+
+    /// If the token of the reward has been burned on-chain.
+    pub fn is_burned(&self) -> bool {
+        self.status != RewardStatus::Active
+    }
+
+    /// Get the address last seen holding the token on-chain.
+    pub fn get_holder(&self) -> Option<Address> {
+        self.holder
+    }
+
+    /// Record that the token was transferred to a new holder. The owner is
+    /// only changed if the holder belongs to a registered user.
+    pub fn transfer_to(&mut self, holder: Address, owner: Option<Uuid>) {
+        self.holder = Some(holder);
+
+        if let Some(owner) = owner {
+            self.owner = owner;
+        }
+    }
+
+    /// Get the reward this reward was split from.
+    pub fn get_parent_id(&self) -> Option<Uuid> {
+        self.parent_id
+    }
+
+    /// Get the rewards this reward was split into.
+    pub fn get_children(&self) -> &[Uuid] {
+        &self.children
+    }
+
+    /// Get the value credited to the holder when the reward was redeemed.
+    pub fn get_redeemed_value(&self) -> U256 {
+        self.redeemed_value
+    }
+
+    /// Record that the reward was split into child rewards, crediting the
+    /// holder with the redeemed value.
+    pub fn set_split(&mut self, redeemed: U256, children: &[RewardNFT]) {
+        self.status = RewardStatus::Split;
+        self.redeemed_value = redeemed;
+        self.children = children.iter().map(|child| child.id).collect();
+    }
+
+    /// Get the transfers of the reward between registered users, oldest
+    /// first.
+    pub fn get_transfers(&self) -> &[RewardTransfer] {
+        &self.transfers
+    }
+
+    /// Record a confirmed transfer to another registered user.
+    pub fn record_transfer(&mut self, transfer: RewardTransfer, holder: Address) {
+        self.transfer_to(holder, Some(transfer.to));
+        self.transfers.push(transfer);
+    }
+
+    /// Set if the token has been burned on-chain. Marking the reward as not
+    /// redeemed only changes its status, anything recorded about the burn is
+    /// kept.
+    pub fn set_redeemed(&mut self, redeemed: bool) {
+        match redeemed {
+            true => (self.status, self.redeemed_value) = (RewardStatus::Redeemed, self.value),
+            false => self.status = RewardStatus::Active,
+        }
+    }
+
+    /// Set where the reward is in its lifecycle.
+    pub fn set_status(&mut self, status: RewardStatus) {
+        self.status = status;
+    }
+
+    /// Set the value of the reward.
+    pub fn set_value(&mut self, value: U256) {
+        self.value = value;
+    }
+
+    /// Get all rewards from the repository. The reward records are read
+    /// rather than the token index, so rewards missing from the index are
+    /// included.
+    pub async fn all() -> Result<Vec<Self>, RewardError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(stored_rewards(&db)?)
+    }
+
+    /// Get the rewards owned by a user.
+    pub async fn by_owner(owner: Uuid) -> Result<Vec<Self>, RewardError> {
+        Ok(Self::all()
+            .await?
+            .into_iter()
+            .filter(|reward| reward.owner == owner)
+            .collect())
+    }
+
+    /// Get the rewards that have expired at the given time but whose tokens
+    /// have not been burned yet.
+    pub async fn expired(at: u64) -> Result<Vec<Self>, RewardError> {
+        Ok(Self::all()
+            .await?
+            .into_iter()
+            .filter(|reward| !reward.is_burned() && reward.is_expired(at))
+            .collect())
+    }
+
+    /// Index the rewards stored without a token index entry, such as the
+    /// ones stored before the index was written, so they can be looked up by
+    /// token id. Returns how many rewards were indexed.
+    pub async fn backfill_index() -> Result<usize, RewardError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+        let mut indexed = 0;
+
+        for reward in stored_rewards(&db)? {
+            let id: Option<Uuid> = db.read(token_key(&reward.token_id))?;
+
+            if id.is_none() {
+                db.create(token_key(&reward.token_id), reward.id)?;
+                indexed += 1;
+            }
         }
+
+        Ok(indexed)
+    }
+
+    /// Look up a reward by its on-chain token id.
+    pub async fn from_token_id(token_id: U256) -> Result<Self, RewardError> {
+        let id = {
+            let connection = get_sled_db()?;
+            let db = connection
+                .read()
+                .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
+            let id: Result<Option<Uuid>, RepositoryError> = db.read(token_key(&token_id));
+
+            match id {
+                Ok(Some(id)) => id,
+                Ok(None) => return Err(RewardError::NotFound),
+                Err(e) => return Err(RewardError::RepositoryError(e)),
+            }
+        };
+
+        Self::from_id(id.to_string()).await
     }
 
     pub async fn from_id(id: String) -> Result<Self, RewardError> {
@@ -88,6 +745,10 @@ impl RewardNFT {
             Ok(None) if create => {
                 db.create(self.id.to_string(), self.clone())
                     .map_err(|_| RewardError::RepositoryError(RepositoryError::InsertionError))?;
+                // Index the reward by token id so on-chain events can be
+                // matched back to the reward
+                db.create(token_key(&self.token_id), self.id)
+                    .map_err(|_| RewardError::RepositoryError(RepositoryError::InsertionError))?;
                 Ok(())
             }
             _ => Err(RewardError::RepositoryError(
@@ -97,6 +758,13 @@ impl RewardNFT {
     }
 }
 
+/// Generate an unsaved reward of a new user for tests.
+#[cfg(test)]
+pub(crate) fn generate_reward(value: u128) -> RewardNFT {
+    let owner = User::new(Uuid::new_v4(), "test".to_string());
+    RewardNFT::new(owner, U256::from(value), random_u256())
+}
+
 #[async_trait::async_trait]
 impl Reward for RewardNFT {
     type Error = RewardError;
@@ -118,81 +786,213 @@ impl Reward for RewardNFT {
     }
 
     fn is_redeemed(&self) -> bool {
-        self.redeemed
+        self.status == RewardStatus::Redeemed
     }
 
     #[cfg(not(test))]
-    async fn mint(value: U256, wallet: &LocalWallet) -> Result<U256, Self::Error> {
-        use ethers::signers::Signer;
-
-        let token_id = random_u256();
-        let url = format!("{}/{}", REWARD_NFT_URL, token_id);
-
-        crate::core::chain::mint_nft_reward(wallet.address(), token_id, url, value)
+    async fn mint(&self, to: Address) -> Result<String, Self::Error> {
+        crate::core::chain::mint_nft_reward(to, self.token_id, self.token_uri(), self.value)
             .await
-            .map_err(|_| RewardError::MintRewardError)?;
-
-        Ok(token_id)
+            .map_err(RewardError::from)
     }
 
     #[cfg(test)]
-    async fn mint(_value: U256, _wallet: &LocalWallet) -> Result<U256, Self::Error> {
-        Ok(random_u256())
+    async fn mint(&self, _to: Address) -> Result<String, Self::Error> {
+        Ok(format!("{:#x}", random_u256()))
     }
 
-    #[cfg(not(test))]
     async fn redeem(&mut self) -> Result<U256, Self::Error> {
-        if self.redeemed {
-            Err(RewardError::AlreadyRedeemed)
-        } else {
-            // Get the user from the repository
-            let _user = User::from_id(self.owner.to_string())
-                .await
-                .map_err(|_| RewardError::RepositoryError(RepositoryError::ConnectionError))?;
-            // Get the user's wallet
-            // TODO The user's wallet cannot cover the gas fee yet
-            // let wallet = user.get_wallet()?;
-            let wallet = crate::core::chain::get_admin_wallet()?;
+        self.check_redeemable()?;
 
-            crate::core::chain::burn_nft_reward(wallet, self.token_id)
-                .await
-                .map_err(|_| RewardError::MintRewardError)?;
+        // Record the burn before it is sent so it can be recovered
+        let mut entry = OutboxEntry::new(OutboxAction::Burn {
+            reward_id: self.id,
+            token_id: self.token_id,
+        });
+        entry.save().await?;
+        entry.process().await?;
 
-            self.redeemed = true;
+        self.set_redeemed(true);
 
-            // Save the reward state to the repository
-            self.save(false)
-                .await
-                .map_err(|_| RewardError::RepositoryError(RepositoryError::UpdateError))?;
+        Ok(self.value)
+    }
+}
 
-            Ok(self.value)
+/// RewardNFTV0 is the layout rewards were stored with before the layout
+/// version was recorded.
+#[derive(Deserialize)]
+struct RewardNFTV0 {
+    id: Uuid,
+    owner: Uuid,
+    token_id: U256,
+    value: U256,
+    url: String,
+    redeemed: bool,
+}
+
+impl From<RewardNFTV0> for RewardNFT {
+    fn from(v0: RewardNFTV0) -> Self {
+        let (status, redeemed_value) = match v0.redeemed {
+            true => (RewardStatus::Redeemed, v0.value),
+            false => (RewardStatus::Active, U256::zero()),
+        };
+
+        Self {
+            id: v0.id,
+            owner: v0.owner,
+            token_id: v0.token_id,
+            value: v0.value,
+            url: v0.url,
+            status,
+            holder: None,
+            campaign_id: None,
+            expires_at: None,
+            revocation: None,
+            transfers: Vec::new(),
+            parent_id: None,
+            children: Vec::new(),
+            redeemed_value,
         }
     }
+}
 
-    #[cfg(test)]
-    async fn redeem(&mut self) -> Result<U256, Self::Error> {
-        self.redeemed = true;
+impl SledModel for RewardNFT {
+    fn migrate(version: u8, data: &[u8]) -> Result<Self, RepositoryError> {
+        match version {
+            0 => bincode::deserialize::<RewardNFTV0>(data)
+                .map(Self::from)
+                .map_err(|_| RepositoryError::ReadError),
+            _ => Err(RepositoryError::ReadError),
+        }
+    }
+}
 
-        // Save the reward state to the repository
-        self.save(false)
-            .await
-            .map_err(|_| RewardError::RepositoryError(RepositoryError::UpdateError))?;
+/// Get the most rewards a reward can be split into, which is the most
+/// rewards accepted in one batch, since each one is minted.
+pub fn max_split_parts() -> usize {
+    parse_env("REWARD_BATCH_MAX_SIZE", 100)
+}
 
-        Ok(self.value)
+/// Simulate minting a reward to an address.
+#[cfg(not(test))]
+async fn simulate_mint(reward: &RewardNFT, to: Address) -> Result<Simulation, RewardError> {
+    Ok(crate::core::chain::simulate_mint_nft_reward(
+        to,
+        reward.token_id,
+        reward.token_uri(),
+        reward.value,
+    )
+    .await?)
+}
+
+#[cfg(test)]
+async fn simulate_mint(_reward: &RewardNFT, _to: Address) -> Result<Simulation, RewardError> {
+    Ok(Simulation {
+        gas_estimate: U256::from(150_000),
+    })
+}
+
+/// Simulate burning the token of a reward from the wallet that would send
+/// the burn in the configured redeem mode.
+#[cfg(not(test))]
+async fn simulate_burn(reward: &RewardNFT) -> Result<Simulation, RewardError> {
+    use super::authorization::{authorize_burn, RedeemMode};
+
+    match RedeemMode::from_env() {
+        RedeemMode::Owner => {
+            let wallet = reward.fetch_owner().await?.get_wallet()?;
+
+            Ok(crate::core::chain::simulate_burn_nft_reward(wallet, reward.token_id).await?)
+        }
+        RedeemMode::Relayer => {
+            let owner = reward.fetch_owner().await?;
+            let (authorization, signature) = authorize_burn(&owner, reward.token_id).await?;
+
+            Ok(
+                crate::core::chain::simulate_relay_burn_nft_reward(&authorization, &signature)
+                    .await?,
+            )
+        }
     }
 }
 
-impl SledModel for RewardNFT {}
+#[cfg(test)]
+async fn simulate_burn(_reward: &RewardNFT) -> Result<Simulation, RewardError> {
+    Ok(Simulation {
+        gas_estimate: U256::from(80_000),
+    })
+}
+
+/// Simulate splitting the token of a reward from the wallet that would send
+/// the split in the configured redeem mode.
+#[cfg(not(test))]
+async fn simulate_split(
+    reward: &RewardNFT,
+    redeemed: U256,
+    children: &[RewardNFT],
+) -> Result<Simulation, RewardError> {
+    use super::authorization::RedeemMode;
+
+    let wallet = match RedeemMode::from_env() {
+        RedeemMode::Owner => reward.fetch_owner().await?.get_wallet()?,
+        RedeemMode::Relayer => crate::core::chain::get_admin_wallet()?,
+    };
+    let tokens: Vec<SplitToken> = children.iter().map(RewardNFT::split_token).collect();
+
+    Ok(
+        crate::core::chain::simulate_split_nft_reward(wallet, reward.token_id, redeemed, &tokens)
+            .await?,
+    )
+}
 
 #[cfg(test)]
-mod tests {
-    use super::*;
+async fn simulate_split(
+    _reward: &RewardNFT,
+    _redeemed: U256,
+    _children: &[RewardNFT],
+) -> Result<Simulation, RewardError> {
+    Ok(Simulation {
+        gas_estimate: U256::from(200_000),
+    })
+}
+
+/// Get the rewards stored in the repository. Rewards are stored under their
+/// id alongside the users, so a record under an id is only taken for a reward
+/// if it reads as a reward with that id.
+fn stored_rewards(db: &SledRepository) -> Result<Vec<RewardNFT>, RepositoryError> {
+    let mut rewards = Vec::new();
+
+    for key in db.keys("")? {
+        if Uuid::parse_str(&key).is_err() {
+            continue;
+        }
 
-    fn generate_reward(value: u128) -> RewardNFT {
-        let owner = User::new(Uuid::new_v4(), "test".to_string());
-        RewardNFT::new(owner, U256::from(value), random_u256())
+        let reward: Result<Option<RewardNFT>, RepositoryError> = db.read(key.clone());
+
+        if let Ok(Some(reward)) = reward {
+            if reward.id.to_string() == key {
+                rewards.push(reward);
+            }
+        }
     }
 
+    Ok(rewards)
+}
+
+/// The prefix of the token index entries.
+const TOKEN_KEY_PREFIX: &str = "token:";
+
+/// Get the repository key of the token index entry for a token id.
+fn token_key(token_id: &U256) -> String {
+    format!("{}{:#x}", TOKEN_KEY_PREFIX, token_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::chain::generate_secret_key;
+
+    use super::*;
+
     #[test]
     fn test_new() {
         let reward = generate_reward(100);
@@ -202,6 +1002,70 @@ mod tests {
         assert_eq!(reward.url, format!("{}/{}", REWARD_NFT_URL, reward.id));
     }
 
+    #[test]
+    fn test_migrate() {
+        #[derive(Serialize)]
+        struct V0 {
+            id: Uuid,
+            owner: Uuid,
+            token_id: U256,
+            value: U256,
+            url: String,
+            redeemed: bool,
+        }
+
+        let v0 = V0 {
+            id: Uuid::new_v4(),
+            owner: Uuid::new_v4(),
+            token_id: random_u256(),
+            value: U256::from(100),
+            url: "url".to_string(),
+            redeemed: true,
+        };
+
+        // Rewards stored before the layout version was recorded are migrated
+        let reward = RewardNFT::from_vec(bincode::serialize(&v0).unwrap()).unwrap();
+
+        assert_eq!(reward.id, v0.id);
+        assert_eq!(reward.owner, v0.owner);
+        assert_eq!(reward.token_id, v0.token_id);
+        assert_eq!(reward.get_status(), RewardStatus::Redeemed);
+        assert_eq!(reward.get_redeemed_value(), U256::from(100));
+
+        // Current records are read back unchanged
+        let reward = generate_reward(100);
+
+        let decoded = RewardNFT::from_vec(reward.to_vec()).unwrap();
+
+        assert_eq!(decoded.to_vec(), reward.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_backfill_index() {
+        // A reward stored before the token index was written
+        let reward = generate_reward(100);
+        get_sled_db()
+            .unwrap()
+            .write()
+            .unwrap()
+            .create(reward.id.to_string(), reward.clone())
+            .unwrap();
+        assert!(RewardNFT::from_token_id(reward.token_id).await.is_err());
+
+        // It is listed before it is indexed
+        assert!(RewardNFT::all()
+            .await
+            .unwrap()
+            .iter()
+            .any(|stored| stored.id == reward.id));
+
+        assert!(RewardNFT::backfill_index().await.unwrap() >= 1);
+        assert_eq!(
+            RewardNFT::from_token_id(reward.token_id).await.unwrap().id,
+            reward.id
+        );
+    }
+
     #[test]
     fn test_get_value() {
         let reward = generate_reward(100);
@@ -219,6 +1083,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_token_uri() {
+        let reward = generate_reward(100);
+
+        assert_eq!(
+            reward.token_uri(),
+            format!("{}/{}", REWARD_NFT_URL, reward.token_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_issue_batch() {
+        let owners = [
+            User::new(Uuid::new_v4(), generate_secret_key()),
+            User::new(Uuid::new_v4(), generate_secret_key()),
+        ];
+        let items = owners
+            .iter()
+            .zip([10u64, 20])
+            .map(|(owner, value)| (owner.clone(), U256::from(value), IssueOptions::default()))
+            .collect();
+
+        let rewards = RewardNFT::issue_batch(items).await;
+
+        // Every reward is issued to its owner and saved
+        assert_eq!(rewards.len(), 2);
+
+        for (reward, (owner, value)) in rewards.into_iter().zip(owners.iter().zip([10u64, 20])) {
+            let reward = reward.unwrap();
+
+            assert_eq!(reward.get_owner(), owner.id);
+            assert_eq!(reward.get_value(), U256::from(value));
+            assert!(RewardNFT::from_id(reward.get_id()).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulate_redeem() {
+        let mut reward = generate_reward(100);
+
+        assert!(reward.simulate_redeem().await.is_ok());
+
+        // Redeemed rewards cannot be simulated
+        reward.set_redeemed(true);
+        assert!(matches!(
+            reward.simulate_redeem().await,
+            Err(RewardError::AlreadyRedeemed)
+        ));
+    }
+
     #[tokio::test]
     async fn test_save() {
         let reward = generate_reward(100);
@@ -263,6 +1177,54 @@ mod tests {
         assert_eq!(result.url, reward.url);
     }
 
+    #[tokio::test]
+    async fn test_from_token_id() {
+        let reward = generate_reward(100);
+
+        // An unknown token should return an error
+        assert!(RewardNFT::from_token_id(reward.token_id).await.is_err());
+
+        // Save the reward so the token is indexed
+        assert!(reward.save(true).await.is_ok());
+
+        let result = RewardNFT::from_token_id(reward.token_id).await.unwrap();
+
+        // Check that the reward is the same
+        assert_eq!(result.id, reward.id);
+    }
+
+    #[tokio::test]
+    async fn test_all() {
+        let reward = generate_reward(100);
+
+        // Save the reward
+        assert!(reward.save(true).await.is_ok());
+
+        let rewards = RewardNFT::all().await.unwrap();
+
+        // The saved reward is included
+        assert!(rewards.iter().any(|r| r.id == reward.id));
+    }
+
+    #[test]
+    fn test_transfer_to() {
+        let mut reward = generate_reward(100);
+        let owner = reward.owner;
+        let holder = Address::random();
+
+        // Transferring to an unknown holder keeps the owner
+        reward.transfer_to(holder, None);
+
+        assert_eq!(reward.get_holder(), Some(holder));
+        assert_eq!(reward.get_owner(), owner);
+
+        // Transferring to a registered user changes the owner
+        let new_owner = Uuid::new_v4();
+        reward.transfer_to(holder, Some(new_owner));
+
+        assert_eq!(reward.get_owner(), new_owner);
+    }
+
     #[tokio::test]
     async fn test_redeem() {
         let mut reward = generate_reward(100);
@@ -275,7 +1237,180 @@ mod tests {
         // Check that the reward was redeemed
         assert_eq!(value, reward.value);
         // Check that the reward is redeemed
-        assert!(reward.redeemed);
+        assert_eq!(reward.status, RewardStatus::Redeemed);
         assert!(reward.is_redeemed());
     }
+
+    #[test]
+    fn test_is_expired() {
+        let mut reward = generate_reward(100);
+
+        // Rewards without an expiry never expire
+        assert!(!reward.is_expired(u64::MAX));
+
+        reward = reward.with_expiry(Some(100));
+
+        assert!(!reward.is_expired(99));
+        assert!(reward.is_expired(100));
+
+        // Redeemed rewards no longer expire
+        reward.set_redeemed(true);
+        assert!(!reward.is_expired(100));
+    }
+
+    #[tokio::test]
+    async fn test_redeem_expired() {
+        let mut reward = generate_reward(100).with_expiry(Some(now() - 1));
+
+        // Expired rewards cannot be redeemed or simulated
+        assert!(matches!(reward.redeem().await, Err(RewardError::Expired)));
+        assert!(matches!(
+            reward.simulate_redeem().await,
+            Err(RewardError::Expired)
+        ));
+
+        // Nor once their token has been burned
+        reward.set_status(RewardStatus::Expired);
+        assert!(matches!(reward.redeem().await, Err(RewardError::Expired)));
+    }
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let from = User::new(Uuid::new_v4(), generate_secret_key());
+        let to = User::new(Uuid::new_v4(), generate_secret_key());
+        let mut reward = RewardNFT::new(from.clone(), U256::from(100), random_u256());
+
+        assert!(reward.save(true).await.is_ok());
+
+        // A reward cannot be transferred to its owner
+        assert!(matches!(
+            reward.transfer(&from).await,
+            Err(RewardError::SameOwner)
+        ));
+
+        let transfer = reward.transfer(&to).await.unwrap();
+
+        assert_eq!(transfer.from, from.id);
+        assert_eq!(transfer.to, to.id);
+        assert!(transfer.tx_hash.is_some());
+
+        // The new owner and the transfer are saved
+        let saved = RewardNFT::from_id(reward.get_id()).await.unwrap();
+
+        assert_eq!(saved.get_owner(), to.id);
+        assert_eq!(saved.get_holder(), Some(to.get_wallet().unwrap().address()));
+        assert_eq!(saved.get_transfers(), &[transfer]);
+
+        // Redeemed rewards cannot be transferred
+        reward.set_redeemed(true);
+        assert!(matches!(
+            reward.transfer(&from).await,
+            Err(RewardError::AlreadyRedeemed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_redeem_partial() {
+        let mut reward = generate_reward(100).with_expiry(Some(now() + 60));
+
+        assert!(reward.save(true).await.is_ok());
+
+        // Nothing or everything cannot be redeemed in part
+        for value in [0u64, 100] {
+            assert!(matches!(
+                reward.redeem_partial(U256::from(value)).await,
+                Err(RewardError::InvalidValue(_))
+            ));
+        }
+
+        assert!(reward.simulate_redeem_partial(U256::from(40)).await.is_ok());
+
+        let child = reward.redeem_partial(U256::from(40)).await.unwrap();
+
+        // The rest of the value is carried over to a child reward
+        assert_eq!(child.get_value(), U256::from(60));
+        assert_eq!(child.get_parent_id(), Some(reward.id));
+        assert_eq!(child.get_owner(), reward.owner);
+        assert_eq!(child.get_expires_at(), reward.expires_at);
+        assert_eq!(child.get_status(), RewardStatus::Active);
+
+        // The parent records the redeemed value and its child
+        assert_eq!(reward.get_status(), RewardStatus::Split);
+        assert_eq!(reward.get_redeemed_value(), U256::from(40));
+        assert_eq!(reward.get_children(), &[child.id]);
+        assert!(matches!(
+            reward.redeem().await,
+            Err(RewardError::AlreadyRedeemed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_split() {
+        let mut reward = generate_reward(100);
+
+        assert!(reward.save(true).await.is_ok());
+
+        // The values must add up to the value of the reward
+        assert!(matches!(
+            reward.split(vec![U256::from(50), U256::from(40)]).await,
+            Err(RewardError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            reward.split(vec![U256::from(100)]).await,
+            Err(RewardError::InvalidValue(_))
+        ));
+
+        // A reward cannot be split into more rewards than a batch holds
+        let parts = max_split_parts() + 1;
+        let mut large = generate_reward(parts as u128);
+
+        assert!(large.save(true).await.is_ok());
+        assert!(matches!(
+            large.split(vec![U256::one(); parts]).await,
+            Err(RewardError::InvalidValue(_))
+        ));
+
+        let children = reward
+            .split(vec![U256::from(50), U256::from(30), U256::from(20)])
+            .await
+            .unwrap();
+
+        assert_eq!(children.len(), 3);
+        assert_eq!(reward.get_redeemed_value(), U256::zero());
+        assert_eq!(
+            reward.get_children(),
+            children.iter().map(|c| c.id).collect::<Vec<_>>()
+        );
+        assert!(children
+            .iter()
+            .all(|c| c.get_parent_id() == Some(reward.id)));
+    }
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let mut reward = generate_reward(100);
+
+        assert!(reward.save(true).await.is_ok());
+
+        reward
+            .revoke("Issued twice".into(), "support".into())
+            .await
+            .unwrap();
+
+        // The revocation is recorded on the saved reward
+        let saved = RewardNFT::from_id(reward.get_id()).await.unwrap();
+        let revocation = saved.get_revocation().unwrap();
+
+        assert_eq!(saved.get_status(), RewardStatus::Revoked);
+        assert!(!saved.is_redeemed());
+        assert_eq!(revocation.reason, "Issued twice");
+        assert_eq!(revocation.actor, "support");
+
+        // Revoked rewards can be neither redeemed nor revoked again
+        assert!(matches!(reward.redeem().await, Err(RewardError::Revoked)));
+        assert!(matches!(
+            reward.revoke("Again".into(), "support".into()).await,
+            Err(RewardError::Revoked)
+        ));
+    }
 }
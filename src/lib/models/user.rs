@@ -1,11 +1,12 @@
 use ethers::signers::{LocalWallet, Signer};
-use ethers::types::U256;
+use ethers::types::{Address, Signature, U256};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 use zeroize::ZeroizeOnDrop;
 
-use crate::core::chain::get_wallet_from_secret_key;
+use crate::core::chain::{get_wallet_from_secret_key, ChainError};
+use crate::core::eip712::BurnAuthorization;
 use crate::core::repository::{Repository, RepositoryError};
 use crate::storage::sled::{get_sled_db, SledModel};
 
@@ -19,6 +20,8 @@ pub enum UserError {
     RepositoryError(#[from] RepositoryError),
     #[error("Unknown error")]
     UnknownError(#[from] std::io::Error),
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
 }
 
 /// User is a struct that contains the user's id and address.
@@ -52,6 +55,25 @@ impl User {
         }
     }
 
+    /// Look up a user by the address of their custodial wallet.
+    pub async fn from_address(address: Address) -> Result<Self, UserError> {
+        let id = {
+            let connection = get_sled_db()?;
+            let db = connection
+                .read()
+                .map_err(|_| UserError::RepositoryError(RepositoryError::ConnectionError))?;
+            let id: Result<Option<Uuid>, RepositoryError> = db.read(address_key(&address));
+
+            match id {
+                Ok(Some(id)) => id,
+                Ok(None) => return Err(UserError::NotFound),
+                Err(e) => return Err(UserError::RepositoryError(e)),
+            }
+        };
+
+        Self::from_id(id.to_string()).await
+    }
+
     /// Save the user to the repository.
     pub async fn save(&self) -> Result<(), UserError> {
         let connection = get_sled_db()?;
@@ -67,16 +89,68 @@ impl User {
         } else {
             db.create(self.id.to_string(), self.clone())
                 .map_err(|_| UserError::RepositoryError(RepositoryError::InsertionError))?;
+            // Index the user by wallet address so on-chain events can be
+            // matched back to the user
+            db.create(address_key(&self.get_wallet()?.address()), self.id)
+                .map_err(|_| UserError::RepositoryError(RepositoryError::InsertionError))?;
 
             Ok(())
         }
     }
 
+    /// Index the users stored without an address index entry, such as the
+    /// ones stored before the index was written, so they can be looked up by
+    /// address. Returns how many users were indexed.
+    pub async fn backfill_index() -> Result<usize, UserError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| UserError::RepositoryError(RepositoryError::ConnectionError))?;
+        let mut indexed = 0;
+
+        for key in db.keys("")? {
+            if Uuid::parse_str(&key).is_err() {
+                continue;
+            }
+
+            // Users are stored under their id alongside the rewards, so a
+            // record is only taken for a user if it has a valid wallet
+            let user: Result<Option<User>, RepositoryError> = db.read(key.clone());
+            let (user, address) = match user {
+                Ok(Some(user)) if user.id.to_string() == key => match user.get_wallet() {
+                    Ok(wallet) => (user, wallet.address()),
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            let id: Option<Uuid> = db.read(address_key(&address))?;
+
+            if id.is_none() {
+                db.create(address_key(&address), user.id)?;
+                indexed += 1;
+            }
+        }
+
+        Ok(indexed)
+    }
+
     /// Get the wallet of the user from the private key.
     pub fn get_wallet(&self) -> Result<LocalWallet, UserError> {
         Ok(get_wallet_from_secret_key(&self.key)?)
     }
 
+    /// Sign an authorization for the relayer to burn one of the user's
+    /// tokens.
+    pub async fn sign_burn_authorization(
+        &self,
+        authorization: &BurnAuthorization,
+    ) -> Result<Signature, UserError> {
+        self.get_wallet()?
+            .sign_typed_data(authorization)
+            .await
+            .map_err(|e| UserError::UnknownError(std::io::Error::other(e)))
+    }
+
     /// Get the reward balance of the user.
     #[cfg(not(test))]
     pub async fn get_reward_balance(&self) -> Result<U256, UserError> {
@@ -95,12 +169,19 @@ impl User {
 
 impl SledModel for User {}
 
+/// Get the repository key of the address index entry for a wallet address.
+fn address_key(address: &Address) -> String {
+    format!("address:{:#x}", address)
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::BorrowMut;
 
     use uuid::Uuid;
 
+    use crate::core::chain::generate_secret_key;
+
     use super::*;
 
     const PRIVATE_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
@@ -152,6 +233,55 @@ mod tests {
         assert!(User::from_id("user2".to_string()).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_from_address() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        let address = user.get_wallet().unwrap().address();
+
+        // An unknown address should return an error
+        assert!(User::from_address(address).await.is_err());
+
+        // Save the user so the address is indexed
+        user.save().await.unwrap();
+
+        // Get the user by address
+        let user_from_address = User::from_address(address).await.unwrap();
+
+        // Check that the user data is correct
+        assert_eq!(user_from_address.id, user.id);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_index() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        let address = user.get_wallet().unwrap().address();
+
+        // A user stored before the address index was written
+        get_sled_db()
+            .unwrap()
+            .write()
+            .unwrap()
+            .create(user.id.to_string(), user.clone())
+            .unwrap();
+        assert!(User::from_address(address).await.is_err());
+
+        assert!(User::backfill_index().await.unwrap() >= 1);
+        assert_eq!(User::from_address(address).await.unwrap().id, user.id);
+
+        // Indexed users are left as they are
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+        User::backfill_index().await.unwrap();
+
+        assert_eq!(
+            User::from_address(user.get_wallet().unwrap().address())
+                .await
+                .unwrap()
+                .id,
+            user.id
+        );
+    }
+
     mod repository {
         use super::*;
 
@@ -0,0 +1,461 @@
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::core::repository::{Repository, RepositoryError};
+use crate::rewards::Reward;
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::helpers::now;
+
+use super::reward::RewardNFT;
+
+/// The prefix of the webhook subscriptions in the repository.
+const SUBSCRIPTION_KEY_PREFIX: &str = "webhook:subscription:";
+
+/// The prefix of the webhook deliveries in the repository.
+const DELIVERY_KEY_PREFIX: &str = "webhook:delivery:";
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("Webhook subscription not found")]
+    NotFound,
+    #[error("Invalid webhook subscription: {0}")]
+    Invalid(String),
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+}
+
+/// WebhookEvent is a reward lifecycle event subscriptions are notified of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEvent {
+    #[serde(rename = "reward.minted")]
+    RewardMinted,
+    #[serde(rename = "reward.redeemed")]
+    RewardRedeemed,
+    #[serde(rename = "reward.split")]
+    RewardSplit,
+    #[serde(rename = "reward.transferred")]
+    RewardTransferred,
+    #[serde(rename = "reward.expired")]
+    RewardExpired,
+    #[serde(rename = "reward.revoked")]
+    RewardRevoked,
+    #[serde(rename = "reward.failed")]
+    RewardFailed,
+}
+
+impl WebhookEvent {
+    /// Get the name of the event, as sent in payloads.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::RewardMinted => "reward.minted",
+            WebhookEvent::RewardRedeemed => "reward.redeemed",
+            WebhookEvent::RewardSplit => "reward.split",
+            WebhookEvent::RewardTransferred => "reward.transferred",
+            WebhookEvent::RewardExpired => "reward.expired",
+            WebhookEvent::RewardRevoked => "reward.revoked",
+            WebhookEvent::RewardFailed => "reward.failed",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reward.minted" => Ok(WebhookEvent::RewardMinted),
+            "reward.redeemed" => Ok(WebhookEvent::RewardRedeemed),
+            "reward.split" => Ok(WebhookEvent::RewardSplit),
+            "reward.transferred" => Ok(WebhookEvent::RewardTransferred),
+            "reward.expired" => Ok(WebhookEvent::RewardExpired),
+            "reward.revoked" => Ok(WebhookEvent::RewardRevoked),
+            "reward.failed" => Ok(WebhookEvent::RewardFailed),
+            _ => Err(format!("Unknown webhook event: {}", s)),
+        }
+    }
+}
+
+/// Subscription is an endpoint notified of reward events, with the secret
+/// its payloads are signed with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    /// The id of the subscription.
+    pub id: Uuid,
+    /// The URL payloads are posted to.
+    pub url: String,
+    /// The events the endpoint is notified of.
+    pub events: Vec<WebhookEvent>,
+    /// The secret payloads are signed with.
+    pub secret: String,
+    /// If payloads are sent to the endpoint.
+    pub active: bool,
+    /// When the subscription was created, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl Subscription {
+    /// Create a new active subscription.
+    pub fn new(
+        url: String,
+        events: Vec<WebhookEvent>,
+        secret: String,
+    ) -> Result<Self, WebhookError> {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(WebhookError::Invalid(
+                "The URL must use http or https".into(),
+            ));
+        }
+
+        if events.is_empty() {
+            return Err(WebhookError::Invalid(
+                "At least one event is required".into(),
+            ));
+        }
+
+        if secret.is_empty() {
+            return Err(WebhookError::Invalid("The secret must not be empty".into()));
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            url,
+            events,
+            secret,
+            active: true,
+            created_at: now(),
+        })
+    }
+
+    /// Look up a subscription by id from the repository.
+    pub async fn from_id(id: Uuid) -> Result<Self, WebhookError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+        let subscription: Option<Subscription> = db.read(subscription_key(&id))?;
+
+        subscription.ok_or(WebhookError::NotFound)
+    }
+
+    /// Get all subscriptions, oldest first.
+    pub async fn all() -> Result<Vec<Self>, WebhookError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+        let subscriptions: Vec<(String, Subscription)> =
+            db.scan(SUBSCRIPTION_KEY_PREFIX.to_string())?;
+        let mut subscriptions: Vec<Subscription> = subscriptions
+            .into_iter()
+            .map(|(_, subscription)| subscription)
+            .collect();
+
+        subscriptions.sort_by_key(|subscription| subscription.created_at);
+
+        Ok(subscriptions)
+    }
+
+    /// Save the subscription to the repository.
+    pub async fn save(&self) -> Result<(), WebhookError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(subscription_key(&self.id), self.clone())?)
+    }
+
+    /// Remove a subscription from the repository. Its deliveries are kept in
+    /// the log.
+    pub async fn delete(id: Uuid) -> Result<(), WebhookError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+        let deleted: Result<Subscription, RepositoryError> = db.delete(subscription_key(&id));
+
+        deleted.map(|_| ()).map_err(|_| WebhookError::NotFound)
+    }
+
+    /// If the endpoint is notified of an event.
+    pub fn is_subscribed(&self, event: WebhookEvent) -> bool {
+        self.active && self.events.contains(&event)
+    }
+
+    /// Sign a payload sent at a unix timestamp. The signature is the hex
+    /// HMAC-SHA256 of `{timestamp}.{payload}` keyed with the secret.
+    pub fn sign(&self, timestamp: u64, payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+impl SledModel for Subscription {}
+
+/// Get the repository key of a subscription.
+fn subscription_key(id: &Uuid) -> String {
+    format!("{}{}", SUBSCRIPTION_KEY_PREFIX, id)
+}
+
+/// DeliveryStatus is the progress of a delivery.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum DeliveryStatus {
+    /// The payload is waiting to be sent, or sent again.
+    Pending,
+    /// The endpoint accepted the payload.
+    Delivered,
+    /// The endpoint did not accept the payload after every attempt.
+    Failed,
+}
+
+/// Delivery is a payload sent to a subscription. Deliveries are kept after
+/// they finish, as a log of what was sent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Delivery {
+    /// The id of the delivery.
+    pub id: Uuid,
+    /// The subscription the payload is sent to.
+    pub subscription_id: Uuid,
+    /// The event of the payload.
+    pub event: WebhookEvent,
+    /// The JSON payload.
+    pub payload: String,
+    /// The progress of the delivery.
+    pub status: DeliveryStatus,
+    /// How many times the payload was sent.
+    pub attempts: u32,
+    /// When the payload is sent next, as a unix timestamp.
+    pub next_attempt_at: u64,
+    /// The HTTP status of the last response.
+    pub response_status: Option<u16>,
+    /// The last error encountered.
+    pub error: Option<String>,
+    /// When the delivery was created, as a unix timestamp.
+    pub created_at: u64,
+    /// When the delivery was last updated, as a unix timestamp.
+    pub updated_at: u64,
+}
+
+impl Delivery {
+    /// Create a pending delivery of an event to a subscription.
+    pub fn new(subscription_id: Uuid, event: WebhookEvent, data: serde_json::Value) -> Self {
+        let id = Uuid::new_v4();
+        let created_at = now();
+        let payload = serde_json::json!({
+            "id": id,
+            "event": event,
+            "created_at": created_at,
+            "data": data,
+        });
+
+        Self {
+            id,
+            subscription_id,
+            event,
+            payload: payload.to_string(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: created_at,
+            response_status: None,
+            error: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    /// Look up a delivery by id from the repository.
+    pub async fn from_id(id: Uuid) -> Result<Self, WebhookError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+        let delivery: Option<Delivery> = db.read(delivery_key(&id))?;
+
+        delivery.ok_or(WebhookError::NotFound)
+    }
+
+    /// Get all deliveries, oldest first.
+    pub async fn all() -> Result<Vec<Self>, WebhookError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+        let deliveries: Vec<(String, Delivery)> = db.scan(DELIVERY_KEY_PREFIX.to_string())?;
+        let mut deliveries: Vec<Delivery> = deliveries
+            .into_iter()
+            .map(|(_, delivery)| delivery)
+            .collect();
+
+        deliveries.sort_by_key(|delivery| delivery.created_at);
+
+        Ok(deliveries)
+    }
+
+    /// Get the deliveries to a subscription, oldest first.
+    pub async fn for_subscription(subscription_id: Uuid) -> Result<Vec<Self>, WebhookError> {
+        Ok(Self::all()
+            .await?
+            .into_iter()
+            .filter(|delivery| delivery.subscription_id == subscription_id)
+            .collect())
+    }
+
+    /// Get the pending deliveries due to be sent at a unix timestamp.
+    pub async fn due(at: u64) -> Result<Vec<Self>, WebhookError> {
+        Ok(Self::all()
+            .await?
+            .into_iter()
+            .filter(|delivery| {
+                delivery.status == DeliveryStatus::Pending && delivery.next_attempt_at <= at
+            })
+            .collect())
+    }
+
+    /// Save the delivery to the repository.
+    pub async fn save(&mut self) -> Result<(), WebhookError> {
+        self.updated_at = now();
+
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| WebhookError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(delivery_key(&self.id), self.clone())?)
+    }
+}
+
+impl SledModel for Delivery {}
+
+/// Get the repository key of a delivery.
+fn delivery_key(id: &Uuid) -> String {
+    format!("{}{}", DELIVERY_KEY_PREFIX, id)
+}
+
+/// Queue a delivery of an event to every subscription notified of it,
+/// returning the queued deliveries.
+pub async fn publish(
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<Vec<Delivery>, WebhookError> {
+    let mut deliveries = vec![];
+
+    for subscription in Subscription::all().await? {
+        if !subscription.is_subscribed(event) {
+            continue;
+        }
+
+        let mut delivery = Delivery::new(subscription.id, event, data.clone());
+        delivery.save().await?;
+        deliveries.push(delivery);
+    }
+
+    if !deliveries.is_empty() {
+        crate::workers::webhooks::notify_queued();
+    }
+
+    Ok(deliveries)
+}
+
+/// Get the data of a reward event payload.
+pub fn reward_data(reward: &RewardNFT, tx_hash: Option<String>) -> serde_json::Value {
+    serde_json::json!({
+        "reward_id": reward.get_id(),
+        "owner": reward.get_owner(),
+        "token_id": reward.get_token_id().to_string(),
+        "value": reward.get_value().to_string(),
+        "status": reward.get_status(),
+        "tx_hash": tx_hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(events: Vec<WebhookEvent>) -> Subscription {
+        Subscription::new("http://127.0.0.1:1/hook".into(), events, "secret".into()).unwrap()
+    }
+
+    #[test]
+    fn test_new_subscription() {
+        let events = vec![WebhookEvent::RewardMinted];
+
+        assert!(Subscription::new("ftp://example.com".into(), events.clone(), "s".into()).is_err());
+        assert!(Subscription::new("https://example.com".into(), vec![], "s".into()).is_err());
+        assert!(Subscription::new("https://example.com".into(), events, "".into()).is_err());
+    }
+
+    #[test]
+    fn test_parse_event() {
+        for event in [WebhookEvent::RewardMinted, WebhookEvent::RewardFailed] {
+            assert_eq!(event.name().parse::<WebhookEvent>(), Ok(event));
+            assert_eq!(
+                serde_json::to_value(event).unwrap(),
+                serde_json::json!(event.name())
+            );
+        }
+
+        assert!("reward.unknown".parse::<WebhookEvent>().is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        let subscription = subscription(vec![WebhookEvent::RewardMinted]);
+
+        // HMAC-SHA256 of "1700000000.{}" keyed with "secret"
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(b"1700000000.{}");
+        let expected = hex::encode(mac.finalize().into_bytes());
+
+        assert_eq!(subscription.sign(1_700_000_000, "{}"), expected);
+        assert_ne!(subscription.sign(1_700_000_001, "{}"), expected);
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let minted = subscription(vec![WebhookEvent::RewardMinted]);
+        let mut paused = subscription(vec![WebhookEvent::RewardMinted]);
+        paused.active = false;
+        let redeemed = subscription(vec![WebhookEvent::RewardRedeemed]);
+
+        for subscription in [&minted, &paused, &redeemed] {
+            subscription.save().await.unwrap();
+        }
+
+        let deliveries = publish(WebhookEvent::RewardMinted, serde_json::json!({}))
+            .await
+            .unwrap();
+        let subscriptions: Vec<Uuid> = deliveries.iter().map(|d| d.subscription_id).collect();
+
+        // Only active subscriptions to the event are notified
+        assert!(subscriptions.contains(&minted.id));
+        assert!(!subscriptions.contains(&paused.id));
+        assert!(!subscriptions.contains(&redeemed.id));
+
+        // The deliveries are queued
+        let queued = Delivery::from_id(deliveries[0].id).await.unwrap();
+
+        assert_eq!(queued.status, DeliveryStatus::Pending);
+        assert_eq!(queued.event, WebhookEvent::RewardMinted);
+
+        for subscription in [minted, paused, redeemed] {
+            Subscription::delete(subscription.id).await.unwrap();
+        }
+    }
+}
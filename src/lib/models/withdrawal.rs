@@ -0,0 +1,448 @@
+use std::str::FromStr;
+
+use ethers::signers::Signer;
+use ethers::types::{Address, U256};
+use ethers::utils::to_checksum;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::core::amount::Amount;
+use crate::core::chain::ChainError;
+use crate::core::repository::{Repository, RepositoryError};
+use crate::core::reward::RewardError;
+use crate::storage::sled::{get_sled_db, SledModel};
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+use super::funding::FundingError;
+use super::outbox::{OutboxAction, OutboxEntry};
+use super::user::{User, UserError};
+
+/// The prefix of the withdrawal records in the repository.
+const WITHDRAWAL_KEY_PREFIX: &str = "withdrawal:";
+
+lazy_static! {
+    /// Held from the balance check of a withdrawal until it is recorded, so
+    /// concurrent withdrawals cannot spend the same balance.
+    static ref WITHDRAW_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, Error)]
+pub enum WithdrawalError {
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Withdrawal amount below the minimum")]
+    BelowMinimum { amount: U256, minimum: U256 },
+    #[error("Withdrawal amount above the maximum")]
+    AboveMaximum { amount: U256, maximum: U256 },
+    #[error("Insufficient balance")]
+    InsufficientBalance { requested: U256, available: U256 },
+    #[error("Repository error")]
+    RepositoryError(#[from] RepositoryError),
+    #[error("User error")]
+    UserError(#[from] UserError),
+    #[error("Funding error")]
+    FundingError(#[from] FundingError),
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
+    #[error("Reward error")]
+    RewardError(#[from] RewardError),
+}
+
+impl From<WithdrawalError> for RewardError {
+    fn from(error: WithdrawalError) -> Self {
+        match error {
+            WithdrawalError::RepositoryError(e) => RewardError::RepositoryError(e),
+            WithdrawalError::UserError(e) => RewardError::UserError(e),
+            WithdrawalError::FundingError(e) => RewardError::FundingError(e),
+            WithdrawalError::ChainError(e) => RewardError::ChainError(e),
+            WithdrawalError::RewardError(e) => e,
+            e => RewardError::InvalidValue(e.to_string()),
+        }
+    }
+}
+
+/// WithdrawalConfig limits how many reward tokens can be withdrawn at once.
+#[derive(Clone, Debug)]
+pub struct WithdrawalConfig {
+    /// The least that can be withdrawn, in the smallest unit of the token.
+    pub minimum: U256,
+    /// The most that can be withdrawn, in the smallest unit of the token.
+    pub maximum: U256,
+}
+
+impl WithdrawalConfig {
+    /// Read the withdrawal limits from the environment. The limits are set
+    /// in whole tokens, such as `0.5`, and converted with the decimals of the
+    /// token.
+    pub fn from_env(decimals: u8) -> Self {
+        Self {
+            minimum: parse_limit("WITHDRAW_MIN_AMOUNT", "1", decimals),
+            maximum: parse_limit("WITHDRAW_MAX_AMOUNT", "1000000", decimals),
+        }
+    }
+
+    /// Check that an amount is within the limits.
+    pub fn check_amount(&self, amount: U256) -> Result<(), WithdrawalError> {
+        if amount < self.minimum {
+            return Err(WithdrawalError::BelowMinimum {
+                amount,
+                minimum: self.minimum,
+            });
+        }
+
+        if amount > self.maximum {
+            return Err(WithdrawalError::AboveMaximum {
+                amount,
+                maximum: self.maximum,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a withdrawal limit in whole tokens from the environment, using the
+/// default if it is not set or is not a valid amount.
+fn parse_limit(name: &str, default: &str, decimals: u8) -> U256 {
+    let limit = |value: String| Amount::Decimal(value).to_units(decimals).ok();
+
+    limit(parse_env(name, default.to_string()))
+        .or_else(|| limit(default.to_string()))
+        .unwrap_or_default()
+}
+
+/// WithdrawalStatus is the progress of a withdrawal.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum WithdrawalStatus {
+    /// The transfer has not been mined yet.
+    Pending,
+    /// The transfer was mined.
+    Confirmed,
+    /// The transfer reverted or could not be sent.
+    Failed,
+}
+
+/// Withdrawal is a record of reward tokens sent from a user's custodial
+/// wallet to an external address.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Withdrawal {
+    /// The id of the withdrawal.
+    pub id: Uuid,
+    /// The user that made the withdrawal.
+    pub user_id: Uuid,
+    /// The custodial wallet the tokens were sent from.
+    pub from: Address,
+    /// The address the tokens were sent to.
+    pub to: Address,
+    /// The amount of reward tokens sent.
+    pub amount: U256,
+    /// The progress of the withdrawal.
+    pub status: WithdrawalStatus,
+    /// The hash of the transaction, once mined.
+    pub tx_hash: Option<String>,
+    /// The error if the transaction reverted or could not be sent.
+    pub error: Option<String>,
+    /// When the withdrawal was made, as a unix timestamp.
+    pub created_at: u64,
+}
+
+impl Withdrawal {
+    /// Create a new withdrawal record.
+    pub fn new(user_id: Uuid, from: Address, to: Address, amount: U256) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            from,
+            to,
+            amount,
+            status: WithdrawalStatus::Pending,
+            tx_hash: None,
+            error: None,
+            created_at: now(),
+        }
+    }
+
+    /// Look up a withdrawal of a user from the repository.
+    pub async fn from_id(user_id: Uuid, id: Uuid) -> Result<Self, WithdrawalError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| WithdrawalError::RepositoryError(RepositoryError::ConnectionError))?;
+        let withdrawal: Option<Withdrawal> = db.read(withdrawal_key(&user_id, &id))?;
+
+        withdrawal.ok_or(WithdrawalError::RepositoryError(RepositoryError::ReadError))
+    }
+
+    /// Get all withdrawals of a user, oldest first.
+    pub async fn for_user(user_id: Uuid) -> Result<Vec<Self>, WithdrawalError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .read()
+            .map_err(|_| WithdrawalError::RepositoryError(RepositoryError::ConnectionError))?;
+        let withdrawals: Vec<(String, Withdrawal)> =
+            db.scan(format!("{}{}:", WITHDRAWAL_KEY_PREFIX, user_id))?;
+        let mut withdrawals: Vec<Withdrawal> = withdrawals
+            .into_iter()
+            .map(|(_, withdrawal)| withdrawal)
+            .collect();
+
+        withdrawals.sort_by_key(|withdrawal| withdrawal.created_at);
+
+        Ok(withdrawals)
+    }
+
+    /// Save the withdrawal to the repository.
+    pub async fn save(&self) -> Result<(), WithdrawalError> {
+        let connection = get_sled_db()?;
+        let db = connection
+            .write()
+            .map_err(|_| WithdrawalError::RepositoryError(RepositoryError::ConnectionError))?;
+
+        Ok(db.update(withdrawal_key(&self.user_id, &self.id), self.clone())?)
+    }
+}
+
+impl SledModel for Withdrawal {}
+
+/// Get the repository key of a withdrawal.
+fn withdrawal_key(user_id: &Uuid, id: &Uuid) -> String {
+    format!("{}{}:{}", WITHDRAWAL_KEY_PREFIX, user_id, id)
+}
+
+/// Parse an external address. Addresses must be `0x` prefixed, and mixed
+/// case addresses must have a valid EIP-55 checksum.
+pub fn parse_address(address: &str) -> Result<Address, WithdrawalError> {
+    let invalid = || WithdrawalError::InvalidAddress(address.to_string());
+    let hex = address.strip_prefix("0x").ok_or_else(invalid)?;
+
+    if hex.len() != 40 {
+        return Err(invalid());
+    }
+
+    let parsed = Address::from_str(hex).map_err(|_| invalid())?;
+    let mixed_case =
+        hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+
+    if mixed_case && to_checksum(&parsed, None) != address {
+        return Err(invalid());
+    }
+
+    if parsed.is_zero() {
+        return Err(invalid());
+    }
+
+    Ok(parsed)
+}
+
+/// Send reward tokens from a user's custodial wallet to an external address.
+/// The withdrawal is recorded with its transfer in the outbox before it is
+/// sent, and is confirmed once the transfer is mined. If the transfer could
+/// not be sent yet, the withdrawal is returned pending. Tokens of withdrawals
+/// that are still pending do not count towards the balance.
+pub async fn withdraw(
+    user: &User,
+    to: Address,
+    amount: U256,
+    config: &WithdrawalConfig,
+) -> Result<Withdrawal, WithdrawalError> {
+    config.check_amount(amount)?;
+
+    let from = user.get_wallet()?.address();
+
+    // Sending to the custodial wallet itself would only burn gas
+    if to == from {
+        return Err(WithdrawalError::InvalidAddress(format!("{:#x}", to)));
+    }
+
+    let withdrawal = Withdrawal::new(user.id, from, to, amount);
+    let mut entry = OutboxEntry::new(OutboxAction::Withdraw {
+        withdrawal_id: withdrawal.id,
+        user_id: user.id,
+        to,
+        amount,
+    });
+
+    {
+        let _lock = WITHDRAW_LOCK.lock().await;
+
+        let pending: U256 = Withdrawal::for_user(user.id)
+            .await?
+            .iter()
+            .filter(|withdrawal| withdrawal.status == WithdrawalStatus::Pending)
+            .fold(U256::zero(), |total, withdrawal| {
+                total.saturating_add(withdrawal.amount)
+            });
+        let available = user.get_reward_balance().await?.saturating_sub(pending);
+
+        if amount > available {
+            return Err(WithdrawalError::InsufficientBalance {
+                requested: amount,
+                available,
+            });
+        }
+
+        withdrawal.save().await?;
+        entry.save().await?;
+    }
+
+    match entry.process().await {
+        // The outbox worker sends the transfer later, so the withdrawal stays
+        // pending until then
+        Ok(()) | Err(RewardError::Queued(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Withdrawal::from_id(user.id, withdrawal.id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::chain::generate_secret_key;
+    use crate::models::activity::{Activity, ActivityKind};
+
+    use super::*;
+
+    const ADDRESS: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    fn config() -> WithdrawalConfig {
+        WithdrawalConfig {
+            minimum: U256::from(10),
+            maximum: U256::from(80),
+        }
+    }
+
+    #[test]
+    fn test_check_amount() {
+        let config = config();
+
+        assert!(config.check_amount(U256::from(10)).is_ok());
+        assert!(config.check_amount(U256::from(80)).is_ok());
+        assert!(matches!(
+            config.check_amount(U256::from(9)),
+            Err(WithdrawalError::BelowMinimum { .. })
+        ));
+        assert!(matches!(
+            config.check_amount(U256::from(81)),
+            Err(WithdrawalError::AboveMaximum { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert!(parse_address(ADDRESS).is_ok());
+        assert!(parse_address(&ADDRESS.to_lowercase()).is_ok());
+
+        // A wrong checksum, a missing prefix, a wrong length and the zero
+        // address are rejected
+        assert!(parse_address(&ADDRESS.replace("aAeb", "aaEb")).is_err());
+        assert!(parse_address(&ADDRESS[2..]).is_err());
+        assert!(parse_address(&ADDRESS[..40]).is_err());
+        assert!(parse_address(&format!("{:#x}", Address::zero())).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_withdraw() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+        let to = parse_address(ADDRESS).unwrap();
+
+        let withdrawal = withdraw(&user, to, U256::from(50), &config())
+            .await
+            .unwrap();
+
+        // The withdrawal is confirmed once its transfer is mined
+        assert_eq!(withdrawal.status, WithdrawalStatus::Confirmed);
+        assert!(withdrawal.tx_hash.is_some());
+        assert_eq!(withdrawal.from, user.get_wallet().unwrap().address());
+
+        // The withdrawal is recorded
+        let withdrawals = Withdrawal::for_user(user.id).await.unwrap();
+
+        assert_eq!(withdrawals.len(), 1);
+        assert_eq!(withdrawals[0].id, withdrawal.id);
+
+        // The withdrawal is in the history of the user
+        let history = Activity::for_user(user.id).await.unwrap();
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].kind, ActivityKind::Withdrawn);
+        assert_eq!(history[0].tx_hash, withdrawal.tx_hash);
+
+        // Withdrawing to the custodial wallet itself is rejected
+        let own = user.get_wallet().unwrap().address();
+
+        assert!(matches!(
+            withdraw(&user, own, U256::from(50), &config()).await,
+            Err(WithdrawalError::InvalidAddress(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        std::env::set_var("WITHDRAW_MIN_AMOUNT", "0.5");
+        std::env::set_var("WITHDRAW_MAX_AMOUNT", "invalid");
+
+        // Limits are in whole tokens and invalid limits use the default
+        let config = WithdrawalConfig::from_env(18);
+
+        assert_eq!(config.minimum, U256::exp10(17) * 5);
+        assert_eq!(config.maximum, U256::exp10(18) * 1_000_000);
+
+        std::env::remove_var("WITHDRAW_MIN_AMOUNT");
+        std::env::remove_var("WITHDRAW_MAX_AMOUNT");
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_queued() {
+        // The transfer cannot be sent for a user missing from the repository
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        let to = parse_address(ADDRESS).unwrap();
+
+        let withdrawal = withdraw(&user, to, U256::from(50), &config())
+            .await
+            .unwrap();
+
+        // The withdrawal stays pending and nothing is in the history yet
+        assert_eq!(withdrawal.status, WithdrawalStatus::Pending);
+        assert!(withdrawal.tx_hash.is_none());
+        assert!(Activity::for_user(user.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_insufficient_balance() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+        let to = parse_address(ADDRESS).unwrap();
+        let config = WithdrawalConfig {
+            minimum: U256::from(1),
+            maximum: U256::from(1000),
+        };
+
+        // The test balance is 100
+        assert!(matches!(
+            withdraw(&user, to, U256::from(101), &config).await,
+            Err(WithdrawalError::InsufficientBalance { available, .. }) if available == U256::from(100)
+        ));
+        assert!(Withdrawal::for_user(user.id).await.unwrap().is_empty());
+
+        // Pending withdrawals do not count towards the balance
+        let mut pending = Withdrawal::new(user.id, Address::random(), to, U256::from(60));
+        pending.save().await.unwrap();
+
+        assert!(matches!(
+            withdraw(&user, to, U256::from(41), &config).await,
+            Err(WithdrawalError::InsufficientBalance { available, .. }) if available == U256::from(40)
+        ));
+
+        // Failed withdrawals do not either
+        pending.status = WithdrawalStatus::Failed;
+        pending.save().await.unwrap();
+
+        assert!(withdraw(&user, to, U256::from(41), &config).await.is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use ethers::{prelude::LocalWallet, types::U256};
+use ethers::types::{Address, U256};
 use uuid::Uuid;
 
 /// Reward is a trait that must be implemented by all rewards.
@@ -11,7 +11,9 @@ pub trait Reward {
     fn get_id(&self) -> String;
     fn get_owner(&self) -> Uuid;
     fn is_redeemed(&self) -> bool;
-    async fn mint(value: U256, wallet: &LocalWallet) -> Result<U256, Self::Error>;
+    /// Send the transaction minting the reward to an address and return the
+    /// transaction hash.
+    async fn mint(&self, to: Address) -> Result<String, Self::Error>;
     async fn redeem(&mut self) -> Result<U256, Self::Error>;
 }
 
@@ -55,8 +57,8 @@ mod tests {
             self.redeemed
         }
 
-        async fn mint(_value: U256, _wallet: &LocalWallet) -> Result<U256, Self::Error> {
-            Ok(random_u256())
+        async fn mint(&self, _to: Address) -> Result<String, Self::Error> {
+            Ok(format!("{:#x}", random_u256()))
         }
 
         async fn redeem(&mut self) -> Result<U256, Self::Error> {
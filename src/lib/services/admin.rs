@@ -0,0 +1,214 @@
+use axum::extract::{Path, Query, Request};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::models::import::{validate, GrantImport, ImportError, ImportFormat, ImportProgress};
+use crate::workers::expiry::{sweep, SweepReport};
+
+use super::{ErrorDetails, ErrorResponse};
+
+/// Reject requests that do not carry the admin API key as a bearer token.
+pub async fn require_admin(request: Request, next: Next) -> Result<Response, ErrorResponse> {
+    if !is_admin(request.headers()) {
+        return Err(ErrorResponse {
+            status: StatusCode::UNAUTHORIZED,
+            error: ErrorDetails {
+                kind: "UnauthorizedError".into(),
+                message: "Admin API key required".into(),
+            },
+        });
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Check if the headers carry the admin API key. Admin requests are refused
+/// when `ADMIN_API_KEY` is not set.
+fn is_admin(headers: &HeaderMap) -> bool {
+    let key = std::env::var("ADMIN_API_KEY").unwrap_or_default();
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if !key.is_empty() => constant_time_eq(token.as_bytes(), key.as_bytes()),
+        _ => false,
+    }
+}
+
+/// Compare two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportOptions {
+    /// The format of the grant list, `csv` or `jsonl`.
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// Validate the list and report its issues without issuing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_format() -> String {
+    "csv".into()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/rewards/import",
+    tag = "admin",
+    params(ImportOptions),
+    request_body(content = String, description = "The grant list", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "The validation report with `dry_run`", body = Object),
+        (status = 202, description = "The progress of the import, which runs in the background", body = Object),
+        (status = 400, description = "Unknown format", body = ErrorDetails),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 422, description = "The validation report of a list with issues", body = Object),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn import_rewards(
+    Query(options): Query<ImportOptions>,
+    body: String,
+) -> Result<Response, ErrorResponse> {
+    let format: ImportFormat = options.format.parse()?;
+
+    if options.dry_run {
+        let (_, report) = validate(&body, format).await?;
+
+        return Ok(Json(report).into_response());
+    }
+
+    let import = match GrantImport::start(&body, format).await {
+        Ok(import) => import,
+        // Return the report so the issues can be fixed
+        Err(ImportError::Invalid(report)) => {
+            return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(report)).into_response())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let progress = import.progress();
+
+    // Running the list again resumes it, so an import that is already
+    // running is left alone
+    import.spawn();
+
+    Ok((StatusCode::ACCEPTED, Json(progress)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/rewards/import/{id}",
+    tag = "admin",
+    params(("id" = String, Path, description = "The id of the import")),
+    responses(
+        (status = 200, description = "The progress of the import", body = Object),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 404, description = "Import not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn get_import(Path(id): Path<String>) -> Result<Json<ImportProgress>, ErrorResponse> {
+    Ok(Json(GrantImport::find(&id).await?.progress()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/rewards/sweep",
+    tag = "admin",
+    responses(
+        (status = 200, description = "The outcome of the sweep", body = SweepReport),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn sweep_rewards() -> Result<Json<SweepReport>, ErrorResponse> {
+    Ok(Json(sweep().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use uuid::Uuid;
+
+    use crate::core::chain::generate_secret_key;
+    use crate::models::import::ImportReport;
+    use crate::models::user::User;
+    use crate::services::read_json;
+
+    use super::*;
+
+    #[test]
+    fn test_is_admin() {
+        std::env::set_var("ADMIN_API_KEY", "secret");
+
+        let mut headers = HeaderMap::new();
+        assert!(!is_admin(&headers));
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+        assert!(!is_admin(&headers));
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        assert!(is_admin(&headers));
+    }
+
+    #[tokio::test]
+    async fn test_import_rewards() {
+        let user = User::new(Uuid::new_v4(), generate_secret_key());
+        user.save().await.unwrap();
+
+        let body = format!(
+            "{{\"user_id\": \"{}\", \"value\": 5}}\n{{\"user_id\": \"{}\", \"value\": 5}}\n",
+            user.id,
+            Uuid::new_v4()
+        );
+        let options = |dry_run| ImportOptions {
+            format: "jsonl".into(),
+            dry_run,
+        };
+
+        // The dry run reports the unknown user
+        let result = import_rewards(Query(options(true)), body.clone()).await;
+        let report: ImportReport = read_json(result.unwrap()).await;
+
+        assert_eq!(report.valid, 1);
+        assert_eq!(report.issues.len(), 1);
+
+        // The import is refused with the report
+        let result = import_rewards(Query(options(false)), body).await.unwrap();
+
+        assert_eq!(result.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // A valid list is imported in the background
+        let body = format!("{{\"user_id\": \"{}\", \"value\": 5}}\n", user.id);
+        let result = import_rewards(Query(options(false)), body).await.unwrap();
+
+        assert_eq!(result.status(), StatusCode::ACCEPTED);
+
+        let progress: ImportProgress = read_json(result).await;
+
+        for _ in 0..50 {
+            if get_import(Path(progress.id.clone())).await.unwrap().issued == 1 {
+                return;
+            }
+
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        panic!("The import did not complete");
+    }
+}
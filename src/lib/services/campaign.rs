@@ -0,0 +1,239 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::Json;
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::campaign::{Campaign, CampaignStatus};
+use crate::utils::helpers::now;
+
+use super::ErrorResponse;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateCampaignRequest {
+    pub name: String,
+    pub budget: u128,
+    pub per_user_cap: u128,
+    /// When the campaign starts, as a unix timestamp. Defaults to now.
+    #[serde(default)]
+    pub starts_at: Option<u64>,
+    pub ends_at: u64,
+    /// How long rewards issued from the campaign can be redeemed, in seconds.
+    #[serde(default)]
+    pub reward_ttl: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CampaignStatusRequest {
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CampaignResult {
+    pub id: Uuid,
+    pub name: String,
+    pub budget: String,
+    pub per_user_cap: String,
+    pub spent: String,
+    pub remaining: String,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub reward_ttl: Option<u64>,
+    pub status: CampaignStatus,
+    /// If the campaign accepts rewards right now.
+    pub active: bool,
+}
+
+impl From<Campaign> for CampaignResult {
+    fn from(campaign: Campaign) -> Self {
+        Self {
+            id: campaign.id,
+            active: campaign.is_active(now()),
+            remaining: campaign.remaining().to_string(),
+            name: campaign.name,
+            budget: campaign.budget.to_string(),
+            per_user_cap: campaign.per_user_cap.to_string(),
+            spent: campaign.spent.to_string(),
+            starts_at: campaign.starts_at,
+            ends_at: campaign.ends_at,
+            reward_ttl: campaign.reward_ttl,
+            status: campaign.status,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/campaigns",
+    tag = "admin",
+    request_body = CreateCampaignRequest,
+    responses(
+        (status = 201, description = "The new campaign", body = CampaignResult),
+        (status = 400, description = "Invalid payload", body = ErrorDetails),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn create_campaign(
+    payload: Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<CampaignResult>), ErrorResponse> {
+    let request: CreateCampaignRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+
+    let campaign = Campaign::new(
+        request.name,
+        U256::from(request.budget),
+        U256::from(request.per_user_cap),
+        request.starts_at.unwrap_or_else(now),
+        request.ends_at,
+    )?
+    .with_reward_ttl(request.reward_ttl);
+    campaign.save().await?;
+
+    Ok((StatusCode::CREATED, Json(CampaignResult::from(campaign))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/campaigns/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the campaign")),
+    responses(
+        (status = 200, description = "The campaign", body = CampaignResult),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 404, description = "Campaign not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn get_campaign(Path(id): Path<Uuid>) -> Result<Json<CampaignResult>, ErrorResponse> {
+    let campaign = Campaign::from_id(id).await?;
+
+    Ok(Json(CampaignResult::from(campaign)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/campaigns/{id}/status",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the campaign")),
+    request_body = CampaignStatusRequest,
+    responses(
+        (status = 200, description = "The updated campaign", body = CampaignResult),
+        (status = 400, description = "Unknown status", body = ErrorDetails),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 404, description = "Campaign not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn set_campaign_status(
+    Path(id): Path<Uuid>,
+    payload: Json<serde_json::Value>,
+) -> Result<Json<CampaignResult>, ErrorResponse> {
+    let request: CampaignStatusRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+    let status: CampaignStatus = request.status.parse()?;
+
+    let campaign = Campaign::set_status(id, status).await?;
+
+    Ok(Json(CampaignResult::from(campaign)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::Query;
+    use serde_json::json;
+
+    use crate::services::user::{register, reward, RewardOptions};
+
+    use super::*;
+
+    async fn create(budget: u128, per_user_cap: u128) -> CampaignResult {
+        let (_, Json(campaign)) = create_campaign(Json(json!({
+            "name": "Launch",
+            "budget": budget,
+            "per_user_cap": per_user_cap,
+            "ends_at": now() + 3600,
+        })))
+        .await
+        .unwrap();
+
+        campaign
+    }
+
+    #[tokio::test]
+    async fn test_create_campaign() {
+        let campaign = create(1000, 100).await;
+
+        assert!(campaign.active);
+        assert_eq!(campaign.remaining, "1000");
+
+        // Campaigns that end before they start are rejected
+        let result = create_campaign(Json(json!({
+            "name": "Launch",
+            "budget": 1000,
+            "per_user_cap": 100,
+            "starts_at": now(),
+            "ends_at": now() - 1,
+        })))
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_campaign_reward() {
+        let campaign = create(150, 100).await;
+        let user_id = Uuid::new_v4();
+
+        assert!(register(Json(json!({ "id": user_id }))).await.is_ok());
+
+        let reward_from_campaign = |value: u128| {
+            reward(
+                Path(user_id),
+                Query(RewardOptions::default()),
+                Json(json!({ "value": value, "campaign_id": campaign.id })),
+            )
+        };
+
+        // The reward is spent from the budget
+        assert!(reward_from_campaign(80).await.is_ok());
+
+        let Json(result) = get_campaign(Path(campaign.id)).await.unwrap();
+
+        assert_eq!(result.spent, "80");
+        assert_eq!(result.remaining, "70");
+
+        // Rewards above the cap per user are rejected
+        let error = reward_from_campaign(30).await.err().unwrap();
+
+        assert_eq!(error.status, StatusCode::CONFLICT);
+        assert_eq!(error.error.kind, "CampaignCapExceededError");
+
+        // Paused campaigns issue nothing
+        let result = set_campaign_status(Path(campaign.id), Json(json!({ "status": "paused" })))
+            .await
+            .unwrap();
+
+        assert!(!result.active);
+
+        let error = reward_from_campaign(10).await.err().unwrap();
+
+        assert_eq!(error.error.kind, "CampaignNotActiveError");
+
+        let Json(result) = get_campaign(Path(campaign.id)).await.unwrap();
+
+        assert_eq!(result.spent, "80");
+    }
+
+    #[tokio::test]
+    async fn test_get_campaign_not_found() {
+        let result = get_campaign(Path(Uuid::new_v4())).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::NOT_FOUND);
+    }
+}
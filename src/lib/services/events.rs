@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use axum::extract::Query;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::core::events::{event_bus, Event, EventBus};
+use crate::models::user::User;
+
+use super::ErrorResponse;
+
+/// The header a reconnecting client sends with the id of the last event it
+/// received.
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+#[derive(Serialize, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventOptions {
+    /// Only send the events of this user.
+    pub user_id: Option<Uuid>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events",
+    tag = "events",
+    params(EventOptions, ("Last-Event-ID" = Option<u64>, Header, description = "The id of the last event received, to catch up on the events missed since")),
+    responses(
+        (status = 200, description = "A stream of server-sent events, each carrying an event as JSON", content_type = "text/event-stream", body = Event),
+        (status = 400, description = "The Last-Event-ID header is not an event id", body = ErrorDetails),
+        (status = 404, description = "User not found", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn stream_events(
+    Query(options): Query<EventOptions>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, axum::Error>>>, ErrorResponse> {
+    if let Some(user_id) = options.user_id {
+        // Check that the user exists
+        User::from_id(user_id.to_string()).await?;
+    }
+
+    let last_id = match headers.get(LAST_EVENT_ID_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| ErrorResponse::from(String::from("Invalid Last-Event-ID")))?,
+        ),
+        None => None,
+    };
+
+    let events = event_stream(event_bus(), options.user_id, last_id).map(|event| {
+        SseEvent::default()
+            .id(event.id.to_string())
+            .event(event.kind.name())
+            .json_data(&event)
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Get the events of a bus for a client, optionally only those of a user.
+/// The events in the log after `last_id` are sent first. A client that falls
+/// behind catches up from the log, and no event is sent twice.
+pub fn event_stream(
+    bus: &'static EventBus,
+    user_id: Option<Uuid>,
+    last_id: Option<u64>,
+) -> impl Stream<Item = Event> {
+    let (missed, receiver) = bus.subscribe(last_id);
+    let state = (
+        VecDeque::from(missed),
+        receiver,
+        last_id.unwrap_or_default(),
+    );
+
+    stream::unfold(
+        state,
+        move |(mut pending, mut receiver, mut last_id)| async move {
+            loop {
+                let event = match pending.pop_front() {
+                    Some(event) => event,
+                    None => match receiver.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Lagged(_)) => {
+                            pending.extend(bus.since(last_id));
+                            continue;
+                        }
+                        Err(RecvError::Closed) => return None,
+                    },
+                };
+
+                if event.id <= last_id {
+                    continue;
+                }
+
+                last_id = event.id;
+
+                if user_id.is_none_or(|user_id| user_id == event.user_id) {
+                    return Some((event, (pending, receiver, last_id)));
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use axum::http::StatusCode;
+
+    use crate::core::events::EventKind;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let user_id = Uuid::new_v4();
+        let mut events = pin!(event_stream(event_bus(), Some(user_id), None));
+
+        // Only the events of the user are sent
+        event_bus().publish(Event::new(EventKind::RewardIssued, Uuid::new_v4()));
+        let issued = event_bus().publish(Event::new(EventKind::RewardIssued, user_id));
+        let minted = event_bus().publish(Event::new(EventKind::RewardMinted, user_id));
+
+        assert_eq!(events.next().await.unwrap(), issued);
+        assert_eq!(events.next().await.unwrap(), minted);
+
+        // A client that reconnects gets the events it missed first
+        let redeemed = event_bus().publish(Event::new(EventKind::RewardRedeemed, user_id));
+        let mut events = pin!(event_stream(event_bus(), Some(user_id), Some(issued.id)));
+
+        assert_eq!(events.next().await.unwrap(), minted);
+        assert_eq!(events.next().await.unwrap(), redeemed);
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_invalid() {
+        let options = |user_id| EventOptions { user_id };
+        let mut headers = HeaderMap::new();
+
+        assert_eq!(
+            stream_events(Query(options(Some(Uuid::new_v4()))), headers.clone())
+                .await
+                .err()
+                .unwrap()
+                .status,
+            StatusCode::NOT_FOUND
+        );
+
+        headers.insert(LAST_EVENT_ID_HEADER, "latest".parse().unwrap());
+
+        assert_eq!(
+            stream_events(Query(options(None)), headers)
+                .await
+                .err()
+                .unwrap()
+                .status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
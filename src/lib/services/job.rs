@@ -0,0 +1,97 @@
+use axum::{extract::Path, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::job::{Job, JobStatus};
+use crate::services::user::RewardResult;
+use crate::services::ErrorResponse;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct JobResult {
+    pub id: Uuid,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub result: Option<RewardResult>,
+    pub error: Option<String>,
+}
+
+impl From<Job> for JobResult {
+    fn from(job: Job) -> Self {
+        let result = match (job.status, job.reward_id, job.url) {
+            (JobStatus::Succeeded, Some(id), Some(url)) => Some(RewardResult {
+                success: true,
+                id,
+                url,
+            }),
+            _ => None,
+        };
+
+        Self {
+            id: job.id,
+            status: job.status,
+            attempts: job.attempts,
+            result,
+            error: job.error,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}",
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "The id of the job")),
+    responses(
+        (status = 200, description = "The job", body = JobResult),
+        (status = 404, description = "Job not found", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn get_job(Path(id): Path<Uuid>) -> Result<Json<JobResult>, ErrorResponse> {
+    // Get the job from the repository
+    let job = Job::from_id(id).await?;
+
+    Ok(Json(JobResult::from(job)))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_job() {
+        // An unknown job is not found
+        let result = get_job(Path(Uuid::new_v4())).await;
+
+        assert_eq!(
+            result.err().unwrap().status,
+            axum::http::StatusCode::NOT_FOUND
+        );
+
+        // A queued job has no result yet
+        let job = Job::new(Uuid::new_v4(), U256::from(100));
+        job.save().await.unwrap();
+
+        let result = get_job(Path(job.id)).await.unwrap();
+
+        assert_eq!(result.id, job.id);
+        assert_eq!(result.status, JobStatus::Queued);
+        assert!(result.result.is_none());
+
+        // A succeeded job includes the reward
+        let job = Job {
+            status: JobStatus::Succeeded,
+            reward_id: Some(Uuid::new_v4().to_string()),
+            url: Some("https://example.com".into()),
+            ..job
+        };
+        job.save().await.unwrap();
+
+        let result = get_job(Path(job.id)).await.unwrap();
+
+        assert!(result.result.as_ref().unwrap().success);
+    }
+}
@@ -1,13 +1,36 @@
-use axum::{body::Body, http::StatusCode, response::IntoResponse};
+use axum::{
+    body::Body,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
 
-use crate::{core::reward::RewardError, models::user::UserError};
+use crate::{
+    core::{
+        chain::{ChainError, Simulation},
+        reward::RewardError,
+    },
+    models::{
+        authorization::AuthorizationError, campaign::CampaignError, funding::FundingError,
+        import::ImportError, job::JobError, outbox::OutboxStatus, user::UserError,
+        webhook::WebhookError, withdrawal::WithdrawalError,
+    },
+};
 
+pub mod admin;
+pub mod campaign;
+pub mod events;
+pub mod job;
+pub mod openapi;
 pub mod reward;
 pub mod status;
 pub mod user;
+pub mod webhook;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct ErrorDetails {
     pub kind: String,
     pub message: String,
@@ -63,6 +86,7 @@ impl From<UserError> for ErrorResponse {
                     message: format!("{:?}", e),
                 },
             },
+            UserError::ChainError(e) => ErrorResponse::from(e),
         }
     }
 }
@@ -91,6 +115,34 @@ impl From<RewardError> for ErrorResponse {
                     message: "Reward already redeemed".into(),
                 },
             },
+            RewardError::Expired => ErrorResponse {
+                status: StatusCode::GONE,
+                error: ErrorDetails {
+                    kind: "RewardExpiredError".into(),
+                    message: "Reward expired".into(),
+                },
+            },
+            RewardError::Revoked => ErrorResponse {
+                status: StatusCode::GONE,
+                error: ErrorDetails {
+                    kind: "RewardRevokedError".into(),
+                    message: "Reward revoked".into(),
+                },
+            },
+            RewardError::SameOwner => ErrorResponse {
+                status: StatusCode::BAD_REQUEST,
+                error: ErrorDetails {
+                    kind: "ValidationError".into(),
+                    message: "Reward already owned by the recipient".into(),
+                },
+            },
+            RewardError::InvalidValue(message) => ErrorResponse {
+                status: StatusCode::BAD_REQUEST,
+                error: ErrorDetails {
+                    kind: "ValidationError".into(),
+                    message,
+                },
+            },
             RewardError::RepositoryError(e) => ErrorResponse {
                 status: StatusCode::INTERNAL_SERVER_ERROR,
                 error: ErrorDetails {
@@ -105,11 +157,11 @@ impl From<RewardError> for ErrorResponse {
                     message: format!("{:?}", e),
                 },
             },
-            RewardError::MintRewardError => ErrorResponse {
+            RewardError::MintRewardError(e) => ErrorResponse {
                 status: StatusCode::INTERNAL_SERVER_ERROR,
                 error: ErrorDetails {
                     kind: "MintRewardError".into(),
-                    message: "Failed to mint reward".into(),
+                    message: format!("Failed to mint reward: {}", e),
                 },
             },
             RewardError::UnknownError(e) => ErrorResponse {
@@ -119,10 +171,333 @@ impl From<RewardError> for ErrorResponse {
                     message: format!("{:?}", e),
                 },
             },
+            RewardError::ChainError(e) => ErrorResponse::from(e),
+            RewardError::FundingError(e) => ErrorResponse::from(e),
+            RewardError::AuthorizationError(e) => ErrorResponse::from(e),
+            RewardError::CampaignError(e) => ErrorResponse::from(e),
+            // Handlers that may queue an action answer through `queued`, so
+            // a queued action is never reported as an error
+            RewardError::Queued(_) => ErrorResponse {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                error: ErrorDetails {
+                    kind: "UnknownError".into(),
+                    message: error.to_string(),
+                },
+            },
+        }
+    }
+}
+
+impl From<ChainError> for ErrorResponse {
+    fn from(error: ChainError) -> Self {
+        let (status, kind) = match &error {
+            ChainError::NotTokenOwner => (StatusCode::FORBIDDEN, "NotTokenOwnerError"),
+            ChainError::NotContractOwner => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "NotContractOwnerError")
+            }
+            ChainError::TokenNotFound => (StatusCode::NOT_FOUND, "TokenNotFoundError"),
+            ChainError::TokenAlreadyExists => (StatusCode::CONFLICT, "TokenAlreadyExistsError"),
+            ChainError::Reverted(_) => (StatusCode::UNPROCESSABLE_ENTITY, "ContractRevertError"),
+            ChainError::FeeTooHigh { .. } => (StatusCode::SERVICE_UNAVAILABLE, "FeeTooHighError"),
+            ChainError::Unavailable(_) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "ChainUnavailableError")
+            }
+            ChainError::NonceRejected(_) | ChainError::Underpriced(_) | ChainError::RpcError(_) => {
+                (StatusCode::BAD_GATEWAY, "ChainError")
+            }
+        };
+
+        ErrorResponse {
+            status,
+            error: ErrorDetails {
+                kind: kind.into(),
+                message: error.to_string(),
+            },
         }
     }
 }
 
+impl From<FundingError> for ErrorResponse {
+    fn from(error: FundingError) -> Self {
+        match error {
+            FundingError::LimitExceeded {
+                requested,
+                remaining,
+            } => ErrorResponse {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                error: ErrorDetails {
+                    kind: "GasLimitExceededError".into(),
+                    message: format!(
+                        "Daily gas limit exceeded: {} wei requested, {} wei remaining",
+                        requested, remaining
+                    ),
+                },
+            },
+            FundingError::RepositoryError(e) => ErrorResponse {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                error: ErrorDetails {
+                    kind: "RepositoryError".into(),
+                    message: format!("{:?}", e),
+                },
+            },
+            FundingError::UserError(e) => ErrorResponse::from(e),
+            FundingError::ChainError(e) => ErrorResponse::from(e),
+        }
+    }
+}
+
+impl From<AuthorizationError> for ErrorResponse {
+    fn from(error: AuthorizationError) -> Self {
+        let message = error.to_string();
+        let (status, kind) = match error {
+            AuthorizationError::InvalidSignature | AuthorizationError::InvalidDomain => {
+                (StatusCode::UNAUTHORIZED, "InvalidAuthorizationError")
+            }
+            AuthorizationError::Expired => (StatusCode::UNAUTHORIZED, "AuthorizationExpiredError"),
+            AuthorizationError::InvalidNonce { .. } => (StatusCode::CONFLICT, "InvalidNonceError"),
+            AuthorizationError::ChainError(e) => return ErrorResponse::from(e),
+            AuthorizationError::UserError(e) => return ErrorResponse::from(e),
+        };
+
+        ErrorResponse {
+            status,
+            error: ErrorDetails {
+                kind: kind.into(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<CampaignError> for ErrorResponse {
+    fn from(error: CampaignError) -> Self {
+        let (status, kind, message) = match &error {
+            CampaignError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "NotFoundError",
+                "Campaign not found".to_string(),
+            ),
+            CampaignError::NotActive => (
+                StatusCode::CONFLICT,
+                "CampaignNotActiveError",
+                error.to_string(),
+            ),
+            CampaignError::BudgetExceeded {
+                requested,
+                remaining,
+            } => (
+                StatusCode::CONFLICT,
+                "CampaignBudgetExceededError",
+                format!(
+                    "Campaign budget exceeded: {} requested, {} remaining",
+                    requested, remaining
+                ),
+            ),
+            CampaignError::UserCapExceeded {
+                requested,
+                remaining,
+            } => (
+                StatusCode::CONFLICT,
+                "CampaignCapExceededError",
+                format!(
+                    "Campaign cap per user exceeded: {} requested, {} remaining",
+                    requested, remaining
+                ),
+            ),
+            CampaignError::RepositoryError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "RepositoryError",
+                format!("{:?}", e),
+            ),
+        };
+
+        ErrorResponse {
+            status,
+            error: ErrorDetails {
+                kind: kind.into(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<ImportError> for ErrorResponse {
+    fn from(error: ImportError) -> Self {
+        match error {
+            ImportError::NotFound => ErrorResponse {
+                status: StatusCode::NOT_FOUND,
+                error: ErrorDetails {
+                    kind: "NotFoundError".into(),
+                    message: "Import not found".into(),
+                },
+            },
+            ImportError::UnknownFormat(_) | ImportError::Invalid(_) => {
+                ErrorResponse::from(error.to_string())
+            }
+            ImportError::RepositoryError(e) => ErrorResponse {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                error: ErrorDetails {
+                    kind: "RepositoryError".into(),
+                    message: format!("{:?}", e),
+                },
+            },
+            ImportError::UserError(e) => ErrorResponse::from(e),
+            ImportError::RewardError(e) => ErrorResponse::from(e),
+        }
+    }
+}
+
+impl From<JobError> for ErrorResponse {
+    fn from(error: JobError) -> Self {
+        match error {
+            JobError::NotFound => ErrorResponse {
+                status: StatusCode::NOT_FOUND,
+                error: ErrorDetails {
+                    kind: "NotFoundError".into(),
+                    message: "Job not found".into(),
+                },
+            },
+            JobError::RepositoryError(e) => ErrorResponse {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                error: ErrorDetails {
+                    kind: "RepositoryError".into(),
+                    message: format!("{:?}", e),
+                },
+            },
+            JobError::RewardError(e) => ErrorResponse::from(e),
+        }
+    }
+}
+
+impl From<WithdrawalError> for ErrorResponse {
+    fn from(error: WithdrawalError) -> Self {
+        let message = match &error {
+            WithdrawalError::BelowMinimum { amount, minimum } => format!(
+                "Withdrawal amount {} is below the minimum of {}",
+                amount, minimum
+            ),
+            WithdrawalError::AboveMaximum { amount, maximum } => format!(
+                "Withdrawal amount {} is above the maximum of {}",
+                amount, maximum
+            ),
+            WithdrawalError::InsufficientBalance {
+                requested,
+                available,
+            } => format!(
+                "Insufficient balance: {} requested, {} available",
+                requested, available
+            ),
+            _ => error.to_string(),
+        };
+        let (status, kind) = match error {
+            WithdrawalError::InvalidAddress(_)
+            | WithdrawalError::BelowMinimum { .. }
+            | WithdrawalError::AboveMaximum { .. } => (StatusCode::BAD_REQUEST, "ValidationError"),
+            WithdrawalError::InsufficientBalance { .. } => {
+                (StatusCode::CONFLICT, "InsufficientBalanceError")
+            }
+            WithdrawalError::RepositoryError(e) => {
+                return ErrorResponse {
+                    status: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: ErrorDetails {
+                        kind: "RepositoryError".into(),
+                        message: format!("{:?}", e),
+                    },
+                }
+            }
+            WithdrawalError::UserError(e) => return ErrorResponse::from(e),
+            WithdrawalError::FundingError(e) => return ErrorResponse::from(e),
+            WithdrawalError::RewardError(e) => return ErrorResponse::from(e),
+            WithdrawalError::ChainError(e) => return ErrorResponse::from(e),
+        };
+
+        ErrorResponse {
+            status,
+            error: ErrorDetails {
+                kind: kind.into(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<WebhookError> for ErrorResponse {
+    fn from(error: WebhookError) -> Self {
+        match error {
+            WebhookError::NotFound => ErrorResponse {
+                status: StatusCode::NOT_FOUND,
+                error: ErrorDetails {
+                    kind: "NotFoundError".into(),
+                    message: "Webhook subscription not found".into(),
+                },
+            },
+            WebhookError::Invalid(_) => ErrorResponse::from(error.to_string()),
+            WebhookError::RepositoryError(e) => ErrorResponse {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                error: ErrorDetails {
+                    kind: "RepositoryError".into(),
+                    message: format!("{:?}", e),
+                },
+            },
+        }
+    }
+}
+
+/// DryRunResult is the outcome of a simulated transaction.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct DryRunResult {
+    pub success: bool,
+    pub gas_estimate: Option<String>,
+    pub error: Option<ErrorDetails>,
+}
+
+impl DryRunResult {
+    /// Convert a simulation into a result. A revert is reported as an
+    /// unsuccessful simulation, while any other error is returned.
+    pub fn from_simulation(
+        simulation: Result<Simulation, RewardError>,
+    ) -> Result<Self, ErrorResponse> {
+        match simulation {
+            Ok(simulation) => Ok(DryRunResult {
+                success: true,
+                gas_estimate: Some(simulation.gas_estimate.to_string()),
+                error: None,
+            }),
+            Err(RewardError::ChainError(e)) if e.is_revert() => Ok(DryRunResult {
+                success: false,
+                gas_estimate: None,
+                error: Some(ErrorResponse::from(e).error),
+            }),
+            Err(e) => Err(ErrorResponse::from(e)),
+        }
+    }
+}
+
+/// QueuedResult is returned with `202 Accepted` when an action was recorded
+/// in the outbox but could not be sent yet. The outbox worker sends it later.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct QueuedResult {
+    /// The id of the outbox entry of the action.
+    pub id: Uuid,
+    pub status: OutboxStatus,
+}
+
+/// Answer `202 Accepted` with the outbox entry of an action that was queued
+/// instead of sent, or return the response of any other error.
+pub fn queued(error: RewardError) -> Result<Response, ErrorResponse> {
+    match error {
+        RewardError::Queued(id) => Ok((
+            StatusCode::ACCEPTED,
+            Json(QueuedResult {
+                id,
+                status: OutboxStatus::Pending,
+            }),
+        )
+            .into_response()),
+        e => Err(ErrorResponse::from(e)),
+    }
+}
+
 impl IntoResponse for ErrorResponse {
     fn into_response(self) -> axum::response::Response {
         let body = serde_json::to_string(&self.error).unwrap();
@@ -132,3 +507,15 @@ impl IntoResponse for ErrorResponse {
             .unwrap()
     }
 }
+
+/// Read the JSON body of a response.
+#[cfg(test)]
+pub(crate) async fn read_json<T: serde::de::DeserializeOwned>(
+    response: axum::response::Response,
+) -> T {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    serde_json::from_slice(&body).unwrap()
+}
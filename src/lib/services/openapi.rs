@@ -0,0 +1,194 @@
+use axum::response::Html;
+use axum::Json;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::core::amount::Amount;
+use crate::core::events::{Event, EventKind};
+use crate::core::retry::CircuitState;
+use crate::models::activity::ActivityKind;
+use crate::models::balance::RewardCounts;
+use crate::models::campaign::CampaignStatus;
+use crate::models::job::JobStatus;
+use crate::models::outbox::OutboxStatus;
+use crate::models::reward::{Revocation, RewardStatus, RewardTransfer};
+use crate::models::webhook::{DeliveryStatus, WebhookEvent};
+use crate::models::withdrawal::WithdrawalStatus;
+use crate::workers::expiry::{SweepFailure, SweepReport};
+
+use super::{admin, campaign, events, job, reward, status, user, webhook};
+use super::{DryRunResult, ErrorDetails, QueuedResult};
+
+/// ApiDoc is the OpenAPI document of the API, generated from the handlers
+/// and the types they take and return.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "nftest", description = "Issue, redeem and track NFT rewards."),
+    paths(
+        status::status,
+        events::stream_events,
+        user::register,
+        user::get_balance,
+        user::get_rewards,
+        user::get_history,
+        user::reward,
+        user::withdraw,
+        user::get_withdrawals,
+        reward::redeem,
+        reward::transfer,
+        reward::split,
+        reward::revoke,
+        reward::batch_reward,
+        job::get_job,
+        admin::import_rewards,
+        admin::get_import,
+        admin::sweep_rewards,
+        campaign::create_campaign,
+        campaign::get_campaign,
+        campaign::set_campaign_status,
+        webhook::list_subscriptions,
+        webhook::create_subscription,
+        webhook::delete_subscription,
+        webhook::get_deliveries,
+    ),
+    components(schemas(
+        Amount,
+        ErrorDetails,
+        DryRunResult,
+        QueuedResult,
+        OutboxStatus,
+        status::Status,
+        status::RpcStatus,
+        CircuitState,
+        Event,
+        EventKind,
+        user::RegisterRequest,
+        user::RegisterResult,
+        user::BalanceResult,
+        RewardCounts,
+        user::HistoryResult,
+        user::ActivityInfo,
+        ActivityKind,
+        user::RewardRequest,
+        user::RewardResult,
+        user::RewardResponse,
+        user::WithdrawRequest,
+        user::WithdrawalResult,
+        WithdrawalStatus,
+        reward::RewardInfo,
+        RewardStatus,
+        Revocation,
+        RewardTransfer,
+        reward::RedeemResult,
+        reward::TransferRequest,
+        reward::SplitRequest,
+        reward::RevokeRequest,
+        reward::BatchRewardRequest,
+        reward::BatchRewardResult,
+        reward::BatchRewardItem,
+        reward::BatchRewardStatus,
+        job::JobResult,
+        JobStatus,
+        SweepReport,
+        SweepFailure,
+        campaign::CreateCampaignRequest,
+        campaign::CampaignStatusRequest,
+        campaign::CampaignResult,
+        CampaignStatus,
+        webhook::CreateSubscriptionRequest,
+        webhook::SubscriptionResult,
+        webhook::DeliveryResult,
+        WebhookEvent,
+        DeliveryStatus,
+    )),
+    modifiers(&AdminKey),
+    tags(
+        (name = "status", description = "Health of the API"),
+        (name = "events", description = "Live reward events"),
+        (name = "users", description = "Users, their balances and their rewards"),
+        (name = "rewards", description = "Redeeming, transferring and splitting rewards"),
+        (name = "jobs", description = "Rewards issued in the background"),
+        (name = "admin", description = "Operations that require the admin API key"),
+    )
+)]
+pub struct ApiDoc;
+
+/// AdminKey adds the admin API key, sent as a bearer token, to the security
+/// schemes of the document.
+struct AdminKey;
+
+impl Modify for AdminKey {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "admin_key",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+#[axum::debug_handler]
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[axum::debug_handler]
+pub async fn docs() -> Html<String> {
+    Html(format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>nftest API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    SwaggerUIBundle({{ url: "{}/openapi.json", dom_id: "#swagger-ui" }});
+  </script>
+</body>
+</html>
+"##,
+        crate::utils::router::get_base_path()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_openapi() {
+        let Json(spec) = openapi().await;
+        let json = serde_json::to_value(&spec).unwrap();
+
+        assert!(json["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(json["components"]["securitySchemes"]["admin_key"].is_object());
+
+        // Every schema that is referred to is defined
+        let schemas = json["components"]["schemas"].as_object().unwrap();
+        let text = json.to_string();
+
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+
+            assert!(schemas.contains_key(name), "{} is not defined", name);
+        }
+
+        // A reward responds with the minted reward or the simulation
+        assert_eq!(
+            schemas["RewardResponse"]["oneOf"].as_array().unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_docs() {
+        let Html(page) = docs().await;
+
+        assert!(page.contains("/api/v1/openapi.json"));
+    }
+}
@@ -1,32 +1,540 @@
-use axum::{extract::Path, Json};
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use ethers::types::U256;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::models::reward::RewardNFT;
+use crate::core::amount::Amount;
+use crate::core::reward::RewardError;
+use crate::models::job::Job;
+use crate::models::outbox::{OutboxAction, OutboxEntry};
+use crate::models::reward::{
+    max_split_parts, IssueOptions, Revocation, RewardNFT, RewardStatus, RewardTransfer,
+};
+use crate::models::user::{User, UserError};
 use crate::rewards::Reward;
-use crate::services::ErrorResponse;
+use crate::services::{queued, DryRunResult, ErrorDetails, ErrorResponse};
+use crate::utils::config::parse_env;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RewardInfo {
+    pub id: String,
+    pub owner: Uuid,
+    pub token_id: String,
+    pub value: String,
+    pub url: String,
+    pub status: RewardStatus,
+    pub expires_at: Option<u64>,
+    /// Why and by whom the reward was revoked, if it was.
+    pub revocation: Option<Revocation>,
+    /// The transfers of the reward between users, oldest first.
+    pub transfers: Vec<RewardTransfer>,
+    /// The reward this reward was split from.
+    pub parent_id: Option<Uuid>,
+    /// The rewards this reward was split into.
+    pub children: Vec<Uuid>,
+    /// The value credited to the owner when the reward was redeemed.
+    pub redeemed_value: String,
+}
+
+impl From<RewardNFT> for RewardInfo {
+    fn from(reward: RewardNFT) -> Self {
+        Self {
+            id: reward.get_id(),
+            owner: reward.get_owner(),
+            token_id: format!("{:#x}", reward.get_token_id()),
+            value: reward.get_value().to_string(),
+            url: reward.get_url(),
+            status: reward.get_status(),
+            expires_at: reward.get_expires_at(),
+            revocation: reward.get_revocation().cloned(),
+            transfers: reward.get_transfers().to_vec(),
+            parent_id: reward.get_parent_id(),
+            children: reward.get_children().to_vec(),
+            redeemed_value: reward.get_redeemed_value().to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct RedeemResult {
     pub id: Uuid,
     pub reward: String,
+    /// The id of the new reward carrying the rest of the value, if only part
+    /// of the reward was redeemed.
+    #[serde(default)]
+    pub remainder: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RedeemOptions {
+    /// Simulate the burn without sending it or saving anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Redeem only this much of the value of the reward, as a raw amount in
+    /// the smallest unit of the token.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Redeem only this much of the value of the reward, as a decimal amount
+    /// of whole tokens.
+    #[serde(default)]
+    pub decimal: Option<String>,
+}
+
+impl RedeemOptions {
+    /// Redeem only the given amount of the value of the reward.
+    pub fn partial(amount: Amount) -> Self {
+        match amount {
+            Amount::Raw(value) => Self {
+                value: Some(value),
+                ..Default::default()
+            },
+            Amount::Decimal(decimal) => Self {
+                decimal: Some(decimal),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Get the amount to redeem, if only part of the reward is redeemed.
+    pub fn amount(&self) -> Result<Option<Amount>, RewardError> {
+        match (&self.value, &self.decimal) {
+            (Some(_), Some(_)) => Err(RewardError::InvalidValue(
+                "Only one of value and decimal can be given".into(),
+            )),
+            (Some(value), None) => Ok(Some(Amount::Raw(value.clone()))),
+            (None, Some(decimal)) => Ok(Some(Amount::Decimal(decimal.clone()))),
+            (None, None) => Ok(None),
+        }
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/reward/{id}/redeem",
+    tag = "rewards",
+    params(("id" = Uuid, Path, description = "The id of the reward"), RedeemOptions),
+    responses(
+        (status = 200, description = "The reward was redeemed, or the outcome of the simulation with `dry_run`", body = RedeemResult),
+        (status = 202, description = "The burn was queued and is sent later", body = QueuedResult),
+        (status = 400, description = "The reward cannot be redeemed", body = ErrorDetails),
+        (status = 404, description = "Reward not found", body = ErrorDetails),
+    ),
+)]
 #[axum::debug_handler]
-pub async fn redeem(Path(id): Path<Uuid>) -> Result<Json<RedeemResult>, ErrorResponse> {
+pub async fn redeem(
+    Path(id): Path<Uuid>,
+    Query(options): Query<RedeemOptions>,
+) -> Result<Response, ErrorResponse> {
     // Get the user from the repository
     let mut reward: RewardNFT = RewardNFT::from_id(id.to_string()).await?;
-    // Get the user's balance of rewards
-    let reward = reward.redeem().await?.to_string();
 
-    Ok(Json(RedeemResult { id, reward }))
+    let value = match options.amount()? {
+        Some(amount) => Some(amount.to_token_units().await?),
+        None => None,
+    };
+
+    if options.dry_run {
+        let simulation = match value {
+            Some(value) => reward.simulate_redeem_partial(value).await,
+            None => reward.simulate_redeem().await,
+        };
+
+        return Ok(Json(DryRunResult::from_simulation(simulation)?).into_response());
+    }
+
+    let result = match value {
+        // Burn the reward and carry the rest of its value over to a new one
+        Some(value) => reward
+            .redeem_partial(value)
+            .await
+            .map(|remainder| RedeemResult {
+                id,
+                remainder: Some(remainder.get_id()),
+                reward: value.to_string(),
+            }),
+        None => reward.redeem().await.map(|value| RedeemResult {
+            id,
+            reward: value.to_string(),
+            remainder: None,
+        }),
+    };
+
+    match result {
+        Ok(result) => Ok(Json(result).into_response()),
+        Err(e) => queued(e),
+    }
+}
+
+/// SplitRequest takes either the values of the new rewards, which must add
+/// up to the value of the reward, or how many rewards of equal value to split
+/// it into.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SplitRequest {
+    #[serde(default)]
+    pub values: Option<Vec<Amount>>,
+    #[serde(default)]
+    pub parts: Option<usize>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/reward/{id}/split",
+    tag = "rewards",
+    params(("id" = Uuid, Path, description = "The id of the reward")),
+    request_body = SplitRequest,
+    responses(
+        (status = 200, description = "The new rewards", body = [RewardInfo]),
+        (status = 202, description = "The split was queued and is sent later", body = QueuedResult),
+        (status = 400, description = "Invalid payload or the values do not add up", body = ErrorDetails),
+        (status = 404, description = "Reward not found", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn split(
+    Path(id): Path<Uuid>,
+    payload: Json<serde_json::Value>,
+) -> Result<Response, ErrorResponse> {
+    let request: SplitRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+    let mut reward = RewardNFT::from_id(id.to_string()).await?;
+
+    let values = match (request.values, request.parts) {
+        (Some(values), None) => {
+            let mut units = Vec::with_capacity(values.len());
+
+            for value in values {
+                units.push(value.to_token_units().await?);
+            }
+
+            units
+        }
+        (None, Some(parts)) if parts > max_split_parts() => {
+            return Err(ErrorResponse::from(format!(
+                "A reward can be split into at most {} rewards",
+                max_split_parts()
+            )))
+        }
+        (None, Some(parts)) => split_evenly(reward.get_value(), parts),
+        _ => return Err(ErrorResponse::from(String::from("Invalid payload"))),
+    };
+
+    match reward.split(values).await {
+        Ok(children) => Ok(Json(
+            children
+                .into_iter()
+                .map(RewardInfo::from)
+                .collect::<Vec<RewardInfo>>(),
+        )
+        .into_response()),
+        Err(e) => queued(e),
+    }
+}
+
+/// Split a value into parts of equal value, the first part taking what is
+/// left over.
+fn split_evenly(value: U256, parts: usize) -> Vec<U256> {
+    if parts == 0 {
+        return vec![];
+    }
+
+    let part = value / parts;
+    let mut values = vec![part; parts];
+    values[0] += value % parts;
+
+    values
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RevokeRequest {
+    /// Why the reward is revoked.
+    pub reason: String,
+    /// Who revokes the reward.
+    pub actor: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/reward/{id}/revoke",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the reward")),
+    request_body = RevokeRequest,
+    responses(
+        (status = 200, description = "The revoked reward", body = RewardInfo),
+        (status = 202, description = "The burn was queued and is sent later", body = QueuedResult),
+        (status = 400, description = "A reason and actor are required", body = ErrorDetails),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 404, description = "Reward not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn revoke(
+    Path(id): Path<Uuid>,
+    payload: Json<serde_json::Value>,
+) -> Result<Response, ErrorResponse> {
+    let request: RevokeRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+
+    if request.reason.trim().is_empty() || request.actor.trim().is_empty() {
+        return Err(ErrorResponse::from(String::from(
+            "A reason and actor are required",
+        )));
+    }
+
+    let mut reward = RewardNFT::from_id(id.to_string()).await?;
+
+    // Burn the token without crediting the owner
+    match reward.revoke(request.reason, request.actor).await {
+        Ok(()) => Ok(Json(RewardInfo::from(reward)).into_response()),
+        Err(e) => queued(e),
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TransferRequest {
+    /// The user the reward is transferred to.
+    pub to: Uuid,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/reward/{id}/transfer",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the reward")),
+    request_body = TransferRequest,
+    responses(
+        (status = 200, description = "The transferred reward", body = RewardInfo),
+        (status = 202, description = "The transfer was queued and is sent later", body = QueuedResult),
+        (status = 400, description = "The reward cannot be transferred", body = ErrorDetails),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorDetails),
+        (status = 404, description = "Reward or recipient not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn transfer(
+    Path(id): Path<Uuid>,
+    payload: Json<serde_json::Value>,
+) -> Result<Response, ErrorResponse> {
+    let request: TransferRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+
+    let mut reward = RewardNFT::from_id(id.to_string()).await?;
+    let recipient = User::from_id(request.to.to_string()).await?;
+
+    // Send the token from the owner's wallet to the recipient's
+    match reward.transfer(&recipient).await {
+        Ok(_) => Ok(Json(RewardInfo::from(reward)).into_response()),
+        Err(e) => queued(e),
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BatchRewardRequest {
+    pub user_id: Uuid,
+    pub value: Amount,
+    /// The campaign the reward is issued from.
+    #[serde(default)]
+    pub campaign_id: Option<Uuid>,
+    /// When the reward expires, as a unix timestamp.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+/// BatchRewardStatus is the outcome of one reward of a batch.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum BatchRewardStatus {
+    /// The reward was minted and saved.
+    Issued,
+    /// The mint could not be sent yet, so the reward was queued as a job,
+    /// which issues it later. It must not be requested again.
+    Queued,
+    /// The reward could not be issued.
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BatchRewardItem {
+    pub user_id: Uuid,
+    pub status: BatchRewardStatus,
+    /// If the reward was issued.
+    pub success: bool,
+    /// The id of the reward, also known for a queued reward.
+    pub id: Option<String>,
+    pub url: Option<String>,
+    /// The job issuing a queued reward, which can be polled.
+    pub job_id: Option<Uuid>,
+    pub error: Option<ErrorDetails>,
+}
+
+impl BatchRewardItem {
+    /// Get the outcome of one reward of a batch. A reward whose mint could
+    /// not be sent yet is handed to the job worker, which reuses its outbox
+    /// entry, as a single reward is.
+    async fn new(
+        user_id: Uuid,
+        value: U256,
+        options: IssueOptions,
+        result: Result<RewardNFT, RewardError>,
+    ) -> Self {
+        let error = match result {
+            Ok(reward) => {
+                return Self {
+                    user_id,
+                    status: BatchRewardStatus::Issued,
+                    success: true,
+                    id: Some(reward.get_id()),
+                    url: Some(reward.get_url()),
+                    job_id: None,
+                    error: None,
+                }
+            }
+            Err(RewardError::Queued(entry_id)) => {
+                match queue_job(user_id, value, options, entry_id).await {
+                    Ok((job, reward)) => {
+                        return Self {
+                            user_id,
+                            status: BatchRewardStatus::Queued,
+                            success: false,
+                            id: reward.as_ref().map(RewardNFT::get_id),
+                            url: reward.as_ref().map(RewardNFT::get_url),
+                            job_id: Some(job.id),
+                            error: None,
+                        }
+                    }
+                    Err(e) => e,
+                }
+            }
+            Err(e) => ErrorResponse::from(e),
+        };
+
+        Self {
+            user_id,
+            status: BatchRewardStatus::Failed,
+            success: false,
+            id: None,
+            url: None,
+            job_id: None,
+            error: Some(error.error),
+        }
+    }
+}
+
+/// Queue a job issuing a reward with the outbox entry of its mint, returning
+/// the job and the reward being minted.
+async fn queue_job(
+    user_id: Uuid,
+    value: U256,
+    options: IssueOptions,
+    entry_id: Uuid,
+) -> Result<(Job, Option<RewardNFT>), ErrorResponse> {
+    let mut job = Job::new(user_id, value).with_options(options);
+    job.outbox_id = Some(entry_id);
+    job.save().await?;
+
+    let reward = match OutboxEntry::from_id(entry_id).await?.action {
+        OutboxAction::Mint { reward, .. } => Some(*reward),
+        _ => None,
+    };
+
+    Ok((job, reward))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BatchRewardResult {
+    /// If every reward in the batch was issued. Queued rewards are issued
+    /// later, so they are not counted as issued.
+    pub success: bool,
+    /// The outcome of each reward, in the order requested.
+    pub results: Vec<BatchRewardItem>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/rewards/batch",
+    tag = "rewards",
+    request_body = [BatchRewardRequest],
+    responses(
+        (status = 200, description = "The outcome of each reward", body = BatchRewardResult),
+        (status = 400, description = "Invalid payload or unknown users", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn batch_reward(
+    payload: Json<serde_json::Value>,
+) -> Result<Json<BatchRewardResult>, ErrorResponse> {
+    let requests: Vec<BatchRewardRequest> = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+    let max_size = parse_env("REWARD_BATCH_MAX_SIZE", 100);
+
+    if requests.is_empty() || requests.len() > max_size {
+        return Err(ErrorResponse::from(format!(
+            "A batch must have between 1 and {} rewards",
+            max_size
+        )));
+    }
+
+    // Validate every user before anything is minted
+    let mut items = Vec::with_capacity(requests.len());
+    let mut unknown = Vec::new();
+
+    for request in &requests {
+        let options = IssueOptions {
+            campaign_id: request.campaign_id,
+            expires_at: request.expires_at,
+        };
+
+        options.validate()?;
+
+        let value = request.value.to_token_units().await?;
+
+        match User::from_id(request.user_id.to_string()).await {
+            Ok(user) => items.push((user, value, options)),
+            Err(UserError::NotFound) => unknown.push(request.user_id.to_string()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if !unknown.is_empty() {
+        return Err(ErrorResponse::from(format!(
+            "Unknown users: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    let issued: Vec<(U256, IssueOptions)> = items
+        .iter()
+        .map(|(_, value, options)| (*value, *options))
+        .collect();
+    let mut results = Vec::with_capacity(requests.len());
+
+    for ((reward, request), (value, options)) in RewardNFT::issue_batch(items)
+        .await
+        .into_iter()
+        .zip(requests)
+        .zip(issued)
+    {
+        results.push(BatchRewardItem::new(request.user_id, value, options, reward).await);
+    }
+
+    Ok(Json(BatchRewardResult {
+        success: results.iter().all(|result| result.success),
+        results,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use crate::services::user::{register, reward, RewardResult};
+    use axum::http::StatusCode;
+
+    use crate::services::user::{register, reward, RewardOptions, RewardResult};
+    use crate::services::{read_json, QueuedResult};
 
     use super::*;
     use uuid::Uuid;
@@ -42,20 +550,351 @@ mod tests {
 
         // Reward the user
         let value = 100;
-        let result: Result<Json<RewardResult>, ErrorResponse> =
-            reward(Path(user_id), Json(serde_json::json!({ "value": value }))).await;
-        let reward_id = Uuid::from_str(&result.unwrap().id).unwrap();
+        let result = reward(
+            Path(user_id),
+            Query(RewardOptions::default()),
+            Json(serde_json::json!({ "value": value })),
+        )
+        .await;
+        let result: RewardResult = read_json(result.unwrap()).await;
+        let reward_id = Uuid::from_str(&result.id).unwrap();
+
+        // Simulate the redeem
+        let result = redeem(
+            Path(reward_id),
+            Query(RedeemOptions {
+                dry_run: true,
+                ..Default::default()
+            }),
+        )
+        .await;
+        let dry_run_result: DryRunResult = read_json(result.unwrap()).await;
+
+        assert!(dry_run_result.success);
+        assert!(dry_run_result.gas_estimate.is_some());
 
         // Call the redeem function
-        let result = redeem(Path(reward_id)).await;
+        let result = redeem(Path(reward_id), Query(RedeemOptions::default())).await;
 
         // Check that the function returned Ok
         assert!(result.is_ok());
 
         // Check that the returned RedeemResult is correct
-        let redeem_result = result.unwrap();
+        let redeem_result: RedeemResult = read_json(result.unwrap()).await;
 
         assert_eq!(redeem_result.id, reward_id);
         assert_eq!(redeem_result.reward, value.to_string());
     }
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let user_id = Uuid::new_v4();
+        assert!(register(Json(serde_json::json!({ "id": user_id })))
+            .await
+            .is_ok());
+
+        let result = reward(
+            Path(user_id),
+            Query(RewardOptions::default()),
+            Json(serde_json::json!({ "value": 100 })),
+        )
+        .await;
+        let result: RewardResult = read_json(result.unwrap()).await;
+        let reward_id = Uuid::from_str(&result.id).unwrap();
+
+        // A reason is required
+        let result = revoke(
+            Path(reward_id),
+            Json(serde_json::json!({ "reason": "", "actor": "support" })),
+        )
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        let result = revoke(
+            Path(reward_id),
+            Json(serde_json::json!({ "reason": "Wrong user", "actor": "support" })),
+        )
+        .await;
+        let info: RewardInfo = read_json(result.unwrap()).await;
+
+        assert_eq!(info.status, RewardStatus::Revoked);
+        assert_eq!(info.revocation.unwrap().reason, "Wrong user");
+
+        // The revoked reward can no longer be redeemed
+        let result = redeem(Path(reward_id), Query(RedeemOptions::default())).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let (from, to) = (Uuid::new_v4(), Uuid::new_v4());
+
+        for user_id in [from, to] {
+            assert!(register(Json(serde_json::json!({ "id": user_id })))
+                .await
+                .is_ok());
+        }
+
+        let result = reward(
+            Path(from),
+            Query(RewardOptions::default()),
+            Json(serde_json::json!({ "value": 100 })),
+        )
+        .await;
+        let result: RewardResult = read_json(result.unwrap()).await;
+        let reward_id = Uuid::from_str(&result.id).unwrap();
+
+        // Unknown recipients are not found
+        let result = transfer(
+            Path(reward_id),
+            Json(serde_json::json!({ "to": Uuid::new_v4() })),
+        )
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::NOT_FOUND);
+
+        let result = transfer(Path(reward_id), Json(serde_json::json!({ "to": to }))).await;
+        let info: RewardInfo = read_json(result.unwrap()).await;
+
+        // The reward belongs to the recipient and the transfer is recorded
+        assert_eq!(info.owner, to);
+        assert_eq!(info.transfers.len(), 1);
+        assert_eq!(info.transfers[0].from, from);
+    }
+
+    #[tokio::test]
+    async fn test_queued() {
+        let id = Uuid::new_v4();
+
+        // Queued actions are only accepted through `queued`
+        let response = queued(RewardError::Queued(id)).unwrap();
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(read_json::<QueuedResult>(response).await.id, id);
+        assert_eq!(
+            ErrorResponse::from(RewardError::Queued(id)).status,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            queued(RewardError::NotFound).err().unwrap().status,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    async fn issue(value: u128) -> Uuid {
+        let user_id = Uuid::new_v4();
+        assert!(register(Json(serde_json::json!({ "id": user_id })))
+            .await
+            .is_ok());
+
+        let result = reward(
+            Path(user_id),
+            Query(RewardOptions::default()),
+            Json(serde_json::json!({ "value": value })),
+        )
+        .await;
+        let result: RewardResult = read_json(result.unwrap()).await;
+
+        Uuid::from_str(&result.id).unwrap()
+    }
+
+    #[test]
+    fn test_redeem_options_query() {
+        let query = |uri: &str| {
+            Query::<RedeemOptions>::try_from_uri(&uri.parse().unwrap())
+                .unwrap()
+                .amount()
+        };
+
+        // Values past 128 bits are read in full
+        assert_eq!(
+            query("/?value=340282366920938463463374607431768211456").unwrap(),
+            Some(Amount::Raw(
+                "340282366920938463463374607431768211456".into()
+            ))
+        );
+        assert_eq!(
+            query("/?decimal=2.5").unwrap(),
+            Some(Amount::Decimal("2.5".into()))
+        );
+        assert_eq!(query("/?dry_run=true").unwrap(), None);
+        assert!(query("/?value=1&decimal=1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redeem_partial() {
+        let reward_id = issue(100).await;
+        let options = |value: u128| RedeemOptions::partial(value.into());
+
+        // The whole value cannot be redeemed in part
+        let result = redeem(Path(reward_id), Query(options(100))).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        let result = redeem(Path(reward_id), Query(options(30))).await;
+        let result: RedeemResult = read_json(result.unwrap()).await;
+
+        assert_eq!(result.reward, "30");
+
+        // Decimal amounts are converted with the decimals of the token
+        let remainder = Uuid::from_str(&result.remainder.unwrap()).unwrap();
+        let options = RedeemOptions::partial(Amount::Decimal("0.00000000000000002".into()));
+        let result = redeem(Path(remainder), Query(options)).await;
+        let result: RedeemResult = read_json(result.unwrap()).await;
+
+        assert_eq!(result.reward, "20");
+
+        // Invalid amounts are rejected
+        let remainder = Uuid::from_str(&result.remainder.unwrap()).unwrap();
+        let options = RedeemOptions::partial(Amount::Raw("-1".into()));
+        let result = redeem(Path(remainder), Query(options)).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        // The rest can be redeemed from the new reward
+        let result = redeem(Path(remainder), Query(RedeemOptions::default())).await;
+        let result: RedeemResult = read_json(result.unwrap()).await;
+
+        assert_eq!(result.reward, "50");
+        assert!(result.remainder.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_split() {
+        let reward_id = issue(100).await;
+
+        let result = split(Path(reward_id), Json(serde_json::json!({ "parts": 3 }))).await;
+        let children: Vec<RewardInfo> = read_json(result.unwrap()).await;
+
+        // The parts add up to the value of the reward
+        let values: Vec<&str> = children.iter().map(|c| c.value.as_str()).collect();
+
+        assert_eq!(values, ["34", "33", "33"]);
+        assert!(children.iter().all(|c| c.parent_id == Some(reward_id)));
+
+        // A child can be split again by value
+        let child_id = Uuid::from_str(&children[0].id).unwrap();
+        let result = split(
+            Path(child_id),
+            Json(serde_json::json!({ "values": [30, { "raw": "4" }] })),
+        )
+        .await;
+        let grandchildren: Vec<RewardInfo> = read_json(result.unwrap()).await;
+
+        assert_eq!(grandchildren.len(), 2);
+
+        // The values must add up to the value of the reward
+        let child_id = Uuid::from_str(&children[1].id).unwrap();
+        let result = split(
+            Path(child_id),
+            Json(serde_json::json!({ "values": [30, 30] })),
+        )
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        // Too many parts are rejected before anything is allocated
+        let result = split(
+            Path(child_id),
+            Json(serde_json::json!({ "parts": usize::MAX })),
+        )
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_split_evenly() {
+        assert_eq!(
+            split_evenly(U256::from(10), 3),
+            [U256::from(4), U256::from(3), U256::from(3)]
+        );
+        assert!(split_evenly(U256::from(10), 0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_reward() {
+        let user_ids = [Uuid::new_v4(), Uuid::new_v4()];
+
+        for user_id in user_ids {
+            assert!(register(Json(serde_json::json!({ "id": user_id })))
+                .await
+                .is_ok());
+        }
+
+        let result = batch_reward(Json(serde_json::json!([
+            { "user_id": user_ids[0], "value": 10 },
+            { "user_id": user_ids[1], "value": 20 },
+        ])))
+        .await
+        .unwrap();
+
+        // Each reward has its own result, in the order requested
+        assert!(result.success);
+        assert_eq!(result.results.len(), 2);
+
+        for (item, user_id) in result.results.iter().zip(user_ids) {
+            assert_eq!(item.user_id, user_id);
+            assert_eq!(item.status, BatchRewardStatus::Issued);
+            assert!(item.success);
+            assert!(item.id.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_reward_queued() {
+        let user_id = Uuid::new_v4();
+        let user = User::new(user_id, crate::core::chain::generate_secret_key());
+        let entry = RewardNFT::prepare_issue(user, U256::from(10), IssueOptions::default())
+            .await
+            .unwrap();
+        let reward_id = match &entry.action {
+            OutboxAction::Mint { reward, .. } => reward.get_id(),
+            _ => unreachable!(),
+        };
+
+        // A mint that could not be sent yet is queued as a job reusing its
+        // outbox entry, and reported with the id of the reward
+        let item = BatchRewardItem::new(
+            user_id,
+            U256::from(10),
+            IssueOptions::default(),
+            Err(RewardError::Queued(entry.id)),
+        )
+        .await;
+
+        assert_eq!(item.status, BatchRewardStatus::Queued);
+        assert!(!item.success);
+        assert_eq!(item.id, Some(reward_id));
+        assert!(item.error.is_none());
+
+        let job = Job::from_id(item.job_id.unwrap()).await.unwrap();
+
+        assert_eq!(job.outbox_id, Some(entry.id));
+        assert_eq!(job.user_id, user_id);
+    }
+
+    #[tokio::test]
+    async fn test_batch_reward_invalid() {
+        let user_id = Uuid::new_v4();
+        assert!(register(Json(serde_json::json!({ "id": user_id })))
+            .await
+            .is_ok());
+
+        // Nothing is issued if any user is unknown
+        let result = batch_reward(Json(serde_json::json!([
+            { "user_id": user_id, "value": 10 },
+            { "user_id": Uuid::new_v4(), "value": 20 },
+        ])))
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        // Empty batches are rejected
+        let result = batch_reward(Json(serde_json::json!([]))).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+    }
 }
@@ -1,15 +1,40 @@
 use axum::Json;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::core::retry::{rpc_circuit, CircuitState};
 
 /// Status contains the version of the application and the current time.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Status {
     pub version: String,
+    pub rpc: RpcStatus,
+}
+
+/// RpcStatus contains the state of the circuit breaker for RPC calls.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RpcStatus {
+    pub circuit: CircuitState,
+    pub failures: u32,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/status",
+    tag = "status",
+    responses(
+        (status = 200, description = "The version and RPC health of the API", body = Status),
+    ),
+)]
 #[axum::debug_handler]
 pub async fn status() -> Json<Status> {
+    let circuit = rpc_circuit();
+
     Json(Status {
         version: crate::VERSION.into(),
+        rpc: RpcStatus {
+            circuit: circuit.state(),
+            failures: circuit.failures(),
+        },
     })
 }
@@ -1,25 +1,52 @@
-use axum::extract::Path;
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use axum::Json;
-use ethers::types::U256;
 use serde::{Deserialize, Serialize};
+use utoipa::openapi::schema::{OneOfBuilder, Schema};
+use utoipa::openapi::{Ref, RefOr};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::models::reward::RewardNFT;
+use crate::core::amount::Amount;
+use crate::core::reward::RewardError;
+use crate::models::activity::{Activity, ActivityKind};
+use crate::models::balance::{get_token_metadata, Balance, RewardCounts};
+use crate::models::campaign::Campaign;
+use crate::models::job::Job;
+use crate::models::reward::{IssueOptions, RewardNFT};
+use crate::models::user::UserError;
+use crate::models::withdrawal::{
+    self, parse_address, Withdrawal, WithdrawalConfig, WithdrawalStatus,
+};
 use crate::rewards::Reward;
+use crate::workers::jobs::notify_queued;
 use crate::{core::chain::generate_secret_key, models::user::User};
 
-use super::ErrorResponse;
+use super::job::JobResult;
+use super::reward::RewardInfo;
+use super::{DryRunResult, ErrorResponse};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct RegisterRequest {
     pub id: Uuid,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct RegisterResult {
     pub success: bool,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/user",
+    tag = "users",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "The user was registered", body = RegisterResult),
+        (status = 400, description = "Invalid payload or the user already exists", body = ErrorDetails),
+    ),
+)]
 #[axum::debug_handler]
 pub async fn register(
     payload: Json<serde_json::Value>,
@@ -43,59 +70,398 @@ pub async fn register(
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct BalanceResult {
+    /// The reward token balance of the user's wallet, in the smallest unit.
     pub balance: String,
+    /// The balance in whole tokens with the token symbol, such as `1.5 RWD`.
+    pub formatted: String,
+    pub symbol: String,
+    pub decimals: u8,
+    /// The total value of the rewards that can still be redeemed.
+    pub unredeemed_value: String,
+    /// The number of rewards in each state.
+    pub rewards: RewardCounts,
 }
 
+impl From<Balance> for BalanceResult {
+    fn from(balance: Balance) -> Self {
+        Self {
+            formatted: balance.formatted(),
+            balance: balance.tokens.to_string(),
+            symbol: balance.metadata.symbol,
+            decimals: balance.metadata.decimals,
+            unredeemed_value: balance.unredeemed.to_string(),
+            rewards: balance.counts,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{id}/balance",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "The id of the user")),
+    responses(
+        (status = 200, description = "The balance of the user", body = BalanceResult),
+        (status = 404, description = "User not found", body = ErrorDetails),
+    ),
+)]
 #[axum::debug_handler]
 pub async fn get_balance(Path(id): Path<Uuid>) -> Result<Json<BalanceResult>, ErrorResponse> {
     // Get the user from the repository
     let user = User::from_id(id.to_string()).await?;
-    // Get the user's balance of rewards
-    let balance = user.get_reward_balance().await?.to_string();
+    // Get the user's balance of reward tokens and rewards
+    let balance = Balance::for_user(&user).await?;
+
+    Ok(Json(BalanceResult::from(balance)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{id}/rewards",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "The id of the user")),
+    responses(
+        (status = 200, description = "The rewards of the user", body = [RewardInfo]),
+        (status = 404, description = "User not found", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn get_rewards(Path(id): Path<Uuid>) -> Result<Json<Vec<RewardInfo>>, ErrorResponse> {
+    // Check that the user exists
+    let user = User::from_id(id.to_string()).await?;
+    let rewards = RewardNFT::by_owner(user.id).await?;
+
+    Ok(Json(rewards.into_iter().map(RewardInfo::from).collect()))
+}
 
-    Ok(Json(BalanceResult { balance }))
+/// The number of history entries returned when no limit is given.
+const HISTORY_DEFAULT_LIMIT: usize = 50;
+
+/// The most history entries returned at once.
+const HISTORY_MAX_LIMIT: usize = 200;
+
+#[derive(Serialize, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryOptions {
+    /// Only return entries of this kind, such as `minted` or
+    /// `transfer_sent`.
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// The number of entries to skip.
+    #[serde(default)]
+    pub offset: usize,
+    /// The most entries to return.
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ActivityInfo {
+    pub id: Uuid,
+    pub kind: ActivityKind,
+    pub reward_id: Option<Uuid>,
+    pub amount: Option<String>,
+    pub tx_hash: Option<String>,
+    pub counterparty: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: u64,
+}
+
+impl From<Activity> for ActivityInfo {
+    fn from(activity: Activity) -> Self {
+        Self {
+            id: activity.id,
+            kind: activity.kind,
+            reward_id: activity.reward_id,
+            amount: activity.amount.map(|amount| amount.to_string()),
+            tx_hash: activity.tx_hash,
+            counterparty: activity.counterparty,
+            detail: activity.detail,
+            created_at: activity.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct HistoryResult {
+    /// The number of entries matching the filter.
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    /// The entries of the page, newest first.
+    pub entries: Vec<ActivityInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{id}/history",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "The id of the user"), HistoryOptions),
+    responses(
+        (status = 200, description = "A page of the history of the user, newest first", body = HistoryResult),
+        (status = 400, description = "Unknown activity kind", body = ErrorDetails),
+        (status = 404, description = "User not found", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn get_history(
+    Path(id): Path<Uuid>,
+    Query(options): Query<HistoryOptions>,
+) -> Result<Json<HistoryResult>, ErrorResponse> {
+    let kind = match options.kind {
+        Some(kind) => Some(kind.parse::<ActivityKind>()?),
+        None => None,
+    };
+    let limit = options
+        .limit
+        .unwrap_or(HISTORY_DEFAULT_LIMIT)
+        .min(HISTORY_MAX_LIMIT);
+
+    // Check that the user exists
+    let user = User::from_id(id.to_string()).await?;
+    let entries: Vec<Activity> = Activity::for_user(user.id)
+        .await
+        .map_err(UserError::from)?
+        .into_iter()
+        .rev()
+        .filter(|activity| kind.is_none_or(|kind| activity.kind == kind))
+        .collect();
+
+    Ok(Json(HistoryResult {
+        total: entries.len(),
+        offset: options.offset,
+        limit,
+        entries: entries
+            .into_iter()
+            .skip(options.offset)
+            .take(limit)
+            .map(ActivityInfo::from)
+            .collect(),
+    }))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WithdrawRequest {
+    /// The external address the reward tokens are sent to.
+    pub to: String,
+    pub amount: Amount,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WithdrawalResult {
+    pub id: Uuid,
+    pub to: String,
+    pub amount: String,
+    pub status: WithdrawalStatus,
+    pub tx_hash: Option<String>,
+    pub error: Option<String>,
+    pub created_at: u64,
+}
+
+impl From<Withdrawal> for WithdrawalResult {
+    fn from(withdrawal: Withdrawal) -> Self {
+        Self {
+            id: withdrawal.id,
+            to: format!("{:#x}", withdrawal.to),
+            amount: withdrawal.amount.to_string(),
+            status: withdrawal.status,
+            tx_hash: withdrawal.tx_hash,
+            error: withdrawal.error,
+            created_at: withdrawal.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/{id}/withdraw",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the user")),
+    request_body = WithdrawRequest,
+    responses(
+        (status = 201, description = "The reward tokens were sent", body = WithdrawalResult),
+        (status = 202, description = "The transfer was queued and is sent later", body = WithdrawalResult),
+        (status = 400, description = "Invalid address or amount", body = ErrorDetails),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorDetails),
+        (status = 404, description = "User not found", body = ErrorDetails),
+        (status = 409, description = "The balance of the user is too low", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn withdraw(
+    Path(id): Path<Uuid>,
+    payload: Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<WithdrawalResult>), ErrorResponse> {
+    let request: WithdrawRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+    let to = parse_address(&request.to)?;
+    let amount = request.amount.to_token_units().await?;
+    let user = User::from_id(id.to_string()).await?;
+
+    let decimals = get_token_metadata().await?.decimals;
+
+    let withdrawal =
+        withdrawal::withdraw(&user, to, amount, &WithdrawalConfig::from_env(decimals)).await?;
+
+    let status = match withdrawal.status {
+        WithdrawalStatus::Pending => StatusCode::ACCEPTED,
+        WithdrawalStatus::Confirmed | WithdrawalStatus::Failed => StatusCode::CREATED,
+    };
+
+    Ok((status, Json(WithdrawalResult::from(withdrawal))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{id}/withdrawals",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "The id of the user")),
+    responses(
+        (status = 200, description = "The withdrawals of the user", body = [WithdrawalResult]),
+        (status = 404, description = "User not found", body = ErrorDetails),
+    ),
+)]
+#[axum::debug_handler]
+pub async fn get_withdrawals(
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<WithdrawalResult>>, ErrorResponse> {
+    // Check that the user exists
+    let user = User::from_id(id.to_string()).await?;
+    let withdrawals = Withdrawal::for_user(user.id).await?;
+
+    Ok(Json(
+        withdrawals
+            .into_iter()
+            .map(WithdrawalResult::from)
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct RewardRequest {
-    pub value: u128,
+    pub value: Amount,
+    /// The campaign the reward is issued from.
+    #[serde(default)]
+    pub campaign_id: Option<Uuid>,
+    /// When the reward expires, as a unix timestamp.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct RewardResult {
     pub success: bool,
     pub id: String,
     pub url: String,
 }
 
+/// RewardResponse is the body of a successful reward request, which is the
+/// outcome of the simulation with `dry_run`.
+pub struct RewardResponse;
+
+impl<'s> ToSchema<'s> for RewardResponse {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let schema = OneOfBuilder::new()
+            .description(Some(
+                "The minted reward, or the outcome of the simulation with `dry_run`",
+            ))
+            .item(Ref::from_schema_name("RewardResult"))
+            .item(Ref::from_schema_name("DryRunResult"));
+
+        ("RewardResponse", schema.into())
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, IntoParams)]
+pub struct RewardOptions {
+    /// Queue the reward as a job instead of waiting for the mint.
+    #[serde(default, rename = "async")]
+    pub is_async: bool,
+    /// Simulate the mint without sending it or saving anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/{id}/reward",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "The id of the user"), RewardOptions),
+    request_body = RewardRequest,
+    responses(
+        (status = 200, description = "The reward was minted, or the outcome of the simulation with `dry_run`", body = RewardResponse),
+        (status = 202, description = "The reward was queued as a job with `async`, or because the mint could not be sent yet", body = JobResult),
+        (status = 400, description = "Invalid payload", body = ErrorDetails),
+        (status = 404, description = "User or campaign not found", body = ErrorDetails),
+    ),
+)]
 #[axum::debug_handler]
 pub async fn reward(
     Path(id): Path<Uuid>,
+    Query(options): Query<RewardOptions>,
     payload: Json<serde_json::Value>,
-) -> Result<Json<RewardResult>, ErrorResponse> {
+) -> Result<Response, ErrorResponse> {
     // TODO validate payload
     let request: Result<RewardRequest, serde_json::Error> = serde_json::from_value(payload.0);
 
     if let Ok(data) = request {
-        let value = data.value;
+        let token_value = data.value.to_token_units().await?;
         // Get the user from the repository
         let user = User::from_id(id.to_string()).await?;
-        let user_wallet = user.get_wallet()?;
-        let token_value = U256::from(value);
-        // Mint the reward
-        let token_id = RewardNFT::mint(token_value, &user_wallet).await?;
-        // Reward the user
-        let reward = RewardNFT::new(user, token_value, token_id);
+        let issue_options = IssueOptions {
+            campaign_id: data.campaign_id,
+            expires_at: data.expires_at,
+        };
+
+        issue_options.validate()?;
+
+        // Check the campaign before anything is simulated or queued
+        if let Some(campaign_id) = data.campaign_id {
+            Campaign::from_id(campaign_id)
+                .await?
+                .check(&user.id, token_value)?;
+        }
+
+        if options.dry_run {
+            let simulation = RewardNFT::simulate_issue(user, token_value).await;
 
-        reward.save(true).await?;
+            return Ok(Json(DryRunResult::from_simulation(simulation)?).into_response());
+        }
+
+        if options.is_async {
+            // Queue the reward for the job worker
+            let job = Job::new(user.id, token_value).with_options(issue_options);
+
+            job.save().await?;
+            notify_queued();
+
+            return Ok((StatusCode::ACCEPTED, Json(JobResult::from(job))).into_response());
+        }
 
-        Ok(Json(RewardResult {
-            success: true,
-            id: reward.get_id(),
-            url: reward.get_url(),
-        }))
+        // Mint the reward and save it once the mint is confirmed
+        let user_id = user.id;
+
+        match RewardNFT::issue(user, token_value, issue_options).await {
+            Ok(reward) => Ok(Json(RewardResult {
+                success: true,
+                id: reward.get_id(),
+                url: reward.get_url(),
+            })
+            .into_response()),
+            // The mint could not be sent yet, so hand it to the job worker,
+            // which reuses the outbox entry
+            Err(RewardError::Queued(entry_id)) => {
+                let mut job = Job::new(user_id, token_value).with_options(issue_options);
+                job.outbox_id = Some(entry_id);
+                job.save().await?;
+
+                Ok((StatusCode::ACCEPTED, Json(JobResult::from(job))).into_response())
+            }
+            Err(e) => Err(e.into()),
+        }
     } else {
         Err(ErrorResponse::from(String::from("Invalid payload")))
     }
@@ -104,7 +470,9 @@ pub async fn reward(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::{http::StatusCode, Json};
+    use crate::services::read_json;
+    use axum::Json;
+    use ethers::types::U256;
     use serde_json::json;
     use uuid::Uuid;
 
@@ -174,6 +542,200 @@ mod tests {
         assert!(!result.unwrap().0.balance.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_get_balance_rewards() {
+        let id = Uuid::new_v4();
+
+        assert!(register_user(id.to_string()).await.is_ok());
+
+        for value in [10, 20] {
+            let result = reward(
+                Path(id),
+                Query(RewardOptions::default()),
+                Json(json!({ "value": value })),
+            )
+            .await;
+
+            assert!(result.is_ok());
+        }
+
+        let Json(result) = get_balance(Path(id)).await.unwrap();
+
+        // The test wallet holds 100 of the smallest unit
+        assert_eq!(result.balance, "100");
+        assert_eq!(result.formatted, "0.0000000000000001 RWD");
+        assert_eq!(result.unredeemed_value, "30");
+        assert_eq!(result.rewards.active, 2);
+        assert_eq!(result.rewards.redeemed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_withdraw() {
+        let id = Uuid::new_v4();
+
+        assert!(register_user(id.to_string()).await.is_ok());
+
+        // The balance of test users is 100 of the smallest unit
+        std::env::set_var("WITHDRAW_MIN_AMOUNT", "0.000000000000000001");
+
+        let to = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let (status, Json(result)) = withdraw(Path(id), Json(json!({ "to": to, "amount": 40 })))
+            .await
+            .unwrap();
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(result.to, to.to_lowercase());
+        assert_eq!(result.status, WithdrawalStatus::Confirmed);
+        assert!(result.tx_hash.is_some());
+
+        // Invalid addresses are rejected
+        let result = withdraw(Path(id), Json(json!({ "to": "0x1234", "amount": 40 }))).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        // The withdrawal is listed
+        let Json(withdrawals) = get_withdrawals(Path(id)).await.unwrap();
+
+        assert_eq!(withdrawals.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reward_decimal_value() {
+        let id = Uuid::new_v4();
+
+        assert!(register_user(id.to_string()).await.is_ok());
+
+        let reward_value = |value| {
+            reward(
+                Path(id),
+                Query(RewardOptions::default()),
+                Json(json!({ "value": value })),
+            )
+        };
+
+        // Decimal values are converted with the decimals of the token
+        let result = reward_value(json!({ "decimal": "2.5" })).await;
+        let result: RewardResult = read_json(result.unwrap()).await;
+        let reward = RewardNFT::from_id(result.id).await.unwrap();
+
+        assert_eq!(reward.get_value(), U256::exp10(17) * 25);
+
+        // Raw values above u128 are accepted
+        let raw = (U256::from(u128::MAX) + 1).to_string();
+
+        assert!(reward_value(json!({ "raw": raw })).await.is_ok());
+
+        // Values with too many decimals or too large for a U256 are rejected
+        let error = reward_value(json!({ "decimal": "0.0000000000000000001" }))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error.error.message,
+            "Amount has more than 18 decimal places"
+        );
+
+        let error = reward_value(json!({ "raw": format!("{}0", U256::MAX) }))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.error.message, "Amount is too large");
+    }
+
+    #[tokio::test]
+    async fn test_get_history() {
+        let id = Uuid::new_v4();
+
+        assert!(register_user(id.to_string()).await.is_ok());
+
+        for value in [10, 20, 30] {
+            let result = reward(
+                Path(id),
+                Query(RewardOptions::default()),
+                Json(json!({ "value": value })),
+            )
+            .await;
+
+            assert!(result.is_ok());
+        }
+
+        let history = |kind: Option<&str>, offset, limit| {
+            get_history(
+                Path(id),
+                Query(HistoryOptions {
+                    kind: kind.map(String::from),
+                    offset,
+                    limit,
+                }),
+            )
+        };
+
+        // Each reward is issued and minted
+        let Json(result) = history(None, 0, None).await.unwrap();
+
+        assert_eq!(result.total, 6);
+
+        // The newest entries come first
+        let Json(result) = history(Some("minted"), 0, Some(2)).await.unwrap();
+        let amounts: Vec<String> = result
+            .entries
+            .iter()
+            .map(|entry| entry.amount.clone().unwrap())
+            .collect();
+
+        assert_eq!(result.total, 3);
+        assert_eq!(amounts, ["30", "20"]);
+        assert!(result.entries[0].tx_hash.is_some());
+
+        let Json(result) = history(Some("minted"), 2, Some(2)).await.unwrap();
+
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].amount.as_deref(), Some("10"));
+
+        // Unknown kinds are rejected
+        let result = history(Some("unknown"), 0, None).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_rewards() {
+        let id = Uuid::new_v4();
+
+        assert!(register_user(id.to_string()).await.is_ok());
+
+        // A new user has no rewards
+        let Json(rewards) = get_rewards(Path(id)).await.unwrap();
+
+        assert!(rewards.is_empty());
+
+        let result = reward(
+            Path(id),
+            Query(RewardOptions::default()),
+            Json(json!({ "value": 100 })),
+        )
+        .await;
+        assert!(result.is_ok());
+
+        // The issued reward is listed with its state
+        let Json(rewards) = get_rewards(Path(id)).await.unwrap();
+
+        assert_eq!(rewards.len(), 1);
+        assert_eq!(rewards[0].value, "100");
+        assert_eq!(
+            rewards[0].status,
+            crate::models::reward::RewardStatus::Active
+        );
+
+        // Unknown users are not found
+        let result = get_rewards(Path(Uuid::new_v4())).await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_reward_success() {
         // Generate a unique UUID for this test
@@ -186,15 +748,104 @@ mod tests {
         assert!(result.is_ok());
 
         // Reward the user
-        let result = reward(Path(id), Json(json!({ "value": 100 }))).await;
+        let result = reward(
+            Path(id),
+            Query(RewardOptions::default()),
+            Json(json!({ "value": 100 })),
+        )
+        .await;
+
+        // Check the result
+        assert!(result.is_ok());
+
+        let response = result.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let result: RewardResult = read_json(response).await;
+
+        assert!(result.success);
+        assert!(!result.id.is_empty());
+        assert!(!result.url.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reward_async() {
+        // Generate a unique UUID for this test
+        let id = Uuid::new_v4();
+
+        // Register the user
+        let result = register_user(id.to_string().clone()).await;
+
+        // Check the result
+        assert!(result.is_ok());
+
+        // Queue the reward
+        let result = reward(
+            Path(id),
+            Query(RewardOptions {
+                is_async: true,
+                ..Default::default()
+            }),
+            Json(json!({ "value": 100 })),
+        )
+        .await;
+
+        // Check the result
+        let response = result.unwrap();
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let result: JobResult = read_json(response).await;
+        let job = Job::from_id(result.id).await.unwrap();
+
+        assert_eq!(job.user_id, id);
+        assert_eq!(job.value, U256::from(100));
+    }
+
+    #[tokio::test]
+    async fn test_reward_dry_run() {
+        // Generate a unique UUID for this test
+        let id = Uuid::new_v4();
+
+        // Register the user
+        let result = register_user(id.to_string().clone()).await;
 
         // Check the result
         assert!(result.is_ok());
 
-        let result = result.unwrap();
+        // Simulate the reward
+        let result = reward(
+            Path(id),
+            Query(RewardOptions {
+                dry_run: true,
+                ..Default::default()
+            }),
+            Json(json!({ "value": 100 })),
+        )
+        .await;
 
-        assert!(result.0.success);
-        assert!(!result.0.id.is_empty());
-        assert!(!result.0.url.is_empty());
+        // Check the result
+        let result: DryRunResult = read_json(result.unwrap()).await;
+
+        assert!(result.success);
+        assert!(result.gas_estimate.is_some());
+        assert!(result.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reward_unknown_user() {
+        // Unknown users are rejected before a job is queued
+        let result = reward(
+            Path(Uuid::new_v4()),
+            Query(RewardOptions {
+                is_async: true,
+                ..Default::default()
+            }),
+            Json(json!({ "value": 100 })),
+        )
+        .await;
+
+        assert_eq!(result.err().unwrap().status, StatusCode::NOT_FOUND);
     }
 }
@@ -0,0 +1,250 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::webhook::{Delivery, DeliveryStatus, Subscription, WebhookEvent};
+
+use super::ErrorResponse;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateSubscriptionRequest {
+    pub url: String,
+    /// The events to notify the endpoint of, such as `reward.minted`.
+    pub events: Vec<String>,
+    /// The secret payloads are signed with.
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionResult {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub active: bool,
+    pub created_at: u64,
+}
+
+impl From<Subscription> for SubscriptionResult {
+    fn from(subscription: Subscription) -> Self {
+        Self {
+            id: subscription.id,
+            url: subscription.url.clone(),
+            events: subscription.events.clone(),
+            active: subscription.active,
+            created_at: subscription.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DeliveryResult {
+    pub id: Uuid,
+    pub event: WebhookEvent,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub payload: serde_json::Value,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl From<Delivery> for DeliveryResult {
+    fn from(delivery: Delivery) -> Self {
+        Self {
+            id: delivery.id,
+            event: delivery.event,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            next_attempt_at: delivery.next_attempt_at,
+            response_status: delivery.response_status,
+            payload: serde_json::from_str(&delivery.payload).unwrap_or_default(),
+            error: delivery.error,
+            created_at: delivery.created_at,
+            updated_at: delivery.updated_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    request_body = CreateSubscriptionRequest,
+    responses(
+        (status = 201, description = "The new subscription", body = SubscriptionResult),
+        (status = 400, description = "Invalid URL, events or secret", body = ErrorDetails),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn create_subscription(
+    payload: Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<SubscriptionResult>), ErrorResponse> {
+    let request: CreateSubscriptionRequest = serde_json::from_value(payload.0)
+        .map_err(|_| ErrorResponse::from(String::from("Invalid payload")))?;
+    let events = request
+        .events
+        .iter()
+        .map(|event| event.parse())
+        .collect::<Result<Vec<WebhookEvent>, String>>()?;
+
+    let subscription = Subscription::new(request.url, events, request.secret)?;
+    subscription.save().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SubscriptionResult::from(subscription)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    responses(
+        (status = 200, description = "The subscriptions, oldest first", body = [SubscriptionResult]),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn list_subscriptions() -> Result<Json<Vec<SubscriptionResult>>, ErrorResponse> {
+    let subscriptions = Subscription::all().await?;
+
+    Ok(Json(
+        subscriptions
+            .into_iter()
+            .map(SubscriptionResult::from)
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the subscription")),
+    responses(
+        (status = 204, description = "The subscription was removed"),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 404, description = "Subscription not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn delete_subscription(Path(id): Path<Uuid>) -> Result<StatusCode, ErrorResponse> {
+    Subscription::delete(id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks/{id}/deliveries",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "The id of the subscription")),
+    responses(
+        (status = 200, description = "The deliveries of the subscription", body = [DeliveryResult]),
+        (status = 401, description = "The admin API key is missing or wrong", body = ErrorDetails),
+        (status = 404, description = "Subscription not found", body = ErrorDetails),
+    ),
+    security(("admin_key" = [])),
+)]
+#[axum::debug_handler]
+pub async fn get_deliveries(
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<DeliveryResult>>, ErrorResponse> {
+    // Check that the subscription exists
+    let subscription = Subscription::from_id(id).await?;
+    let deliveries = Delivery::for_subscription(subscription.id).await?;
+
+    Ok(Json(
+        deliveries.into_iter().map(DeliveryResult::from).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::models::webhook::publish;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let (status, Json(subscription)) = create_subscription(Json(json!({
+            "url": "http://127.0.0.1:1/hook",
+            "events": ["reward.redeemed"],
+            "secret": "secret",
+        })))
+        .await
+        .unwrap();
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(subscription.events, [WebhookEvent::RewardRedeemed]);
+
+        let Json(subscriptions) = list_subscriptions().await.unwrap();
+
+        assert!(subscriptions.iter().any(|s| s.id == subscription.id));
+
+        // Events are logged as deliveries of the subscription
+        let queued = publish(WebhookEvent::RewardRedeemed, json!({ "reward_id": "test" }))
+            .await
+            .unwrap();
+        let delivery_id = queued
+            .iter()
+            .find(|delivery| delivery.subscription_id == subscription.id)
+            .unwrap()
+            .id;
+        let Json(deliveries) = get_deliveries(Path(subscription.id)).await.unwrap();
+        let delivery = deliveries.iter().find(|d| d.id == delivery_id).unwrap();
+
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.payload["event"], "reward.redeemed");
+        assert_eq!(delivery.payload["data"]["reward_id"], "test");
+
+        assert_eq!(
+            delete_subscription(Path(subscription.id)).await.unwrap(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            get_deliveries(Path(subscription.id))
+                .await
+                .err()
+                .unwrap()
+                .status,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_subscription_invalid() {
+        let create = |events: serde_json::Value| {
+            create_subscription(Json(json!({
+                "url": "http://127.0.0.1:1/hook",
+                "events": events,
+                "secret": "secret",
+            })))
+        };
+
+        assert_eq!(
+            create(json!(["reward.unknown"]))
+                .await
+                .err()
+                .unwrap()
+                .status,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            create(json!([])).await.err().unwrap().status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
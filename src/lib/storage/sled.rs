@@ -3,25 +3,54 @@ use std::sync::RwLock;
 use lazy_static::lazy_static;
 use serde::de::DeserializeOwned;
 use sled::Db;
+use uuid::Uuid;
 
 use crate::core::repository::{Repository, RepositoryError};
 
+/// The first byte of a record stored with a layout version. Records stored
+/// before versions were recorded start with the length of an id instead.
+const VERSION_TAG: u8 = 0xff;
+
 /// Model is a trait that must be implemented by all models that are stored
 /// in the repository. This will allow blanket implementations of the
 /// repository for any struct that implements this trait.
 pub trait SledModel: serde::Serialize + DeserializeOwned {
+    /// The version of the layout the model is stored with. Bump it when the
+    /// layout changes and decode the older versions in `migrate`.
+    const VERSION: u8 = 1;
+
     /// Convert the model to a vector of bytes.
     fn to_vec(&self) -> Vec<u8> {
-        bincode::serialize(self).unwrap()
+        let mut v = vec![VERSION_TAG, Self::VERSION];
+        v.extend(bincode::serialize(self).unwrap());
+        v
     }
 
     /// Convert a vector of bytes to a model.
-    fn from_vec(v: Vec<u8>) -> Self {
-        // TODO: Handle errors
-        bincode::deserialize(&v).unwrap()
+    fn from_vec(v: Vec<u8>) -> Result<Self, RepositoryError> {
+        match v.as_slice() {
+            [VERSION_TAG, version, data @ ..] if *version == Self::VERSION => {
+                bincode::deserialize(data).map_err(|_| RepositoryError::ReadError)
+            }
+            [VERSION_TAG, version, data @ ..] => Self::migrate(*version, data),
+            data => Self::migrate(0, data),
+        }
+    }
+
+    /// Decode a record stored with an older layout version. Version 0 is a
+    /// record stored before versions were recorded, which is decoded with the
+    /// current layout unless the model overrides this.
+    fn migrate(version: u8, data: &[u8]) -> Result<Self, RepositoryError> {
+        match version {
+            0 => bincode::deserialize(data).map_err(|_| RepositoryError::ReadError),
+            _ => Err(RepositoryError::ReadError),
+        }
     }
 }
 
+/// Ids are stored on their own as index entries pointing to other records.
+impl SledModel for Uuid {}
+
 pub struct SledRepository {
     db: Db,
 }
@@ -30,6 +59,19 @@ impl SledRepository {
     pub fn new(db: Db) -> Self {
         Self { db }
     }
+
+    /// Get the keys of all records whose key starts with the prefix, ordered
+    /// by key, without reading the records.
+    pub fn keys(&self, prefix: &str) -> Result<Vec<String>, RepositoryError> {
+        self.db
+            .scan_prefix(prefix)
+            .keys()
+            .map(|key| {
+                let key = key.map_err(|_| RepositoryError::ReadError)?;
+                String::from_utf8(key.to_vec()).map_err(|_| RepositoryError::ReadError)
+            })
+            .collect()
+    }
 }
 
 impl<M: SledModel> Repository<M> for SledRepository {
@@ -42,7 +84,7 @@ impl<M: SledModel> Repository<M> for SledRepository {
 
     fn read(&self, key: String) -> Result<Option<M>, RepositoryError> {
         let result = self.db.get(key).map_err(|_| RepositoryError::ReadError)?;
-        Ok(result.map(|v| M::from_vec(v.to_vec())))
+        result.map(|v| M::from_vec(v.to_vec())).transpose()
     }
 
     fn update(&self, key: String, value: M) -> Result<(), RepositoryError> {
@@ -58,11 +100,23 @@ impl<M: SledModel> Repository<M> for SledRepository {
             .remove(key)
             .map_err(|_| RepositoryError::DeletionError)?;
         if let Some(result) = result {
-            Ok(M::from_vec(result.to_vec()))
+            M::from_vec(result.to_vec())
         } else {
             Err(RepositoryError::DeletionError)
         }
     }
+
+    fn scan(&self, prefix: String) -> Result<Vec<(String, M)>, RepositoryError> {
+        self.db
+            .scan_prefix(prefix)
+            .map(|result| {
+                let (key, value) = result.map_err(|_| RepositoryError::ReadError)?;
+                let key =
+                    String::from_utf8(key.to_vec()).map_err(|_| RepositoryError::ReadError)?;
+                Ok((key, M::from_vec(value.to_vec())?))
+            })
+            .collect()
+    }
 }
 
 lazy_static! {
@@ -130,6 +184,50 @@ mod tests {
         assert_eq!(deleted_value.data, updated_value.data);
     }
 
+    #[test]
+    fn test_scan() {
+        let config = Config::new().temporary(true);
+        let db = config.open().unwrap();
+        let repo = SledRepository::new(db);
+
+        for key in ["a:2", "b:1", "a:1"] {
+            let value = TestModel {
+                data: key.to_string(),
+            };
+            repo.create(key.to_string(), value).unwrap();
+        }
+
+        // Only records with the prefix are returned, ordered by key
+        let records: Vec<(String, TestModel)> = repo.scan("a:".to_string()).unwrap();
+        let keys: Vec<&str> = records.iter().map(|(key, _)| key.as_str()).collect();
+
+        assert_eq!(keys, vec!["a:1", "a:2"]);
+        assert_eq!(records[0].1.data, "a:1");
+        assert_eq!(repo.keys("a:").unwrap(), vec!["a:1", "a:2"]);
+    }
+
+    #[test]
+    fn test_versions() {
+        let value = TestModel {
+            data: "test data".to_string(),
+        };
+
+        // Records are stored with their layout version
+        let v = value.to_vec();
+        assert_eq!(v[..2], [VERSION_TAG, TestModel::VERSION]);
+        assert_eq!(TestModel::from_vec(v).unwrap().data, value.data);
+
+        // Records stored without a version are decoded with the current layout
+        let v = bincode::serialize(&value).unwrap();
+        assert_eq!(TestModel::from_vec(v).unwrap().data, value.data);
+
+        // Unknown versions and invalid records are errors
+        let mut v = value.to_vec();
+        v[1] = TestModel::VERSION + 1;
+        assert!(TestModel::from_vec(v).is_err());
+        assert!(TestModel::from_vec(vec![VERSION_TAG, TestModel::VERSION, 1]).is_err());
+    }
+
     #[test]
     fn test_get_sled_db() -> Result<(), Box<dyn std::error::Error>> {
         // Call the function
@@ -1,4 +1,4 @@
-use std::{env, ffi::OsString};
+use std::{env, ffi::OsString, str::FromStr};
 
 /// Parse the port from either the provided command line argument, or the
 /// `API_PORT` environment variable. If neither are set, use 3001.
@@ -17,6 +17,23 @@ pub fn parse_port(port: Option<OsString>) -> String {
     }
 }
 
+/// Parse a value from the environment variable `name`. If it is not set or
+/// cannot be parsed, use the default.
+pub fn parse_env<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Parse a value for a network, preferring `{name}_{chain_id}` over `name`
+/// so settings can be overridden per network.
+pub fn parse_network_env<T: FromStr>(name: &str, chain_id: u64, default: T) -> T {
+    let default = parse_env(name, default);
+
+    parse_env(&format!("{}_{}", name, chain_id), default)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -34,4 +51,28 @@ mod tests {
         let port = parse_port(None);
         assert_eq!(port, "3002");
     }
+
+    #[test]
+    fn test_parse_env() {
+        // Unset variables use the default
+        assert_eq!(parse_env("NFTEST_TEST_UNSET", 5u64), 5);
+
+        env::set_var("NFTEST_TEST_NUMBER", "12");
+        assert_eq!(parse_env("NFTEST_TEST_NUMBER", 5u64), 12);
+
+        // Invalid values use the default
+        env::set_var("NFTEST_TEST_INVALID", "twelve");
+        assert_eq!(parse_env("NFTEST_TEST_INVALID", 5u64), 5);
+    }
+
+    #[test]
+    fn test_parse_network_env() {
+        env::set_var("NFTEST_TEST_NETWORK", "1");
+        env::set_var("NFTEST_TEST_NETWORK_5", "2");
+
+        // Network values override the shared value
+        assert_eq!(parse_network_env("NFTEST_TEST_NETWORK", 5, 0u64), 2);
+        assert_eq!(parse_network_env("NFTEST_TEST_NETWORK", 1, 0u64), 1);
+        assert_eq!(parse_network_env("NFTEST_TEST_NETWORK_UNSET", 1, 3u64), 3);
+    }
 }
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use ethers::{
     core::{rand, rand::Rng},
     types::U256,
@@ -14,6 +16,14 @@ pub fn random_u256() -> U256 {
     upper + lower
 }
 
+/// Get the current unix timestamp in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -28,4 +38,13 @@ mod tests {
         assert_ne!(a, c, "Two random U256 numbers are equal");
         assert_ne!(b, c, "Two random U256 numbers are equal");
     }
+
+    #[test]
+    fn test_now() {
+        let a = now();
+        let b = now();
+
+        assert!(a > 0, "Timestamp should be after the epoch");
+        assert!(b >= a, "Timestamps should not go backwards");
+    }
 }
@@ -1,12 +1,30 @@
 use axum::{
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
 
-use crate::services::{
-    reward::redeem,
-    status::status,
-    user::{get_balance, register, reward},
+use crate::{
+    services::{
+        admin::{get_import, import_rewards, require_admin, sweep_rewards},
+        campaign::{create_campaign, get_campaign, set_campaign_status},
+        events::stream_events,
+        job::get_job,
+        openapi::{docs, openapi},
+        reward::{batch_reward, redeem, revoke, split, transfer},
+        status::status,
+        user::{
+            get_balance, get_history, get_rewards, get_withdrawals, register, reward, withdraw,
+        },
+        webhook::{create_subscription, delete_subscription, get_deliveries, list_subscriptions},
+    },
+    workers::{
+        expiry::{self, ExpiryConfig},
+        indexer::{Indexer, IndexerConfig},
+        jobs::{self, JobConfig},
+        outbox::{self, OutboxConfig},
+        webhooks::{self, WebhookConfig},
+    },
 };
 
 /// Get the base path for the API.
@@ -17,16 +35,96 @@ pub fn get_base_path() -> String {
 /// Initialize the router for the API.
 pub fn init_router() -> Router {
     let base_path = &get_base_path();
+    let admin = Router::new()
+        .route(
+            &format!("{base_path}/admin/rewards/import"),
+            post(import_rewards),
+        )
+        .route(
+            &format!("{base_path}/admin/rewards/import/:id"),
+            get(get_import),
+        )
+        .route(&format!("{base_path}/reward/:id/revoke"), post(revoke))
+        .route(&format!("{base_path}/reward/:id/transfer"), post(transfer))
+        .route(&format!("{base_path}/user/:id/withdraw"), post(withdraw))
+        .route(
+            &format!("{base_path}/admin/rewards/sweep"),
+            post(sweep_rewards),
+        )
+        .route(
+            &format!("{base_path}/admin/campaigns"),
+            post(create_campaign),
+        )
+        .route(
+            &format!("{base_path}/admin/campaigns/:id"),
+            get(get_campaign),
+        )
+        .route(
+            &format!("{base_path}/admin/campaigns/:id/status"),
+            post(set_campaign_status),
+        )
+        .route(
+            &format!("{base_path}/admin/webhooks"),
+            get(list_subscriptions).post(create_subscription),
+        )
+        .route(
+            &format!("{base_path}/admin/webhooks/:id"),
+            delete(delete_subscription),
+        )
+        .route(
+            &format!("{base_path}/admin/webhooks/:id/deliveries"),
+            get(get_deliveries),
+        )
+        .route_layer(middleware::from_fn(require_admin));
+
     Router::new()
         .route(&format!("{base_path}/status"), get(status))
+        .route(&format!("{base_path}/events"), get(stream_events))
+        .route(&format!("{base_path}/openapi.json"), get(openapi))
+        .route(&format!("{base_path}/docs"), get(docs))
         .route(&format!("{base_path}/user"), post(register))
         .route(&format!("{base_path}/user/:id/balance"), get(get_balance))
+        .route(&format!("{base_path}/user/:id/rewards"), get(get_rewards))
+        .route(&format!("{base_path}/user/:id/history"), get(get_history))
         .route(&format!("{base_path}/user/:id/reward"), post(reward))
+        .route(
+            &format!("{base_path}/user/:id/withdrawals"),
+            get(get_withdrawals),
+        )
         .route(&format!("{base_path}/reward/:id/redeem"), post(redeem))
+        .route(&format!("{base_path}/reward/:id/split"), post(split))
+        .route(&format!("{base_path}/rewards/batch"), post(batch_reward))
+        .route(&format!("{base_path}/jobs/:id"), get(get_job))
+        .merge(admin)
+}
+
+/// Start the background workers of the API.
+pub fn spawn_workers() {
+    // start the indexer in the background if it is enabled
+    let indexer_config = IndexerConfig::from_env();
+    if indexer_config.enabled {
+        let indexer = Indexer::new(indexer_config);
+        tokio::spawn(async move { indexer.run().await });
+    }
+
+    // recover outbox entries left unfinished by a restart or failure
+    tokio::spawn(outbox::run(OutboxConfig::from_env()));
+    // process rewards queued as jobs
+    tokio::spawn(jobs::run(JobConfig::from_env()));
+    // send queued webhook payloads to subscribed endpoints
+    tokio::spawn(webhooks::run(WebhookConfig::from_env()));
+
+    // burn the tokens of expired rewards if the sweeper is enabled
+    let expiry_config = ExpiryConfig::from_env();
+    if expiry_config.enabled {
+        tokio::spawn(expiry::run(expiry_config));
+    }
 }
 
 /// Initialize the server for the API and listen on the specified address.
 pub async fn init_server(bind_address: String) -> Result<(), std::io::Error> {
+    spawn_workers();
+
     // initialize our router and bind the address
     let app = init_router();
     let listener = tokio::net::TcpListener::bind(bind_address).await?;
@@ -36,12 +134,157 @@ pub async fn init_server(bind_address: String) -> Result<(), std::io::Error> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
+    use axum::http::StatusCode;
+    use utoipa::OpenApi;
+
+    use crate::services::openapi::ApiDoc;
+
     use super::*;
 
+    /// The routes that are not part of the OpenAPI document.
+    const UNDOCUMENTED: [&str; 2] = ["/openapi.json", "/docs"];
+
+    /// Get the operations of the OpenAPI document as `METHOD path` pairs.
+    fn documented() -> BTreeSet<(String, String)> {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(|method| (method.to_uppercase(), path.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Get the routes of `init_router` as `METHOD path` pairs, read from the
+    /// source of this file since a router does not list its routes.
+    fn routed() -> BTreeSet<(String, String)> {
+        let source = include_str!("router.rs");
+        let init_router = source
+            .split("pub fn init_router()")
+            .nth(1)
+            .and_then(|s| s.split("\n}\n").next())
+            .unwrap();
+        let mut routes = BTreeSet::new();
+
+        for route in init_router.split("\n        .route(").skip(1) {
+            let route = route.split("\n        .").next().unwrap();
+            let path = route
+                .split("{base_path}")
+                .nth(1)
+                .and_then(|s| s.split('"').next())
+                .unwrap();
+
+            if UNDOCUMENTED.contains(&path) {
+                continue;
+            }
+
+            // Path parameters are `:id` in the router and `{id}` in the spec
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(name) => format!("{{{}}}", name),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            for method in ["get", "post", "put", "patch", "delete"] {
+                if route.contains(&format!("{}(", method)) {
+                    routes.insert((
+                        method.to_uppercase(),
+                        format!("{}{}", get_base_path(), path),
+                    ));
+                }
+            }
+        }
+
+        routes
+    }
+
     #[test]
     fn test_get_base_path() {
         let base_path = get_base_path();
 
         assert!(base_path.contains("/api/v"));
     }
+
+    #[test]
+    fn test_openapi_matches_routes() {
+        let documented = documented();
+        let routed = routed();
+
+        assert!(!routed.is_empty());
+        assert_eq!(
+            routed.difference(&documented).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "routes missing from the OpenAPI document"
+        );
+        assert_eq!(
+            documented.difference(&routed).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "documented operations without a route"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_openapi_operations_are_routed() {
+        // Requests that match no route are answered by the fallback
+        let app = init_router().fallback(|| async { StatusCode::IM_A_TEAPOT });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+
+        for (method, path) in documented() {
+            let url = format!(
+                "http://{}{}",
+                address,
+                path.replace("{id}", &uuid::Uuid::new_v4().to_string())
+            );
+            let response = client
+                .request(method.parse().unwrap(), &url)
+                .send()
+                .await
+                .unwrap();
+
+            let status = response.status().as_u16();
+
+            assert!(
+                ![StatusCode::IM_A_TEAPOT, StatusCode::METHOD_NOT_ALLOWED]
+                    .map(|status| status.as_u16())
+                    .contains(&status),
+                "{} {} is not routed",
+                method,
+                path
+            );
+        }
+
+        // The served document is the generated one
+        let served: serde_json::Value = client
+            .get(format!(
+                "http://{}{}/openapi.json",
+                address,
+                get_base_path()
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(served, serde_json::to_value(ApiDoc::openapi()).unwrap());
+    }
 }
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::core::reward::RewardError;
+use crate::models::reward::RewardNFT;
+use crate::rewards::Reward;
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+/// ExpiryConfig controls how often expired rewards are swept.
+#[derive(Clone, Debug)]
+pub struct ExpiryConfig {
+    /// If the sweeper runs in the background.
+    pub enabled: bool,
+    /// How long to wait between sweeps.
+    pub interval: Duration,
+}
+
+impl ExpiryConfig {
+    /// Read the sweeper configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            enabled: parse_env("EXPIRY_SWEEP_ENABLED", true),
+            interval: Duration::from_secs(parse_env("EXPIRY_SWEEP_INTERVAL", 300)),
+        }
+    }
+}
+
+/// SweepFailure is an expired reward whose token could not be burned.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SweepFailure {
+    /// The id of the reward.
+    pub reward_id: String,
+    /// Why the burn failed.
+    pub error: String,
+}
+
+/// SweepReport is the outcome of a sweep.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SweepReport {
+    /// The number of expired rewards found.
+    pub checked: usize,
+    /// The ids of the rewards whose tokens were burned.
+    pub expired: Vec<String>,
+    /// The rewards whose tokens could not be burned. They are retried on the
+    /// next sweep.
+    pub failed: Vec<SweepFailure>,
+}
+
+/// Run the sweeper until the process exits.
+pub async fn run(config: ExpiryConfig) {
+    loop {
+        match sweep().await {
+            Ok(report) if report.checked > 0 => info!(
+                "Swept {} expired rewards, {} failed",
+                report.expired.len(),
+                report.failed.len()
+            ),
+            Ok(_) => {}
+            Err(e) => error!("Sweeper failed to load rewards: {:?}", e),
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+/// Burn the tokens of every expired reward with the admin wallet, without
+/// crediting their holders, and mark the rewards as expired.
+pub async fn sweep() -> Result<SweepReport, RewardError> {
+    let mut report = SweepReport::default();
+
+    for mut reward in RewardNFT::expired(now()).await? {
+        let reward_id = reward.get_id();
+
+        report.checked += 1;
+
+        match reward.expire().await {
+            Ok(()) => report.expired.push(reward_id),
+            Err(e) => {
+                error!("Sweeper failed to expire reward {}: {:?}", reward_id, e);
+                report.failed.push(SweepFailure {
+                    reward_id,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+    use uuid::Uuid;
+
+    use crate::models::reward::RewardStatus;
+    use crate::models::user::User;
+    use crate::utils::helpers::random_u256;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sweep() {
+        let owner = User::new(Uuid::new_v4(), "test".to_string());
+        let expired =
+            RewardNFT::new(owner.clone(), U256::from(100), random_u256()).with_expiry(Some(now()));
+        let active =
+            RewardNFT::new(owner, U256::from(100), random_u256()).with_expiry(Some(now() + 3600));
+
+        expired.save(true).await.unwrap();
+        active.save(true).await.unwrap();
+
+        let report = sweep().await.unwrap();
+
+        // Only the expired reward is swept
+        assert!(report.expired.contains(&expired.get_id()));
+        assert!(!report.expired.contains(&active.get_id()));
+
+        let saved = RewardNFT::from_id(expired.get_id()).await.unwrap();
+
+        assert_eq!(saved.get_status(), RewardStatus::Expired);
+        assert!(!saved.is_redeemed());
+
+        // Swept rewards are not swept again
+        let report = sweep().await.unwrap();
+
+        assert!(!report.expired.contains(&expired.get_id()));
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use ethers::types::{Address, Log, H256, U256};
+use log::error;
+
+use crate::core::chain::{
+    get_block_hash, get_block_number, get_reward_balance, get_reward_nft_address,
+    get_reward_token_address, get_transfer_logs,
+};
+use crate::core::reward::RewardError;
+use crate::models::indexer::{Checkpoint, IndexerError, TokenBalance};
+use crate::models::reward::RewardNFT;
+use crate::models::user::User;
+use crate::utils::config::parse_env;
+
+/// IndexerConfig controls how the indexer follows the chain.
+#[derive(Clone, Debug)]
+pub struct IndexerConfig {
+    /// If the indexer should be started with the server.
+    pub enabled: bool,
+    /// The first block to index when there is no checkpoint.
+    pub start_block: u64,
+    /// How many blocks a block must be buried under before it is indexed,
+    /// and how many blocks to rewind when a reorg is detected anyway.
+    pub reorg_depth: u64,
+    /// The maximum number of blocks to fetch logs for at once.
+    pub batch_size: u64,
+    /// How long to wait between polls once the indexer has caught up.
+    pub poll_interval: Duration,
+}
+
+impl IndexerConfig {
+    /// Read the indexer configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            enabled: parse_env("INDEXER_ENABLED", false),
+            start_block: parse_env("INDEXER_START_BLOCK", 0),
+            reorg_depth: parse_env("INDEXER_REORG_DEPTH", 12),
+            batch_size: parse_env("INDEXER_BATCH_SIZE", 1000).max(1),
+            poll_interval: Duration::from_secs(parse_env("INDEXER_POLL_INTERVAL", 5)),
+        }
+    }
+}
+
+/// Transfer is a decoded `Transfer` event of either reward contract. For the
+/// NFT `value` is the token id, for the token it is the amount transferred.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transfer {
+    pub contract: Address,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+impl Transfer {
+    /// Decode a `Transfer` event log. ERC721 indexes the token id as the
+    /// third topic while ERC20 puts the amount in the data.
+    pub fn from_log(log: &Log) -> Option<Self> {
+        let from = Address::from(*log.topics.get(1)?);
+        let to = Address::from(*log.topics.get(2)?);
+        let value = match log.topics.get(3) {
+            Some(token_id) => U256::from_big_endian(token_id.as_bytes()),
+            None if log.data.len() == 32 => U256::from_big_endian(&log.data),
+            None => return None,
+        };
+
+        Some(Self {
+            contract: log.address,
+            from,
+            to,
+            value,
+        })
+    }
+}
+
+/// Get the first block to index given the current checkpoint and the hash the
+/// chain now reports for the checkpoint block. If the hashes differ the block
+/// was reorged out, so the indexer rewinds and processes the blocks again.
+pub fn next_block(
+    checkpoint: Option<&Checkpoint>,
+    canonical_hash: Option<H256>,
+    config: &IndexerConfig,
+) -> u64 {
+    match checkpoint {
+        Some(checkpoint) if canonical_hash == Some(checkpoint.hash) => checkpoint.block + 1,
+        Some(checkpoint) => checkpoint
+            .block
+            .saturating_sub(config.reorg_depth)
+            .max(config.start_block),
+        None => config.start_block,
+    }
+}
+
+/// Get the last block that may be indexed. Blocks newer than the reorg depth
+/// can still be reorged out, and changes applied from them would not be
+/// reverted, so they are left until they are confirmed.
+pub fn confirmed_block(latest_block: u64, config: &IndexerConfig) -> u64 {
+    latest_block.saturating_sub(config.reorg_depth)
+}
+
+/// Apply a reward NFT transfer to the reward it belongs to. Tokens without a
+/// reward record were minted outside of the API and are ignored.
+pub async fn apply_nft_transfer(transfer: &Transfer) -> Result<(), IndexerError> {
+    let mut reward = match RewardNFT::from_token_id(transfer.value).await {
+        Ok(reward) => reward,
+        Err(RewardError::NotFound) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if transfer.to == Address::zero() {
+        // The token was burned, and redeemed unless it was burned because it
+        // expired
+        if !reward.is_burned() {
+            reward.set_redeemed(true);
+        }
+    } else {
+        // Only registered users can become the owner of a reward
+        let owner = User::from_address(transfer.to)
+            .await
+            .ok()
+            .map(|user| user.id);
+        reward.transfer_to(transfer.to, owner);
+    }
+
+    reward.save(false).await?;
+
+    Ok(())
+}
+
+/// Indexer follows the `Transfer` events of the reward contracts and syncs
+/// the rewards and cached token balances in the repository.
+pub struct Indexer {
+    config: IndexerConfig,
+    nft_address: Address,
+    token_address: Address,
+}
+
+impl Indexer {
+    /// Create a new indexer for the configured reward contracts.
+    pub fn new(config: IndexerConfig) -> Self {
+        Self {
+            config,
+            nft_address: get_reward_nft_address(),
+            token_address: get_reward_token_address(),
+        }
+    }
+
+    /// Run the indexer until the process exits.
+    pub async fn run(&self) {
+        loop {
+            match self.sync().await {
+                // Keep going while there are blocks left to index
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => error!("Indexer failed to sync: {:?}", e),
+            }
+
+            tokio::time::sleep(self.config.poll_interval).await;
+        }
+    }
+
+    /// Index the next batch of confirmed blocks. Returns whether there are
+    /// blocks left.
+    pub async fn sync(&self) -> Result<bool, IndexerError> {
+        let latest_block = confirmed_block(get_block_number().await?, &self.config);
+        let checkpoint = Checkpoint::load().await?;
+        let canonical_hash = match &checkpoint {
+            Some(checkpoint) => get_block_hash(checkpoint.block).await?,
+            None => None,
+        };
+        let from_block = next_block(checkpoint.as_ref(), canonical_hash, &self.config);
+
+        if from_block > latest_block {
+            return Ok(false);
+        }
+
+        let to_block = latest_block.min(from_block + self.config.batch_size - 1);
+        let logs = get_transfer_logs(
+            vec![self.nft_address, self.token_address],
+            from_block,
+            to_block,
+        )
+        .await?;
+
+        // Balances are read from the chain rather than summed from the events
+        // so processing a block again after a reorg does not count it twice
+        let mut touched = HashSet::new();
+
+        for transfer in logs.iter().filter_map(Transfer::from_log) {
+            if transfer.contract == self.nft_address {
+                apply_nft_transfer(&transfer).await?;
+            } else if transfer.contract == self.token_address {
+                touched.insert(transfer.from);
+                touched.insert(transfer.to);
+            }
+        }
+
+        touched.remove(&Address::zero());
+
+        for address in touched {
+            let balance = get_reward_balance(address).await?;
+            TokenBalance::new(address, balance, to_block).save().await?;
+        }
+
+        if let Some(hash) = get_block_hash(to_block).await? {
+            Checkpoint::new(to_block, hash).save().await?;
+        }
+
+        Ok(to_block < latest_block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::signers::Signer;
+    use ethers::types::Bytes;
+    use uuid::Uuid;
+
+    use crate::core::chain::generate_secret_key;
+    use crate::rewards::Reward;
+    use crate::utils::helpers::random_u256;
+
+    use super::*;
+
+    fn config() -> IndexerConfig {
+        IndexerConfig {
+            enabled: true,
+            start_block: 10,
+            reorg_depth: 5,
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    fn transfer_log(from: Address, to: Address, token_id: Option<U256>, data: Vec<u8>) -> Log {
+        let mut topics = vec![H256::random(), H256::from(from), H256::from(to)];
+
+        if let Some(token_id) = token_id {
+            let mut bytes = [0u8; 32];
+            token_id.to_big_endian(&mut bytes);
+            topics.push(H256::from(bytes));
+        }
+
+        Log {
+            address: Address::random(),
+            topics,
+            data: Bytes::from(data),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transfer_from_log() {
+        let from = Address::random();
+        let to = Address::random();
+
+        // ERC721 transfers have the token id as a topic
+        let log = transfer_log(from, to, Some(U256::from(7)), vec![]);
+        let transfer = Transfer::from_log(&log).unwrap();
+
+        assert_eq!(transfer.from, from);
+        assert_eq!(transfer.to, to);
+        assert_eq!(transfer.value, U256::from(7));
+
+        // ERC20 transfers have the amount as data
+        let mut data = [0u8; 32];
+        U256::from(1000).to_big_endian(&mut data);
+        let log = transfer_log(from, to, None, data.to_vec());
+        let transfer = Transfer::from_log(&log).unwrap();
+
+        assert_eq!(transfer.value, U256::from(1000));
+
+        // Logs without a value cannot be decoded
+        let log = transfer_log(from, to, None, vec![]);
+
+        assert!(Transfer::from_log(&log).is_none());
+    }
+
+    #[test]
+    fn test_next_block() {
+        let config = config();
+        let hash = H256::random();
+        let checkpoint = Checkpoint::new(100, hash);
+
+        // Without a checkpoint start from the configured block
+        assert_eq!(next_block(None, None, &config), 10);
+        // Continue after the checkpoint if it is still canonical
+        assert_eq!(next_block(Some(&checkpoint), Some(hash), &config), 101);
+        // Rewind if the checkpoint block was reorged out
+        assert_eq!(
+            next_block(Some(&checkpoint), Some(H256::random()), &config),
+            95
+        );
+        assert_eq!(next_block(Some(&checkpoint), None, &config), 95);
+
+        // Never rewind before the start block
+        let checkpoint = Checkpoint::new(12, hash);
+
+        assert_eq!(next_block(Some(&checkpoint), None, &config), 10);
+    }
+
+    #[test]
+    fn test_confirmed_block() {
+        let config = config();
+
+        // Only blocks buried under the reorg depth are indexed
+        assert_eq!(confirmed_block(100, &config), 95);
+        assert_eq!(confirmed_block(3, &config), 0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_nft_transfer() {
+        let owner = User::new(Uuid::new_v4(), generate_secret_key());
+        let reward = RewardNFT::new(owner.clone(), U256::from(100), random_u256());
+        reward.save(true).await.unwrap();
+
+        // Transfer the token to a registered user
+        let recipient = User::new(Uuid::new_v4(), generate_secret_key());
+        let recipient_address = recipient.get_wallet().unwrap().address();
+        recipient.save().await.unwrap();
+
+        let transfer = Transfer {
+            contract: Address::random(),
+            from: owner.get_wallet().unwrap().address(),
+            to: recipient_address,
+            value: reward.get_token_id(),
+        };
+        apply_nft_transfer(&transfer).await.unwrap();
+
+        let updated = RewardNFT::from_token_id(reward.get_token_id())
+            .await
+            .unwrap();
+
+        assert_eq!(updated.get_owner(), recipient.id);
+        assert_eq!(updated.get_holder(), Some(recipient_address));
+        assert!(!updated.is_redeemed());
+
+        // Burn the token
+        let transfer = Transfer {
+            to: Address::zero(),
+            from: recipient_address,
+            ..transfer
+        };
+        apply_nft_transfer(&transfer).await.unwrap();
+
+        let updated = RewardNFT::from_token_id(reward.get_token_id())
+            .await
+            .unwrap();
+
+        assert!(updated.is_redeemed());
+
+        // Unknown tokens are ignored
+        let transfer = Transfer {
+            value: random_u256(),
+            ..transfer
+        };
+
+        assert!(apply_nft_transfer(&transfer).await.is_ok());
+    }
+}
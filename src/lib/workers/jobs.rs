@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use log::error;
+use tokio::sync::{Notify, Semaphore};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::models::job::{Job, JobError, JobStatus};
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+lazy_static! {
+    /// Wakes the job worker when a new job is queued.
+    static ref JOB_QUEUED: Notify = Notify::new();
+}
+
+/// Wake the job worker so a newly queued job starts without waiting for the
+/// next poll.
+pub fn notify_queued() {
+    JOB_QUEUED.notify_one();
+}
+
+/// JobConfig controls how jobs are processed.
+#[derive(Clone, Debug)]
+pub struct JobConfig {
+    /// The maximum number of jobs processed at once.
+    pub concurrency: usize,
+    /// How many times a job is attempted before it fails.
+    pub max_attempts: u32,
+    /// How long to wait before retrying a failed job.
+    pub retry_delay: Duration,
+    /// How long to wait between checks for queued jobs.
+    pub poll_interval: Duration,
+}
+
+impl JobConfig {
+    /// Read the job configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            concurrency: parse_env("JOB_CONCURRENCY", 4).max(1),
+            max_attempts: parse_env("JOB_MAX_ATTEMPTS", 3).max(1),
+            retry_delay: Duration::from_secs(parse_env("JOB_RETRY_DELAY", 30)),
+            poll_interval: Duration::from_secs(parse_env("JOB_POLL_INTERVAL", 5)),
+        }
+    }
+}
+
+/// Check if a queued job can be started.
+pub fn is_ready(job: &Job, now: u64, in_flight: &HashSet<Uuid>) -> bool {
+    job.status == JobStatus::Queued && job.run_after <= now && !in_flight.contains(&job.id)
+}
+
+/// Queue jobs that were running when the process stopped.
+pub async fn requeue_running() -> Result<usize, JobError> {
+    let jobs = Job::with_status(JobStatus::Running).await?;
+    let count = jobs.len();
+
+    for mut job in jobs {
+        job.status = JobStatus::Queued;
+        job.save().await?;
+    }
+
+    Ok(count)
+}
+
+/// Start every ready job, waiting for a free worker when all are busy.
+async fn dispatch(
+    config: &JobConfig,
+    workers: &Arc<Semaphore>,
+    in_flight: &Arc<Mutex<HashSet<Uuid>>>,
+) -> Result<Vec<JoinHandle<()>>, JobError> {
+    let mut handles = vec![];
+
+    for mut job in Job::with_status(JobStatus::Queued).await? {
+        if !is_ready(&job, now(), &in_flight.lock().unwrap()) {
+            continue;
+        }
+
+        let permit = workers
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("job worker semaphore closed");
+
+        in_flight.lock().unwrap().insert(job.id);
+
+        let in_flight = in_flight.clone();
+        let max_attempts = config.max_attempts;
+        let retry_delay = config.retry_delay.as_secs();
+
+        handles.push(tokio::spawn(async move {
+            if let Err(e) = job.run(max_attempts, retry_delay).await {
+                error!("Failed to run job {}: {:?}", job.id, e);
+            }
+
+            in_flight.lock().unwrap().remove(&job.id);
+            drop(permit);
+        }));
+    }
+
+    Ok(handles)
+}
+
+/// Run the job worker until the process exits.
+pub async fn run(config: JobConfig) {
+    let workers = Arc::new(Semaphore::new(config.concurrency));
+    let in_flight = Arc::new(Mutex::new(HashSet::new()));
+
+    if let Err(e) = requeue_running().await {
+        error!("Failed to queue interrupted jobs: {:?}", e);
+    }
+
+    loop {
+        if let Err(e) = dispatch(&config, &workers, &in_flight).await {
+            error!("Failed to load queued jobs: {:?}", e);
+        }
+
+        tokio::select! {
+            _ = JOB_QUEUED.notified() => {}
+            _ = tokio::time::sleep(config.poll_interval) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::*;
+
+    #[test]
+    fn test_is_ready() {
+        let job = Job::new(Uuid::new_v4(), U256::from(100));
+        let mut in_flight = HashSet::new();
+
+        assert!(is_ready(&job, job.run_after, &in_flight));
+        // Jobs waiting for a retry are not ready
+        assert!(!is_ready(&job, job.run_after - 1, &in_flight));
+
+        // Jobs already started are not ready
+        in_flight.insert(job.id);
+        assert!(!is_ready(&job, job.run_after, &in_flight));
+
+        // Only queued jobs are ready
+        let job = Job {
+            status: JobStatus::Succeeded,
+            ..job
+        };
+        assert!(!is_ready(&job, job.run_after, &HashSet::new()));
+    }
+
+    #[tokio::test]
+    async fn test_requeue_running() {
+        let job = Job {
+            status: JobStatus::Running,
+            ..Job::new(Uuid::new_v4(), U256::from(100))
+        };
+        job.save().await.unwrap();
+
+        assert!(requeue_running().await.unwrap() >= 1);
+        assert_eq!(
+            Job::from_id(job.id).await.unwrap().status,
+            JobStatus::Queued
+        );
+    }
+}
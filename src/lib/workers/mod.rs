@@ -0,0 +1,6 @@
+pub mod expiry;
+pub mod indexer;
+pub mod jobs;
+pub mod outbox;
+pub mod reconcile;
+pub mod webhooks;
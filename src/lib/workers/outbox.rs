@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use log::error;
+
+use crate::core::reward::RewardError;
+use crate::models::outbox::OutboxEntry;
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+/// OutboxConfig controls how the outbox worker recovers unfinished entries.
+#[derive(Clone, Debug)]
+pub struct OutboxConfig {
+    /// How long to wait between checks for unfinished entries.
+    pub poll_interval: Duration,
+    /// How long an entry must be idle before the worker takes it over. Entries
+    /// leased by a request are left alone until their lease expires.
+    pub retry_after: Duration,
+}
+
+impl OutboxConfig {
+    /// Read the outbox configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(parse_env("OUTBOX_POLL_INTERVAL", 10)),
+            retry_after: Duration::from_secs(parse_env("OUTBOX_RETRY_AFTER", 60)),
+        }
+    }
+}
+
+/// Run the outbox worker until the process exits.
+pub async fn run(config: OutboxConfig) {
+    loop {
+        if let Err(e) = process_unfinished(&config).await {
+            error!("Outbox failed to load entries: {:?}", e);
+        }
+
+        tokio::time::sleep(config.poll_interval).await;
+    }
+}
+
+/// Process every unfinished entry that has been idle long enough. Returns the
+/// number of entries that were confirmed.
+pub async fn process_unfinished(config: &OutboxConfig) -> Result<usize, RewardError> {
+    let mut confirmed = 0;
+
+    for mut entry in OutboxEntry::unfinished().await? {
+        if now().saturating_sub(entry.updated_at) < config.retry_after.as_secs() {
+            continue;
+        }
+
+        match entry.process().await {
+            Ok(()) => confirmed += 1,
+            // Still waiting to be sent, or held by another process
+            Err(RewardError::Queued(_)) => {}
+            Err(e) => error!("Outbox failed to process entry {}: {:?}", entry.id, e),
+        }
+    }
+
+    Ok(confirmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Address;
+    use uuid::Uuid;
+
+    use crate::models::outbox::{OutboxAction, OutboxStatus};
+    use crate::models::reward::RewardNFT;
+    use crate::models::user::User;
+    use crate::rewards::Reward;
+    use crate::utils::helpers::random_u256;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process_unfinished() {
+        let owner = User::new(Uuid::new_v4(), "test".to_string());
+        let reward = RewardNFT::new(owner, 100.into(), random_u256());
+        let mut entry = OutboxEntry::new(OutboxAction::Mint {
+            reward: Box::new(reward.clone()),
+            to: Address::random(),
+        });
+        entry.save().await.unwrap();
+
+        // Recently updated entries are left alone
+        let config = OutboxConfig {
+            poll_interval: Duration::from_secs(1),
+            retry_after: Duration::from_secs(3600),
+        };
+        process_unfinished(&config).await.unwrap();
+
+        assert_eq!(
+            OutboxEntry::from_id(entry.id).await.unwrap().status,
+            OutboxStatus::Pending
+        );
+
+        // Idle entries are recovered
+        let config = OutboxConfig {
+            retry_after: Duration::from_secs(0),
+            ..config
+        };
+        process_unfinished(&config).await.unwrap();
+
+        assert_eq!(
+            OutboxEntry::from_id(entry.id).await.unwrap().status,
+            OutboxStatus::Confirmed
+        );
+        assert!(RewardNFT::from_id(reward.get_id()).await.is_ok());
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::HashSet;
+
+use ethers::signers::Signer;
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::core::chain::{
+    check_token_exists, get_block_number, get_reward_nft_address, get_token_owner,
+    get_token_reward_value, get_transfer_logs, ChainError,
+};
+use crate::core::reward::RewardError;
+use crate::models::indexer::{IndexerError, MintCursor};
+use crate::models::outbox::{OutboxAction, OutboxEntry};
+use crate::models::reward::{RewardNFT, RewardStatus};
+use crate::models::user::{User, UserError};
+use crate::rewards::Reward;
+use crate::workers::indexer::{confirmed_block, IndexerConfig, Transfer};
+
+#[derive(Debug, Error)]
+pub enum ReconcileError {
+    #[error("Reward error")]
+    RewardError(#[from] RewardError),
+    #[error("User error")]
+    UserError(#[from] UserError),
+    #[error("Unknown error")]
+    UnknownError(#[from] std::io::Error),
+    #[error("Chain error")]
+    ChainError(#[from] ChainError),
+    #[error("Indexer error")]
+    IndexerError(#[from] IndexerError),
+}
+
+/// TokenState is the on-chain state of a reward NFT.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenState {
+    /// If the token exists, i.e. it was minted and not burned.
+    pub exists: bool,
+    /// The reward value of the token.
+    pub value: U256,
+    /// The owner of the token.
+    pub owner: Address,
+}
+
+/// DiscrepancyKind describes how a reward differs from the chain.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum DiscrepancyKind {
+    /// The reward is unredeemed but the token does not exist on-chain.
+    MissingToken,
+    /// The value of the reward differs from the value of the token.
+    ValueMismatch { expected: U256, actual: U256 },
+    /// The token is not held by the owner of the reward.
+    OwnerMismatch { expected: Address, actual: Address },
+    /// The token exists on-chain but is not tracked as an unredeemed reward.
+    OrphanToken { owner: Address },
+}
+
+/// Discrepancy is a single difference found between the repository and the
+/// chain.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Discrepancy {
+    /// The id of the reward, if the token has a reward record.
+    pub reward_id: Option<String>,
+    /// The token id of the reward.
+    pub token_id: U256,
+    #[serde(flatten)]
+    pub kind: DiscrepancyKind,
+    /// If the repository was repaired to match the chain.
+    pub repaired: bool,
+}
+
+/// ReconcileReport is the outcome of a reconciliation run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// The number of tokens checked.
+    pub checked: usize,
+    /// The discrepancies that were found.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// Compare a reward with the on-chain state of its token. The owner is only
+/// compared if the expected owner address is known.
+pub fn compare(
+    reward: &RewardNFT,
+    state: &TokenState,
+    expected_owner: Option<Address>,
+) -> Vec<DiscrepancyKind> {
+    let mut discrepancies = vec![];
+
+    if reward.is_burned() {
+        if state.exists {
+            discrepancies.push(DiscrepancyKind::OrphanToken { owner: state.owner });
+        }
+    } else if !state.exists {
+        discrepancies.push(DiscrepancyKind::MissingToken);
+    } else {
+        if reward.get_value() != state.value {
+            discrepancies.push(DiscrepancyKind::ValueMismatch {
+                expected: reward.get_value(),
+                actual: state.value,
+            });
+        }
+
+        match expected_owner {
+            Some(expected) if expected != state.owner => {
+                discrepancies.push(DiscrepancyKind::OwnerMismatch {
+                    expected,
+                    actual: state.owner,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    discrepancies
+}
+
+/// Repair a reward so it matches the chain. The new owner is the registered
+/// user holding the token, if any. Returns if the reward was repaired; a token
+/// that still exists is only repaired for a redeemed reward, expired, revoked
+/// and split rewards are only reported.
+pub fn repair(reward: &mut RewardNFT, kind: &DiscrepancyKind, new_owner: Option<Uuid>) -> bool {
+    match kind {
+        DiscrepancyKind::MissingToken => reward.set_redeemed(true),
+        DiscrepancyKind::ValueMismatch { actual, .. } => reward.set_value(*actual),
+        DiscrepancyKind::OwnerMismatch { actual, .. } => reward.transfer_to(*actual, new_owner),
+        DiscrepancyKind::OrphanToken { .. } => match reward.get_status() {
+            RewardStatus::Redeemed => reward.set_redeemed(false),
+            _ => return false,
+        },
+    }
+
+    true
+}
+
+/// Read the on-chain state of a token.
+async fn get_token_state(token_id: U256) -> Result<TokenState, ReconcileError> {
+    if !check_token_exists(token_id).await? {
+        return Ok(TokenState {
+            exists: false,
+            value: U256::zero(),
+            owner: Address::zero(),
+        });
+    }
+
+    Ok(TokenState {
+        exists: true,
+        value: get_token_reward_value(token_id).await?,
+        owner: get_token_owner(token_id).await?,
+    })
+}
+
+/// Get the address the token of a reward is expected to be held by.
+async fn get_expected_owner(reward: &RewardNFT) -> Option<Address> {
+    match reward.get_holder() {
+        Some(holder) => Some(holder),
+        None => User::from_id(reward.get_owner().to_string())
+            .await
+            .ok()
+            .and_then(|user| user.get_wallet().ok())
+            .map(|wallet| wallet.address()),
+    }
+}
+
+/// Get the ids of the tokens minted by the reward NFT contract between two
+/// blocks.
+async fn get_mints(
+    config: &IndexerConfig,
+    mut from_block: u64,
+    latest_block: u64,
+) -> Result<Vec<U256>, ReconcileError> {
+    let nft_address = get_reward_nft_address();
+    let mut token_ids = vec![];
+
+    while from_block <= latest_block {
+        let to_block = latest_block.min(from_block + config.batch_size - 1);
+        let logs = get_transfer_logs(vec![nft_address], from_block, to_block).await?;
+
+        token_ids.extend(
+            logs.iter()
+                .filter_map(Transfer::from_log)
+                .filter(|transfer| transfer.from == Address::zero())
+                .map(|transfer| transfer.value),
+        );
+
+        from_block = to_block + 1;
+    }
+
+    Ok(token_ids)
+}
+
+/// Get the ids of all tokens minted by the reward NFT contract. Only blocks
+/// after the stored cursor are scanned. The cursor is advanced to the blocks
+/// deeper than the reorg depth, newer blocks are scanned again on every run.
+async fn get_minted_token_ids(config: &IndexerConfig) -> Result<Vec<U256>, ReconcileError> {
+    let latest_block = get_block_number().await?;
+    let confirmed_block = confirmed_block(latest_block, config);
+    let mut cursor = MintCursor::load().await?;
+    let from_block = cursor.block.map_or(config.start_block, |block| block + 1);
+
+    if from_block <= confirmed_block {
+        let token_ids = get_mints(config, from_block, confirmed_block).await?;
+
+        cursor.token_ids.extend(token_ids);
+        cursor.block = Some(confirmed_block);
+        cursor.save().await?;
+    }
+
+    let from_block = from_block.max(confirmed_block + 1);
+    let mut token_ids = cursor.token_ids;
+
+    token_ids.extend(get_mints(config, from_block, latest_block).await?);
+
+    Ok(token_ids)
+}
+
+/// Get the ids of the tokens minted by unfinished outbox entries. Their
+/// records are only created once the mint is confirmed, so until then the
+/// tokens are not orphans.
+fn pending_mints(entries: &[OutboxEntry]) -> HashSet<U256> {
+    entries
+        .iter()
+        .filter(|entry| !entry.is_finished())
+        .flat_map(|entry| match &entry.action {
+            OutboxAction::Mint { reward, .. } => vec![reward.get_token_id()],
+            OutboxAction::Split { children, .. } => {
+                children.iter().map(RewardNFT::get_token_id).collect()
+            }
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// Reconcile all rewards in the repository with the chain. If `apply` is set
+/// the repository is repaired to match the chain; the chain is never changed.
+pub async fn reconcile(apply: bool) -> Result<ReconcileReport, ReconcileError> {
+    let mut report = ReconcileReport::default();
+    let rewards = RewardNFT::all().await?;
+    let mut known: HashSet<U256> = rewards.iter().map(|r| r.get_token_id()).collect();
+
+    known.extend(pending_mints(&OutboxEntry::unfinished().await?));
+
+    for mut reward in rewards {
+        let token_id = reward.get_token_id();
+        let state = get_token_state(token_id).await?;
+        let expected_owner = get_expected_owner(&reward).await;
+        let kinds = compare(&reward, &state, expected_owner);
+
+        report.checked += 1;
+
+        if kinds.is_empty() {
+            continue;
+        }
+
+        let mut repaired = vec![false; kinds.len()];
+
+        if apply {
+            let new_owner = User::from_address(state.owner).await.ok().map(|u| u.id);
+
+            for (kind, repaired) in kinds.iter().zip(repaired.iter_mut()) {
+                *repaired = repair(&mut reward, kind, new_owner);
+            }
+
+            reward.save(false).await?;
+        }
+
+        report
+            .discrepancies
+            .extend(
+                kinds
+                    .into_iter()
+                    .zip(repaired)
+                    .map(|(kind, repaired)| Discrepancy {
+                        reward_id: Some(reward.get_id()),
+                        token_id,
+                        kind,
+                        repaired,
+                    }),
+            );
+    }
+
+    // Tokens minted outside of the API have no reward record at all
+    for token_id in get_minted_token_ids(&IndexerConfig::from_env()).await? {
+        if known.contains(&token_id) {
+            continue;
+        }
+
+        let state = get_token_state(token_id).await?;
+
+        report.checked += 1;
+
+        if !state.exists {
+            continue;
+        }
+
+        let mut discrepancy = Discrepancy {
+            reward_id: None,
+            token_id,
+            kind: DiscrepancyKind::OrphanToken { owner: state.owner },
+            repaired: false,
+        };
+
+        // A record can only be created if the token is held by a user
+        if apply {
+            if let Ok(user) = User::from_address(state.owner).await {
+                let reward = RewardNFT::new(user, state.value, token_id);
+                reward.save(true).await?;
+
+                discrepancy.reward_id = Some(reward.get_id());
+                discrepancy.repaired = true;
+            }
+        }
+
+        report.discrepancies.push(discrepancy);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::outbox::OutboxStatus;
+    use crate::models::reward::generate_reward;
+    use crate::utils::helpers::random_u256;
+
+    use super::*;
+
+    fn state(exists: bool, value: u128, owner: Address) -> TokenState {
+        TokenState {
+            exists,
+            value: U256::from(value),
+            owner,
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let owner = Address::random();
+        let other = Address::random();
+        let mut reward = generate_reward(100);
+
+        // Matching state has no discrepancies
+        assert!(compare(&reward, &state(true, 100, owner), Some(owner)).is_empty());
+        // The owner is not compared when it is unknown
+        assert!(compare(&reward, &state(true, 100, other), None).is_empty());
+
+        // A burned token is missing
+        assert_eq!(
+            compare(&reward, &state(false, 0, Address::zero()), Some(owner)),
+            vec![DiscrepancyKind::MissingToken]
+        );
+
+        // Value and owner are both compared
+        assert_eq!(
+            compare(&reward, &state(true, 50, other), Some(owner)),
+            vec![
+                DiscrepancyKind::ValueMismatch {
+                    expected: U256::from(100),
+                    actual: U256::from(50),
+                },
+                DiscrepancyKind::OwnerMismatch {
+                    expected: owner,
+                    actual: other,
+                },
+            ]
+        );
+
+        // A redeemed reward must not exist on-chain
+        reward.set_redeemed(true);
+
+        assert!(compare(&reward, &state(false, 0, Address::zero()), Some(owner)).is_empty());
+        assert_eq!(
+            compare(&reward, &state(true, 100, owner), Some(owner)),
+            vec![DiscrepancyKind::OrphanToken { owner }]
+        );
+    }
+
+    #[test]
+    fn test_repair() {
+        let holder = Address::random();
+        let new_owner = Uuid::new_v4();
+        let mut reward = generate_reward(100);
+
+        assert!(repair(&mut reward, &DiscrepancyKind::MissingToken, None));
+        assert!(reward.is_redeemed());
+
+        let redeemed_value = reward.get_redeemed_value();
+        let orphan = DiscrepancyKind::OrphanToken { owner: holder };
+
+        // A redeemed reward is restored without losing the burn details
+        assert!(repair(&mut reward, &orphan, None));
+        assert!(!reward.is_redeemed());
+        assert_eq!(reward.get_redeemed_value(), redeemed_value);
+
+        // Other burned rewards are only reported
+        for status in [
+            RewardStatus::Expired,
+            RewardStatus::Revoked,
+            RewardStatus::Split,
+        ] {
+            reward.set_status(status);
+
+            assert!(!repair(&mut reward, &orphan, None));
+            assert_eq!(reward.get_status(), status);
+        }
+
+        reward.set_status(RewardStatus::Active);
+
+        let kind = DiscrepancyKind::ValueMismatch {
+            expected: U256::from(100),
+            actual: U256::from(50),
+        };
+        assert!(repair(&mut reward, &kind, None));
+        assert_eq!(reward.get_value(), U256::from(50));
+
+        let kind = DiscrepancyKind::OwnerMismatch {
+            expected: Address::random(),
+            actual: holder,
+        };
+        assert!(repair(&mut reward, &kind, Some(new_owner)));
+        assert_eq!(reward.get_holder(), Some(holder));
+        assert_eq!(reward.get_owner(), new_owner);
+    }
+
+    #[test]
+    fn test_pending_mints() {
+        let reward = generate_reward(100);
+        let child = generate_reward(50);
+        let mut confirmed = OutboxEntry::new(OutboxAction::Mint {
+            reward: Box::new(generate_reward(100)),
+            to: Address::random(),
+        });
+        confirmed.status = OutboxStatus::Confirmed;
+
+        let entries = vec![
+            OutboxEntry::new(OutboxAction::Mint {
+                reward: Box::new(reward.clone()),
+                to: Address::random(),
+            }),
+            OutboxEntry::new(OutboxAction::Split {
+                reward_id: Uuid::new_v4(),
+                token_id: random_u256(),
+                redeemed: U256::zero(),
+                children: vec![child.clone()],
+            }),
+            OutboxEntry::new(OutboxAction::Burn {
+                reward_id: Uuid::new_v4(),
+                token_id: random_u256(),
+            }),
+        ];
+
+        // The tokens of unfinished mints and splits are not orphans yet
+        assert_eq!(
+            pending_mints(&entries),
+            HashSet::from([reward.get_token_id(), child.get_token_id()])
+        );
+        assert!(pending_mints(&[confirmed]).is_empty());
+    }
+
+    #[test]
+    fn test_discrepancy_serialization() {
+        let discrepancy = Discrepancy {
+            reward_id: None,
+            token_id: U256::from(1),
+            kind: DiscrepancyKind::MissingToken,
+            repaired: false,
+        };
+        let json = serde_json::to_value(&discrepancy).unwrap();
+
+        // The kind is flattened into the discrepancy
+        assert_eq!(json["kind"], "MissingToken");
+    }
+}
@@ -0,0 +1,306 @@
+use std::time::Duration;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Request};
+use hyper_rustls::HttpsConnectorBuilder;
+use lazy_static::lazy_static;
+use log::error;
+use tokio::sync::Notify;
+
+use crate::core::retry::RetryPolicy;
+use crate::models::webhook::{Delivery, DeliveryStatus, Subscription, WebhookError};
+use crate::utils::config::parse_env;
+use crate::utils::helpers::now;
+
+/// The header carrying the name of the event.
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+/// The header carrying the id of the delivery, which stays the same when a
+/// payload is sent again.
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// The header carrying the unix timestamp the payload was signed at.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// The header carrying the signature of the payload, `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+lazy_static! {
+    /// Wakes the webhook worker when a delivery is queued.
+    static ref DELIVERY_QUEUED: Notify = Notify::new();
+}
+
+/// Wake the webhook worker so a newly queued delivery is sent without
+/// waiting for the next poll.
+pub fn notify_queued() {
+    DELIVERY_QUEUED.notify_one();
+}
+
+/// WebhookConfig controls how webhook payloads are delivered.
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    /// How many times a payload is sent and how long to wait between
+    /// attempts.
+    pub retry: RetryPolicy,
+    /// How long to wait for an endpoint to respond.
+    pub timeout: Duration,
+    /// How long to wait between checks for due deliveries.
+    pub poll_interval: Duration,
+}
+
+impl WebhookConfig {
+    /// Read the webhook configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            retry: RetryPolicy {
+                max_attempts: parse_env("WEBHOOK_MAX_ATTEMPTS", 8).max(1),
+                base_delay: Duration::from_secs(parse_env("WEBHOOK_RETRY_BASE_DELAY", 10)),
+                max_delay: Duration::from_secs(parse_env("WEBHOOK_RETRY_MAX_DELAY", 3600)),
+            },
+            timeout: Duration::from_secs(parse_env("WEBHOOK_TIMEOUT", 10)),
+            poll_interval: Duration::from_secs(parse_env("WEBHOOK_POLL_INTERVAL", 5)),
+        }
+    }
+}
+
+/// Run the webhook worker until the process exits.
+pub async fn run(config: WebhookConfig) {
+    loop {
+        if let Err(e) = deliver_due(&config).await {
+            error!("Failed to load webhook deliveries: {:?}", e);
+        }
+
+        tokio::select! {
+            _ = DELIVERY_QUEUED.notified() => {}
+            _ = tokio::time::sleep(config.poll_interval) => {}
+        }
+    }
+}
+
+/// Send every delivery that is due, returning how many were sent.
+pub async fn deliver_due(config: &WebhookConfig) -> Result<usize, WebhookError> {
+    let deliveries = Delivery::due(now()).await?;
+    let count = deliveries.len();
+
+    for mut delivery in deliveries {
+        if let Err(e) = deliver(&mut delivery, config).await {
+            error!("Failed to deliver webhook {}: {:?}", delivery.id, e);
+        }
+    }
+
+    Ok(count)
+}
+
+/// Send a delivery to its subscription. A payload that is not accepted is
+/// sent again after a backoff until the attempts run out. Deliveries to
+/// removed or paused subscriptions fail without being sent.
+pub async fn deliver(delivery: &mut Delivery, config: &WebhookConfig) -> Result<(), WebhookError> {
+    let subscription = match Subscription::from_id(delivery.subscription_id).await {
+        Ok(subscription) if subscription.active => subscription,
+        Ok(_) => return fail(delivery, "Subscription is paused".into()).await,
+        Err(WebhookError::NotFound) => {
+            return fail(delivery, "Subscription was removed".into()).await
+        }
+        Err(e) => return Err(e),
+    };
+
+    delivery.attempts += 1;
+
+    match send(&subscription, delivery, config.timeout).await {
+        Ok(status) if (200..300).contains(&status) => {
+            delivery.status = DeliveryStatus::Delivered;
+            delivery.response_status = Some(status);
+            delivery.error = None;
+        }
+        result => {
+            let error = match result {
+                Ok(status) => {
+                    delivery.response_status = Some(status);
+                    format!("Endpoint responded with status {}", status)
+                }
+                Err(e) => {
+                    delivery.response_status = None;
+                    e
+                }
+            };
+
+            if delivery.attempts >= config.retry.max_attempts {
+                return fail(delivery, error).await;
+            }
+
+            delivery.error = Some(error);
+            delivery.next_attempt_at =
+                now() + config.retry.delay(delivery.attempts).as_secs().max(1);
+        }
+    }
+
+    delivery.save().await
+}
+
+/// Mark a delivery as failed.
+async fn fail(delivery: &mut Delivery, error: String) -> Result<(), WebhookError> {
+    delivery.status = DeliveryStatus::Failed;
+    delivery.error = Some(error);
+
+    delivery.save().await
+}
+
+/// Post the signed payload of a delivery and return the status of the
+/// response.
+async fn send(
+    subscription: &Subscription,
+    delivery: &Delivery,
+    timeout: Duration,
+) -> Result<u16, String> {
+    let timestamp = now();
+    let signature = subscription.sign(timestamp, &delivery.payload);
+
+    let request = Request::post(&subscription.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, delivery.event.name())
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+        .body(Body::from(delivery.payload.clone()))
+        .map_err(|e| format!("Failed to build request: {}", e))?;
+    let connector = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+
+    let response =
+        tokio::time::timeout(timeout, Client::builder().build(connector).request(request))
+            .await
+            .map_err(|_| "Failed to send payload: timed out".to_string())?
+            .map_err(|e| format!("Failed to send payload: {}", e))?;
+
+    Ok(response.status().as_u16())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+
+    use crate::models::webhook::WebhookEvent;
+
+    use super::*;
+
+    /// A payload received by the test endpoint.
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// Start a local endpoint that responds with the given statuses in turn,
+    /// returning its URL and the payloads it received.
+    async fn receiver(statuses: Vec<StatusCode>) -> (String, Received) {
+        let received: Received = Arc::default();
+        let statuses = Arc::new(Mutex::new(statuses.into_iter()));
+        let app = {
+            let received = received.clone();
+
+            Router::new().route(
+                "/hook",
+                post(move |headers: HeaderMap, body: String| async move {
+                    received.lock().unwrap().push((headers, body));
+                    statuses.lock().unwrap().next().unwrap_or(StatusCode::OK)
+                }),
+            )
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (url, received)
+    }
+
+    fn config(max_attempts: u32) -> WebhookConfig {
+        WebhookConfig {
+            retry: RetryPolicy {
+                max_attempts,
+                base_delay: Duration::from_secs(10),
+                max_delay: Duration::from_secs(60),
+            },
+            timeout: Duration::from_secs(5),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    async fn queue(url: String) -> (Subscription, Delivery) {
+        let subscription =
+            Subscription::new(url, vec![WebhookEvent::RewardMinted], "secret".into()).unwrap();
+        subscription.save().await.unwrap();
+
+        let mut delivery = Delivery::new(
+            subscription.id,
+            WebhookEvent::RewardMinted,
+            serde_json::json!({ "reward_id": "test" }),
+        );
+        delivery.save().await.unwrap();
+
+        (subscription, delivery)
+    }
+
+    #[tokio::test]
+    async fn test_deliver() {
+        let (url, received) = receiver(vec![StatusCode::INTERNAL_SERVER_ERROR]).await;
+        let (subscription, mut delivery) = queue(url).await;
+
+        // A rejected payload is retried after a backoff
+        deliver(&mut delivery, &config(3)).await.unwrap();
+
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.response_status, Some(500));
+        assert!(delivery.next_attempt_at > now());
+
+        deliver(&mut delivery, &config(3)).await.unwrap();
+
+        let saved = Delivery::from_id(delivery.id).await.unwrap();
+
+        assert_eq!(saved.status, DeliveryStatus::Delivered);
+        assert_eq!(saved.attempts, 2);
+
+        // The payload is signed with the secret of the subscription
+        let (headers, body) = received.lock().unwrap()[1].clone();
+        let header = |name: &str| headers.get(name).unwrap().to_str().unwrap();
+        let timestamp: u64 = header(TIMESTAMP_HEADER).parse().unwrap();
+
+        assert_eq!(body, delivery.payload);
+        assert_eq!(header(EVENT_HEADER), "reward.minted");
+        assert_eq!(header(DELIVERY_HEADER), delivery.id.to_string());
+        assert_eq!(
+            header(SIGNATURE_HEADER),
+            format!("sha256={}", subscription.sign(timestamp, &body))
+        );
+
+        Subscription::delete(subscription.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_deliver_fails_after_max_attempts() {
+        let (url, _) = receiver(vec![StatusCode::BAD_GATEWAY; 2]).await;
+        let (subscription, mut delivery) = queue(url).await;
+
+        deliver(&mut delivery, &config(2)).await.unwrap();
+        deliver(&mut delivery, &config(2)).await.unwrap();
+
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 2);
+
+        // Deliveries to removed subscriptions fail without being sent
+        Subscription::delete(subscription.id).await.unwrap();
+
+        let mut delivery = Delivery::new(
+            subscription.id,
+            WebhookEvent::RewardMinted,
+            serde_json::json!({}),
+        );
+        deliver(&mut delivery, &config(2)).await.unwrap();
+
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 0);
+    }
+}
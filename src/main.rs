@@ -1,18 +1,118 @@
 use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
+use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
+use env_logger::Env;
 
+use nftest::models::import::{validate, GrantImport, ImportError, ImportFormat};
+use nftest::models::migration::backfill_indexes;
 use nftest::utils::router::init_server;
+use nftest::workers::expiry::sweep;
+use nftest::workers::reconcile::reconcile;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the API server (default)
+    Serve,
+    /// Compare the rewards in the database with the chain
+    Reconcile {
+        /// Repair the database to match the chain
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Manage rewards
+    Reward {
+        #[command(subcommand)]
+        command: RewardCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum RewardCommand {
+    /// Issue the rewards of a CSV or JSONL grant list. Importing the same
+    /// list again resumes an interrupted import.
+    Import {
+        /// The grant list, with a .csv or .jsonl extension
+        file: PathBuf,
+        /// Validate the list and report its issues without issuing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Burn the tokens of expired rewards without crediting their holders
+    Sweep,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv().expect(".env file not found");
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+
+    // Records stored by earlier versions are indexed before they are looked
+    // up
+    backfill_indexes().await?;
+
+    match Cli::parse().command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            // get the port from the environment, or use 3001 if it's not set
+            let api_port = env::var("API_PORT").unwrap_or_else(|_| "3001".into());
+            // create a string for our bind address
+            let bind_address = format!("0.0.0.0:{api_port}");
+
+            Ok(init_server(bind_address).await?)
+        }
+        Command::Reconcile { repair } => {
+            let report = reconcile(repair).await?;
+
+            println!("{}", serde_json::to_string_pretty(&report)?);
+
+            Ok(())
+        }
+        Command::Reward {
+            command: RewardCommand::Import { file, dry_run },
+        } => {
+            let format = ImportFormat::from_path(&file)?;
+            let content = std::fs::read_to_string(&file)?;
+
+            if dry_run {
+                let (_, report) = validate(&content, format).await?;
+
+                println!("{}", serde_json::to_string_pretty(&report)?);
+
+                return Ok(());
+            }
+
+            let mut import = match GrantImport::start(&content, format).await {
+                Ok(import) => import,
+                Err(ImportError::Invalid(report)) => {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+
+                    return Err(ImportError::Invalid(report).into());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let progress = import.run().await?;
+
+            println!("{}", serde_json::to_string_pretty(&progress)?);
+
+            Ok(())
+        }
+        Command::Reward {
+            command: RewardCommand::Sweep,
+        } => {
+            let report = sweep().await?;
 
-    // get the port from the environment, or use 3001 if it's not set
-    let api_port = env::var("API_PORT").unwrap_or_else(|_| "3001".into());
-    // create a string for our bind address
-    let bind_address = format!("0.0.0.0:{api_port}");
+            println!("{}", serde_json::to_string_pretty(&report)?);
 
-    Ok(init_server(bind_address).await?)
+            Ok(())
+        }
+    }
 }
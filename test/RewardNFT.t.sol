@@ -104,4 +104,271 @@ contract RewardNFTTest is Test {
          */
         assertEq(reward.balanceOf(initialOwner), initialBalance + rewardValue);
     }
-}
\ No newline at end of file
+
+    function testForfeit() public {
+        uint256 tokenId = 1;
+        uint256 initialBalance = reward.balanceOf(initialOwner);
+
+        /**
+         * Mint the NFT with the reward value
+         */
+        rewardNFT.safeMint(initialOwner, tokenId, "https://example.com", 100);
+        /**
+         * Forfeit the NFT
+         */
+        rewardNFT.forfeit(tokenId);
+
+        /**
+         * The NFT is burned
+         */
+        assertFalse(rewardNFT.checkIfTokenExist(tokenId));
+        /**
+         * No reward value is minted
+         */
+        assertEq(reward.balanceOf(initialOwner), initialBalance);
+    }
+
+    function testForfeitOnlyOwner() public {
+        uint256 tokenId = 1;
+        address holder = address(0xBEEF);
+
+        rewardNFT.safeMint(holder, tokenId, "https://example.com", 100);
+
+        /**
+         * The holder cannot forfeit the NFT
+         */
+        vm.prank(holder);
+        vm.expectRevert();
+        rewardNFT.forfeit(tokenId);
+
+        assertTrue(rewardNFT.checkIfTokenExist(tokenId));
+    }
+
+    function testSplit() public {
+        uint256 tokenId = 1;
+        uint256 initialBalance = reward.balanceOf(initialOwner);
+
+        rewardNFT.safeMint(initialOwner, tokenId, "https://example.com", 100);
+
+        uint256[] memory newTokenIds = new uint256[](2);
+        newTokenIds[0] = 2;
+        newTokenIds[1] = 3;
+        uint256[] memory values = new uint256[](2);
+        values[0] = 30;
+        values[1] = 30;
+        string[] memory uris = new string[](2);
+        uris[0] = "https://example.com/2";
+        uris[1] = "https://example.com/3";
+
+        /**
+         * Redeem 40 and split the rest into two NFTs
+         */
+        rewardNFT.split(tokenId, 40, newTokenIds, values, uris);
+
+        /**
+         * The NFT is burned and the new NFTs carry the rest of the value
+         */
+        assertFalse(rewardNFT.checkIfTokenExist(tokenId));
+        assertEq(rewardNFT.ownerOf(2), initialOwner);
+        assertEq(rewardNFT.getRewardValue(2), 30);
+        assertEq(rewardNFT.getRewardValue(3), 30);
+        /**
+         * Only the redeemed value is minted
+         */
+        assertEq(reward.balanceOf(initialOwner), initialBalance + 40);
+    }
+
+    function testSplitInvalidTotal() public {
+        uint256 tokenId = 1;
+
+        rewardNFT.safeMint(initialOwner, tokenId, "https://example.com", 100);
+
+        uint256[] memory newTokenIds = new uint256[](1);
+        newTokenIds[0] = 2;
+        uint256[] memory values = new uint256[](1);
+        values[0] = 70;
+        string[] memory uris = new string[](1);
+        uris[0] = "https://example.com/2";
+
+        /**
+         * The split must add up to the value of the NFT
+         */
+        vm.expectRevert();
+        rewardNFT.split(tokenId, 40, newTokenIds, values, uris);
+
+        assertTrue(rewardNFT.checkIfTokenExist(tokenId));
+    }
+
+    /**
+     * Sign a burn authorization with a private key, matching the
+     * authorizations signed by the API
+     */
+    function signBurnAuthorization(
+        uint256 privateKey,
+        address owner,
+        uint256 tokenId,
+        uint256 nonce,
+        uint256 deadline
+    ) internal view returns (bytes memory) {
+        bytes32 domainSeparator = keccak256(
+            abi.encode(
+                keccak256(
+                    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"
+                ),
+                keccak256("RewardNFT"),
+                keccak256("1"),
+                block.chainid,
+                address(rewardNFT)
+            )
+        );
+        bytes32 structHash = keccak256(
+            abi.encode(
+                keccak256(
+                    "BurnAuthorization(address owner,uint256 tokenId,uint256 nonce,uint256 deadline)"
+                ),
+                owner,
+                tokenId,
+                nonce,
+                deadline
+            )
+        );
+        bytes32 digest = keccak256(
+            abi.encodePacked("\x19\x01", domainSeparator, structHash)
+        );
+        (uint8 v, bytes32 r, bytes32 s) = vm.sign(privateKey, digest);
+
+        return abi.encodePacked(r, s, v);
+    }
+
+    function testBurnWithAuthorization() public {
+        uint256 privateKey = 0xA11CE;
+        address holder = vm.addr(privateKey);
+        uint256 tokenId = 1;
+        uint256 deadline = block.timestamp + 300;
+
+        rewardNFT.safeMint(holder, tokenId, "https://example.com", 100);
+
+        bytes memory signature = signBurnAuthorization(
+            privateKey,
+            holder,
+            tokenId,
+            0,
+            deadline
+        );
+
+        /**
+         * Anyone can relay the burn with the holder's signature
+         */
+        vm.prank(address(0xBEEF));
+        rewardNFT.burnWithAuthorization(
+            holder,
+            tokenId,
+            0,
+            deadline,
+            signature
+        );
+
+        /**
+         * The NFT is burned, the reward value is minted to the holder and the
+         * nonce is used up
+         */
+        assertFalse(rewardNFT.checkIfTokenExist(tokenId));
+        assertEq(reward.balanceOf(holder), 100);
+        assertEq(rewardNFT.burnNonces(holder), 1);
+    }
+
+    function testBurnWithAuthorizationReplay() public {
+        uint256 privateKey = 0xA11CE;
+        address holder = vm.addr(privateKey);
+        uint256 deadline = block.timestamp + 300;
+
+        rewardNFT.safeMint(holder, 1, "https://example.com", 100);
+        rewardNFT.safeMint(holder, 2, "https://example.com", 100);
+
+        bytes memory signature = signBurnAuthorization(
+            privateKey,
+            holder,
+            1,
+            0,
+            deadline
+        );
+        rewardNFT.burnWithAuthorization(holder, 1, 0, deadline, signature);
+
+        /**
+         * The used nonce cannot be signed again for another token
+         */
+        signature = signBurnAuthorization(privateKey, holder, 2, 0, deadline);
+
+        vm.expectRevert("Error: Invalid nonce!");
+        rewardNFT.burnWithAuthorization(holder, 2, 0, deadline, signature);
+
+        assertTrue(rewardNFT.checkIfTokenExist(2));
+    }
+
+    function testBurnWithAuthorizationInvalid() public {
+        uint256 privateKey = 0xA11CE;
+        address holder = vm.addr(privateKey);
+        uint256 tokenId = 1;
+        uint256 deadline = block.timestamp + 300;
+
+        rewardNFT.safeMint(holder, tokenId, "https://example.com", 100);
+
+        /**
+         * A signature by anyone but the holder is rejected
+         */
+        bytes memory signature = signBurnAuthorization(
+            0xB0B,
+            holder,
+            tokenId,
+            0,
+            deadline
+        );
+
+        vm.expectRevert("Error: Invalid authorization!");
+        rewardNFT.burnWithAuthorization(
+            holder,
+            tokenId,
+            0,
+            deadline,
+            signature
+        );
+
+        /**
+         * The signature does not carry over to another token
+         */
+        signature = signBurnAuthorization(privateKey, holder, 2, 0, deadline);
+
+        vm.expectRevert("Error: Invalid authorization!");
+        rewardNFT.burnWithAuthorization(
+            holder,
+            tokenId,
+            0,
+            deadline,
+            signature
+        );
+
+        /**
+         * Expired authorizations are rejected
+         */
+        signature = signBurnAuthorization(
+            privateKey,
+            holder,
+            tokenId,
+            0,
+            deadline
+        );
+        vm.warp(deadline + 1);
+
+        vm.expectRevert("Error: Authorization expired!");
+        rewardNFT.burnWithAuthorization(
+            holder,
+            tokenId,
+            0,
+            deadline,
+            signature
+        );
+
+        assertTrue(rewardNFT.checkIfTokenExist(tokenId));
+        assertEq(rewardNFT.burnNonces(holder), 0);
+    }
+}
@@ -11,6 +11,8 @@ use nftest::core::chain::get_reward_nft_contract;
 use nftest::core::chain::get_reward_token_contract;
 use nftest::core::chain::get_wallet_from_secret_key;
 use nftest::core::chain::mint_nft_reward;
+use nftest::models::job::JobStatus;
+use nftest::services::job::JobResult;
 use nftest::services::reward::RedeemResult;
 use nftest::services::user::BalanceResult;
 use nftest::services::user::RegisterRequest;
@@ -171,7 +173,11 @@ async fn test_full_flow() {
 
     // Reward the user with an NFT
     let value = 1337;
-    let request = RewardRequest { value };
+    let request = RewardRequest {
+        value: value.into(),
+        campaign_id: None,
+        expires_at: None,
+    };
     let result = client
         .post(&format!("{}/user/{}/reward", api_path, user_id))
         .json(&request)
@@ -217,3 +223,70 @@ async fn test_full_flow() {
     // Check that the balance has been updated with the redeemed reward
     assert_eq!(result.balance, value.to_string());
 }
+
+#[tokio::test]
+async fn test_async_reward() {
+    // Deploy the contracts
+    helpers::deploy_contracts().await.unwrap();
+    // Start a new test server
+    let api_path = helpers::get_test_base_path().await;
+    let client = reqwest::Client::new();
+
+    // Register a new user
+    let user_id = Uuid::new_v4();
+    let request = RegisterRequest { id: user_id };
+    let result = client
+        .post(format!("{}/user", api_path))
+        .json(&request)
+        .send()
+        .await;
+
+    // Ensure the registration was successful
+    assert!(result.is_ok());
+
+    // Queue the reward
+    let request = RewardRequest {
+        value: 42.into(),
+        campaign_id: None,
+        expires_at: None,
+    };
+    let result = client
+        .post(format!("{}/user/{}/reward?async=true", api_path, user_id))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    // Ensure the reward was accepted
+    assert_eq!(result.status(), reqwest::StatusCode::ACCEPTED);
+
+    let job = result.json::<JobResult>().await.unwrap();
+
+    // Poll the job until it is finished
+    let mut status = job.status;
+    let mut result = None;
+
+    for _ in 0..30 {
+        let job = client
+            .get(format!("{}/jobs/{}", api_path, job.id))
+            .send()
+            .await
+            .unwrap()
+            .json::<JobResult>()
+            .await
+            .unwrap();
+
+        status = job.status;
+        result = job.result;
+
+        if matches!(status, JobStatus::Succeeded | JobStatus::Failed) {
+            break;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+
+    // Check that the reward was issued
+    assert_eq!(status, JobStatus::Succeeded);
+    assert!(result.unwrap().success);
+}
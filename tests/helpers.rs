@@ -8,6 +8,7 @@ use axum::Router;
 use dotenvy::dotenv;
 use nftest::utils::router::get_base_path;
 use nftest::utils::router::init_router;
+use nftest::utils::router::spawn_workers;
 use tokio::sync::OnceCell;
 // use ethers::utils::Anvil;
 use ethers::{abi::Abi, core::k256::ecdsa::SigningKey, prelude::*};
@@ -90,6 +91,9 @@ async fn start_server(router: Router) -> SocketAddr {
 
     let socket_addr = listener.local_addr().expect("could not get socket address");
 
+    // Start the background workers alongside the server
+    spawn_workers();
+
     tokio::spawn(async move {
         axum::serve(listener, router.into_make_service())
             .await